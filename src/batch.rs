@@ -0,0 +1,339 @@
+//! The `batch` subcommand: run the GAF stage over many samples with shared
+//! options, summarizing each sample on one line of a combined table.
+//!
+//! Each row of the manifest is run as its own `gfa_recomb` process with `--json`,
+//! so a sample that fails (a missing file, a malformed GAF) doesn't stop the
+//! others. Rows run concurrently with the `parallel` cargo feature.
+
+use anyhow::{Context, Result};
+use bstr::ByteSlice;
+use clap::ArgMatches;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::gfa::GraphIndex;
+
+/// A row of the batch manifest.
+#[derive(Debug, Clone, PartialEq)]
+struct BatchRow {
+    /// 1-based line of the row in the manifest.
+    line: usize,
+    gfa: PathBuf,
+    gaf: PathBuf,
+    /// Focal node IDs, ranges or globs, one per line.
+    nodes: PathBuf,
+}
+
+impl BatchRow {
+    /// The sample name: the GAF file name without its extension.
+    fn sample(&self) -> String {
+        self.gaf
+            .file_stem()
+            .map_or_else(|| self.gaf.to_string_lossy(), |stem| stem.to_string_lossy())
+            .into_owned()
+    }
+}
+
+/// The GAF stage aggregates of one sample, as reported in its JSON.
+#[derive(Debug, Clone, PartialEq)]
+struct SampleSummary {
+    sample: String,
+    pairs: usize,
+    /// The remaining aggregates, formatted as in the JSON report.
+    values: Vec<String>,
+}
+
+/// The JSON fields of the summary, in table order.
+const SUMMARY_FIELDS: [&str; 5] = [
+    "recombination_potential",
+    "rci",
+    "recombination_rate",
+    "mean_entropy",
+    "total_entropy",
+];
+
+/// Run the `batch` subcommand.
+pub fn run(args: &ArgMatches) -> Result<()> {
+    let manifest = args
+        .get_one::<PathBuf>("MANIFEST")
+        .expect("MANIFEST required");
+    let shared: Vec<String> = args
+        .get_many::<String>("ARGS")
+        .map(|args| args.cloned().collect())
+        .unwrap_or_default();
+    let text = std::fs::read_to_string(manifest)
+        .with_context(|| format!("Failed to read batch manifest: {:?}", manifest.as_os_str()))?;
+    let base = manifest.parent().unwrap_or(Path::new(""));
+    let rows = parse_manifest(&text, base)
+        .with_context(|| format!("Malformed batch manifest: {:?}", manifest.as_os_str()))?;
+
+    let exe = std::env::current_exe().context("Failed to locate the gfa_recomb executable")?;
+    let results = crate::parallel::map(&rows, |row| run_row(&exe, row, &shared));
+
+    let mut out = io::stdout().lock();
+    writeln!(out, "sample\tpairs\t{}", SUMMARY_FIELDS.join("\t"))?;
+    let mut failures = Vec::new();
+    for (row, result) in rows.iter().zip(results) {
+        match result {
+            Ok(summary) => writeln!(
+                out,
+                "{}\t{}\t{}",
+                summary.sample,
+                summary.pairs,
+                summary.values.join("\t")
+            )?,
+            Err(e) => failures.push((row, e)),
+        }
+    }
+    out.flush()?;
+
+    for (row, e) in &failures {
+        eprintln!(
+            "Error: sample {} (manifest line {}): {:#}",
+            row.sample(),
+            row.line,
+            e
+        );
+    }
+    if !failures.is_empty() {
+        anyhow::bail!("{} of {} batch row(s) failed", failures.len(), rows.len());
+    }
+    Ok(())
+}
+
+/// Parse a manifest of `gfa_path`, `gaf_path` and `nodes_path` columns. Blank lines
+/// and lines starting with `#` are ignored, and relative paths are relative to `base`,
+/// the manifest's directory.
+fn parse_manifest(text: &str, base: &Path) -> Result<Vec<BatchRow>> {
+    let mut rows = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [gfa, gaf, nodes] = fields[..] else {
+            anyhow::bail!(
+                "line {}: expected gfa_path, gaf_path and nodes_path columns, found {}",
+                i + 1,
+                fields.len()
+            );
+        };
+        rows.push(BatchRow {
+            line: i + 1,
+            gfa: base.join(gfa),
+            gaf: base.join(gaf),
+            nodes: base.join(nodes),
+        });
+    }
+    Ok(rows)
+}
+
+/// Read focal node IDs, one per line. Blank lines and lines starting with `#` are
+/// ignored.
+fn load_nodes(path: &Path) -> Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read nodes file: {:?}", path.as_os_str()))?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Whether a nodes file entry is a range such as `u25-u30` or a glob such as `u2*`.
+fn is_pattern(entry: &str) -> bool {
+    entry.contains(['*', '?']) || parse_range(entry).is_some()
+}
+
+/// Split a range such as `u25-u30`, or `u25-30`, into its shared prefix and its
+/// inclusive bounds.
+fn parse_range(entry: &str) -> Option<(&str, u64, u64)> {
+    let (start, end) = entry.split_once('-')?;
+    let digits = start.len() - start.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let (prefix, from) = start.split_at(start.len() - digits);
+    let to = end.strip_prefix(prefix).unwrap_or(end);
+    if from.is_empty() || to.is_empty() || !to.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((prefix, from.parse().ok()?, to.parse().ok()?))
+}
+
+/// Whether `name` is matched by the range or glob `pattern`. Range bounds are
+/// compared as numbers, so `u8-u10` matches `u9` and `u09` alike.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    if let Some((prefix, from, to)) = parse_range(pattern) {
+        return name
+            .strip_prefix(prefix)
+            .filter(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|n| n.parse::<u64>().ok())
+            .is_some_and(|n| (from..=to).contains(&n));
+    }
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+    regex::Regex::new(&regex).is_ok_and(|r| r.is_match(name))
+}
+
+/// Replace the ranges and globs among `entries` by the `segments` they match, in
+/// GFA order. An entry naming a segment exactly is kept as is, even if it looks
+/// like a range, and a pattern matching no segment is dropped with a warning.
+fn expand_nodes(entries: Vec<String>, segments: &[String], nodes: &Path) -> Vec<String> {
+    let mut expanded = Vec::new();
+    for entry in entries {
+        if !is_pattern(&entry) || segments.contains(&entry) {
+            expanded.push(entry);
+            continue;
+        }
+        let before = expanded.len();
+        expanded.extend(
+            segments
+                .iter()
+                .filter(|name| matches_pattern(&entry, name))
+                .cloned(),
+        );
+        if expanded.len() == before {
+            eprintln!(
+                "Warning: {} in {:?} matches no segment",
+                entry,
+                nodes.as_os_str()
+            );
+        }
+    }
+    expanded
+}
+
+/// Run the GAF stage on `row` with the `shared` options, in a process of its own.
+fn run_row(exe: &Path, row: &BatchRow, shared: &[String]) -> Result<SampleSummary> {
+    let mut nodes = load_nodes(&row.nodes)?;
+    if nodes.iter().any(|entry| is_pattern(entry)) {
+        // patterns are matched against the names in the sample's own GFA
+        let index = GraphIndex::load(&row.gfa, false, None, false)?;
+        let segments: Vec<String> = index
+            .segments
+            .iter()
+            .map(|(id, _)| id.to_str_lossy().into_owned())
+            .collect();
+        nodes = expand_nodes(nodes, &segments, &row.nodes);
+    }
+    if nodes.is_empty() {
+        anyhow::bail!("no focal nodes in {:?}", row.nodes.as_os_str());
+    }
+    let output = Command::new(exe)
+        .arg(&row.gfa)
+        .arg("--gaf")
+        .arg(&row.gaf)
+        .arg("--focal")
+        .arg(nodes.join(","))
+        .arg("--json")
+        .args(shared)
+        .output()
+        .context("Failed to run gfa_recomb")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr.lines().last().unwrap_or("no error message");
+        anyhow::bail!("{}", message.trim_start_matches("Error: "));
+    }
+
+    let report: serde_json::Value =
+        serde_json::from_slice(&output.stdout).context("Failed to parse the JSON report")?;
+    summarize_report(row.sample(), &report)
+}
+
+/// The summary line of a sample's JSON report.
+fn summarize_report(sample: String, report: &serde_json::Value) -> Result<SampleSummary> {
+    let gaf = &report["gaf"];
+    let pairs = gaf["pairs"]
+        .as_array()
+        .context("JSON report without GAF pairs")?
+        .len();
+    let values = SUMMARY_FIELDS
+        .iter()
+        .map(|field| match &gaf[field] {
+            serde_json::Value::Number(n) => Ok(n.to_string()),
+            _ => anyhow::bail!("JSON report without {}", field),
+        })
+        .collect::<Result<_>>()?;
+    Ok(SampleSummary {
+        sample,
+        pairs,
+        values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let text = "# samples\na.gfa\ta.gaf\ta.txt\n\n/data/b.gfa\tb.gaf\tb.txt\n";
+        let rows = parse_manifest(text, Path::new("runs")).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(rows[0].gaf, Path::new("runs/a.gaf"));
+        assert_eq!(rows[1].gfa, Path::new("/data/b.gfa"));
+        assert_eq!(rows[1].sample(), "b");
+
+        let err = parse_manifest("a.gfa\ta.gaf\n", Path::new("")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1: expected gfa_path, gaf_path and nodes_path columns, found 2"
+        );
+    }
+
+    #[test]
+    fn test_expand_nodes() {
+        let segments: Vec<String> = ["u24", "u25", "u27", "u30", "u31", "u2", "x26", "v1-v2"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let expand = |entries: &[&str]| {
+            let entries = entries.iter().map(|s| s.to_string()).collect();
+            expand_nodes(entries, &segments, Path::new("nodes.txt"))
+        };
+
+        assert_eq!(expand(&["u25-u30"]), ["u25", "u27", "u30"]);
+        assert_eq!(expand(&["u25-27", "x26"]), ["u25", "u27", "x26"]);
+        // a segment named like a range is taken literally
+        assert_eq!(expand(&["v1-v2"]), ["v1-v2"]);
+        assert!(expand(&["u40-u50"]).is_empty());
+    }
+
+    #[test]
+    fn test_expand_glob() {
+        let segments: Vec<String> = ["u2", "u24", "u25", "u3", "u12"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let expand = |entry: &str| expand_nodes(vec![entry.into()], &segments, Path::new("n"));
+        assert_eq!(expand("u2*"), ["u2", "u24", "u25"]);
+        assert_eq!(expand("u?"), ["u2", "u3"]);
+        assert!(expand("w*").is_empty());
+        assert!(!is_pattern("u25"));
+    }
+
+    #[test]
+    fn test_summarize_report() {
+        let report: serde_json::Value = serde_json::from_str(
+            r#"{"candidates":[],"gaf":{"pairs":[{},{}],"recombination_potential":0.5,
+            "rci":1.0,"recombination_rate":0.25,"repeats":[],"mean_entropy":0.999,
+            "total_entropy":1.998}}"#,
+        )
+        .unwrap();
+        let summary = summarize_report("s1".into(), &report).unwrap();
+        assert_eq!(summary.pairs, 2);
+        assert_eq!(summary.values, ["0.5", "1.0", "0.25", "0.999", "1.998"]);
+
+        let err = summarize_report("s1".into(), &serde_json::json!({"candidates": []}));
+        assert!(err.is_err());
+    }
+}
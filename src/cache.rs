@@ -0,0 +1,170 @@
+//! On-disk cache of a [`GraphIndex`], so a large GFA only has to be parsed once
+//! when it is analysed repeatedly with different thresholds.
+//!
+//! The cache is a small text file recording the size and modification time of the
+//! GFA it was built from, followed by the segment sizes and the edge map. It is
+//! rebuilt whenever the GFA no longer matches.
+
+use anyhow::{Context, Result};
+use bstr::{io::BufReadExt, ByteSlice};
+use gfa::gfa::Orientation;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::gaf::parse_orientation;
+use crate::gfa::{EdgeMap, GraphIndex};
+
+const MAGIC: &str = "gfa_recomb graph cache v1";
+
+/// Size and modification time of the source GFA.
+fn fingerprint(gfa: &Path) -> Result<String> {
+    let meta = std::fs::metadata(gfa)
+        .with_context(|| format!("Failed to read GFA metadata: {:?}", gfa.as_os_str()))?;
+    let mtime = meta.modified()?.duration_since(UNIX_EPOCH)?;
+    Ok(format!(
+        "source\t{}\t{}\t{}",
+        meta.len(),
+        mtime.as_secs(),
+        mtime.subsec_nanos()
+    ))
+}
+
+/// Load the cached index for `gfa`, or `None` if there is no cache or it is stale.
+pub fn load(cache: &Path, gfa: &Path, verbose: bool) -> Result<Option<GraphIndex>> {
+    let Ok(file) = File::open(cache) else {
+        if verbose {
+            eprintln!(
+                "[debug] graph cache miss: no cache at {:?}",
+                cache.as_os_str()
+            );
+        }
+        return Ok(None);
+    };
+    let mut lines = BufReader::new(file).byte_lines();
+
+    let magic = lines.next().transpose()?;
+    let source = lines.next().transpose()?;
+    if magic.as_deref() != Some(MAGIC.as_bytes())
+        || source.as_deref() != Some(fingerprint(gfa)?.as_bytes())
+    {
+        if verbose {
+            eprintln!("[debug] graph cache miss: GFA changed since the cache was built");
+        }
+        return Ok(None);
+    }
+
+    let mut segments = Vec::new();
+    let mut edge_map: EdgeMap = HashMap::new();
+    let bad_line = || format!("Malformed graph cache: {:?}", cache.as_os_str());
+
+    for line in lines {
+        let line = line?;
+        let fields: Vec<&[u8]> = line.split_str("\t").collect();
+        match fields.as_slice() {
+            [b"S", name, size] => {
+                let size = size.to_str()?.parse().with_context(bad_line)?;
+                segments.push((name.to_vec(), size));
+            }
+            [b"E", name, orient, neighbor, neighbor_orient] => {
+                let orient = match orient {
+                    [symbol] => parse_orientation(*symbol),
+                    _ => None,
+                }
+                .with_context(bad_line)?;
+                let neighbor_orient = match neighbor_orient {
+                    [symbol] => parse_orientation(*symbol),
+                    _ => None,
+                }
+                .with_context(bad_line)?;
+                edge_map
+                    .entry(name.to_vec())
+                    .or_default()
+                    .entry(orient)
+                    .or_default()
+                    .push((neighbor.to_vec(), neighbor_orient));
+            }
+            _ => anyhow::bail!(bad_line()),
+        }
+    }
+
+    if verbose {
+        eprintln!("[debug] graph cache hit: {:?}", cache.as_os_str());
+    }
+
+    Ok(Some(GraphIndex {
+        segment_sizes: segments.iter().cloned().collect(),
+        segments,
+        edge_map,
+        canonical_links: false,
+    }))
+}
+
+/// Write `index`, built from `gfa`, to `cache`.
+pub fn save(cache: &Path, gfa: &Path, index: &GraphIndex) -> Result<()> {
+    let file = File::create(cache)
+        .with_context(|| format!("Failed to create graph cache: {:?}", cache.as_os_str()))?;
+    let mut out = BufWriter::new(file);
+
+    writeln!(out, "{}", MAGIC)?;
+    writeln!(out, "{}", fingerprint(gfa)?)?;
+
+    for (name, size) in &index.segments {
+        writeln!(out, "S\t{}\t{}", name.as_bstr(), size)?;
+    }
+
+    let mut names: Vec<_> = index.edge_map.keys().collect();
+    names.sort();
+    for name in names {
+        let orient_map = &index.edge_map[name];
+        for orient in [Orientation::Forward, Orientation::Backward] {
+            for (neighbor, neighbor_orient) in orient_map.get(&orient).into_iter().flatten() {
+                writeln!(
+                    out,
+                    "E\t{}\t{}\t{}\t{}",
+                    name.as_bstr(),
+                    orient,
+                    neighbor.as_bstr(),
+                    neighbor_orient
+                )?;
+            }
+        }
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfa::load_gfa;
+
+    #[test]
+    fn test_cache_hit_and_miss() {
+        let dir = std::env::temp_dir().join(format!("gfa_recomb_cache_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let gfa_path = dir.join("graph.gfa");
+        let cache_path = dir.join("graph.cache");
+        std::fs::write(&gfa_path, "S\ta\tACGT\nS\tb\tACGTACGT\nL\ta\t+\tb\t-\t0M\n").unwrap();
+
+        // no cache yet
+        assert_eq!(load(&cache_path, &gfa_path, false).unwrap(), None);
+
+        let index = GraphIndex::new(&load_gfa(&gfa_path).unwrap(), false, None, false).unwrap();
+        save(&cache_path, &gfa_path, &index).unwrap();
+        assert_eq!(load(&cache_path, &gfa_path, false).unwrap(), Some(index));
+
+        // changing the GFA invalidates the cache
+        std::fs::write(
+            &gfa_path,
+            "S\ta\tACGT\nS\tb\tACGTACGT\nS\tc\tA\nL\ta\t+\tb\t-\t0M\n",
+        )
+        .unwrap();
+        assert_eq!(load(&cache_path, &gfa_path, false).unwrap(), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,278 @@
+//! Parquet output of the pair and entropy tables (`--format parquet`), for loading
+//! large results into dataframe libraries without parsing TSV.
+//!
+//! Only available when built with the `parquet` cargo feature, so the Arrow and
+//! Parquet crates are not a dependency otherwise.
+
+use anyhow::Result;
+use std::path::Path;
+
+use crate::gaf::GafReport;
+use crate::manifest::Manifest;
+
+/// Write `report`'s pair table to `pairs.parquet` and its entropy table to
+/// `entropy.parquet` in `dir`, creating it if needed.
+#[cfg(feature = "parquet")]
+pub fn write_parquet(dir: &Path, report: &GafReport, manifest: &mut Manifest) -> Result<()> {
+    use anyhow::Context;
+    use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray, UInt64Array};
+    use parquet::arrow::ArrowWriter;
+    use std::fs::File;
+    use std::sync::Arc;
+
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create Parquet directory: {:?}", dir.as_os_str()))?;
+    let write = |name: &str, columns: Vec<(&str, ArrayRef)>| -> Result<std::path::PathBuf> {
+        let path = dir.join(name);
+        let batch = RecordBatch::try_from_iter(columns)?;
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create Parquet file: {:?}", path.as_os_str()))?;
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(path)
+    };
+
+    let pairs = &report.pairs;
+    let path = write(
+        "pairs.parquet",
+        vec![
+            (
+                "path_1",
+                Arc::new(StringArray::from_iter_values(
+                    pairs.iter().map(|p| &p.path_1),
+                )),
+            ),
+            (
+                "cov_1",
+                Arc::new(UInt64Array::from_iter_values(pairs.iter().map(|p| p.cov_1))),
+            ),
+            (
+                "path_2",
+                Arc::new(StringArray::from_iter_values(
+                    pairs.iter().map(|p| &p.path_2),
+                )),
+            ),
+            (
+                "cov_2",
+                Arc::new(UInt64Array::from_iter_values(pairs.iter().map(|p| p.cov_2))),
+            ),
+            (
+                "recomb_score",
+                Arc::new(Float64Array::from_iter_values(
+                    pairs.iter().map(|p| p.recomb_score),
+                )),
+            ),
+            (
+                "pvalue",
+                Arc::new(Float64Array::from_iter(pairs.iter().map(|p| p.pvalue))),
+            ),
+            (
+                "qvalue",
+                Arc::new(Float64Array::from_iter(pairs.iter().map(|p| p.qvalue))),
+            ),
+            (
+                "direction",
+                Arc::new(StringArray::from_iter(pairs.iter().map(|p| p.direction))),
+            ),
+            (
+                "breakpoint",
+                Arc::new(UInt64Array::from_iter(
+                    pairs.iter().map(|p| p.breakpoint.flatten()),
+                )),
+            ),
+            (
+                "focal_node",
+                Arc::new(StringArray::from_iter(
+                    pairs.iter().map(|p| p.focal_node.as_deref()),
+                )),
+            ),
+            (
+                "focal_pos",
+                Arc::new(UInt64Array::from_iter(
+                    pairs.iter().map(|p| p.focal_pos.map(|pos| pos as u64)),
+                )),
+            ),
+        ],
+    )?;
+    manifest.add("parquet_pairs", &path);
+
+    let repeats = &report.repeats;
+    let path = write(
+        "entropy.parquet",
+        vec![
+            (
+                "repeat_node",
+                Arc::new(StringArray::from_iter_values(
+                    repeats.iter().map(|r| &r.repeat_node),
+                )),
+            ),
+            (
+                "path_count",
+                Arc::new(UInt64Array::from_iter_values(
+                    repeats.iter().map(|r| r.path_count as u64),
+                )),
+            ),
+            (
+                "entropy",
+                Arc::new(Float64Array::from_iter(repeats.iter().map(|r| r.entropy))),
+            ),
+            (
+                "fwd_cov",
+                Arc::new(UInt64Array::from_iter_values(
+                    repeats.iter().map(|r| r.fwd_cov),
+                )),
+            ),
+            (
+                "rev_cov",
+                Arc::new(UInt64Array::from_iter_values(
+                    repeats.iter().map(|r| r.rev_cov),
+                )),
+            ),
+            (
+                "top_path",
+                Arc::new(StringArray::from_iter(
+                    repeats.iter().map(|r| r.top_path.as_deref()),
+                )),
+            ),
+            (
+                "top_path_frac",
+                Arc::new(Float64Array::from_iter(
+                    repeats.iter().map(|r| r.top_path_frac),
+                )),
+            ),
+            (
+                "focal_pos",
+                Arc::new(UInt64Array::from_iter(
+                    repeats.iter().map(|r| r.focal_pos.map(|pos| pos as u64)),
+                )),
+            ),
+            (
+                "n_pairs",
+                Arc::new(UInt64Array::from_iter(
+                    repeats.iter().map(|r| r.n_pairs.map(|n| n as u64)),
+                )),
+            ),
+        ],
+    )?;
+    manifest.add("parquet_entropy", &path);
+    Ok(())
+}
+
+/// Fail unless built with Parquet support, so `--format parquet` is rejected
+/// before the analysis runs.
+#[cfg(feature = "parquet")]
+pub fn check_support() -> Result<()> {
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+pub fn check_support() -> Result<()> {
+    anyhow::bail!(
+        "--format parquet needs Parquet support; rebuild with `cargo build --features parquet`"
+    )
+}
+
+#[cfg(not(feature = "parquet"))]
+pub fn write_parquet(_dir: &Path, _report: &GafReport, _manifest: &mut Manifest) -> Result<()> {
+    check_support()
+}
+
+#[cfg(all(test, feature = "parquet"))]
+mod tests {
+    use super::*;
+    use crate::gaf::{EntropyRecord, PairRecord};
+    use arrow_array::{Array, Float64Array, StringArray, UInt64Array};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let dir = std::env::temp_dir().join(format!("gfa_recomb_parquet_{}", std::process::id()));
+        let report = GafReport {
+            pairs: vec![PairRecord {
+                path_1: "<u67<u66>u65".into(),
+                cov_1: 192,
+                path_2: "<u65>u66>u67".into(),
+                cov_2: 180,
+                recomb_score: 0.967741935483871,
+                rel_1: None,
+                rel_2: None,
+                pvalue: None,
+                qvalue: None,
+                direction: None,
+                breakpoint: None,
+                focal_node: None,
+                focal_pos: None,
+                left_flank: None,
+                right_flank: None,
+                flank_distance: None,
+            }],
+            repeats: vec![
+                EntropyRecord {
+                    repeat_node: "u66".into(),
+                    path_count: 2,
+                    entropy: Some(0.9992492479956566),
+                    fwd_cov: 180,
+                    rev_cov: 192,
+                    top_path: Some("<u67<u66>u65".into()),
+                    top_path_frac: Some(192.0 / 372.0),
+                    focal_pos: None,
+                    n_pairs: None,
+                },
+                EntropyRecord {
+                    repeat_node: "u69".into(),
+                    path_count: 0,
+                    entropy: None,
+                    fwd_cov: 0,
+                    rev_cov: 0,
+                    top_path: None,
+                    top_path_frac: None,
+                    focal_pos: None,
+                    n_pairs: None,
+                },
+            ],
+            ..Default::default()
+        };
+        let mut manifest = Manifest::default();
+        write_parquet(&dir, &report, &mut manifest).unwrap();
+        assert_eq!(manifest.files.len(), 2);
+
+        let read = |name: &str| {
+            let file = std::fs::File::open(dir.join(name)).unwrap();
+            let mut reader = ParquetRecordBatchReaderBuilder::try_new(file)
+                .unwrap()
+                .build()
+                .unwrap();
+            reader.next().unwrap().unwrap()
+        };
+
+        let pairs = read("pairs.parquet");
+        assert_eq!(pairs.num_rows(), 1);
+        let column = |name: &str| pairs.column_by_name(name).unwrap().clone();
+        let path_1 = column("path_1");
+        let path_1 = path_1.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(path_1.value(0), "<u67<u66>u65");
+        let cov_2 = column("cov_2");
+        assert_eq!(
+            cov_2
+                .as_any()
+                .downcast_ref::<UInt64Array>()
+                .unwrap()
+                .value(0),
+            180
+        );
+        let score = column("recomb_score");
+        let score = score.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(score.value(0), 0.967741935483871);
+        assert!(column("focal_node").is_null(0));
+
+        let entropy = read("entropy.parquet");
+        assert_eq!(entropy.num_rows(), 2);
+        let h = entropy.column_by_name("entropy").unwrap();
+        let h = h.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(h.value(0), 0.9992492479956566);
+        assert!(h.is_null(1));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
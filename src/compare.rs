@@ -0,0 +1,189 @@
+//! The `compare` subcommand: per-repeat differences between two `--dump-counts`
+//! tables, e.g. before and after a treatment.
+
+use anyhow::Result;
+use clap::ArgMatches;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::gaf::{load_profiles, RepeatProfile};
+
+/// The change in one repeat's path usage between two runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepeatDelta {
+    pub repeat: String,
+    pub paths_1: usize,
+    pub paths_2: usize,
+    pub entropy_1: f64,
+    pub entropy_2: f64,
+    pub rci_1: f64,
+    pub rci_2: f64,
+    /// Paths only observed in the second run, sorted.
+    pub gained: Vec<String>,
+    /// Paths only observed in the first run, sorted.
+    pub lost: Vec<String>,
+}
+
+impl RepeatDelta {
+    pub fn entropy_change(&self) -> f64 {
+        self.entropy_2 - self.entropy_1
+    }
+}
+
+/// Run the `compare` subcommand.
+pub fn run(args: &ArgMatches) -> Result<()> {
+    let first = load_profiles(args.get_one::<PathBuf>("FIRST").expect("FIRST required"))?;
+    let second = load_profiles(args.get_one::<PathBuf>("SECOND").expect("SECOND required"))?;
+    let precision = *args.get_one::<usize>("precision").unwrap();
+
+    write_deltas(&compare(&first, &second), precision, io::stdout().lock())
+}
+
+/// Compare the repeats of two runs. A repeat missing from one run counts as having
+/// no paths there. Deltas are sorted by decreasing absolute entropy change, then repeat.
+pub fn compare(first: &[RepeatProfile], second: &[RepeatProfile]) -> Vec<RepeatDelta> {
+    let mut repeats: BTreeMap<&str, (Option<&RepeatProfile>, Option<&RepeatProfile>)> =
+        BTreeMap::new();
+    for profile in first {
+        repeats.entry(&profile.repeat).or_default().0 = Some(profile);
+    }
+    for profile in second {
+        repeats.entry(&profile.repeat).or_default().1 = Some(profile);
+    }
+
+    let mut deltas: Vec<RepeatDelta> = repeats
+        .into_iter()
+        .map(|(repeat, (a, b))| {
+            let only_in = |x: Option<&RepeatProfile>, y: Option<&RepeatProfile>| {
+                let mut paths: Vec<String> = x
+                    .into_iter()
+                    .flat_map(|x| x.paths.keys())
+                    .filter(|p| !y.is_some_and(|y| y.paths.contains_key(*p)))
+                    .cloned()
+                    .collect();
+                paths.sort();
+                paths
+            };
+            RepeatDelta {
+                repeat: repeat.to_string(),
+                paths_1: a.map_or(0, |a| a.paths.len()),
+                paths_2: b.map_or(0, |b| b.paths.len()),
+                entropy_1: a.map_or(0.0, |a| a.entropy),
+                entropy_2: b.map_or(0.0, |b| b.entropy),
+                rci_1: a.map_or(0.0, |a| a.rci),
+                rci_2: b.map_or(0.0, |b| b.rci),
+                gained: only_in(b, a),
+                lost: only_in(a, b),
+            }
+        })
+        .collect();
+
+    // the repeats are already in order, and the sort is stable
+    deltas.sort_by(|x, y| {
+        y.entropy_change()
+            .abs()
+            .total_cmp(&x.entropy_change().abs())
+    });
+    deltas
+}
+
+/// Write the deltas as a table. Gained and lost paths are comma separated, or `-`.
+pub fn write_deltas<W: Write>(deltas: &[RepeatDelta], precision: usize, mut out: W) -> Result<()> {
+    writeln!(
+        out,
+        "repeat_node\tpaths_1\tpaths_2\tdelta_paths\tentropy_1\tentropy_2\tdelta_entropy\trci_1\trci_2\tdelta_rci\tgained\tlost"
+    )?;
+    let list = |paths: &[String]| {
+        if paths.is_empty() {
+            "-".to_string()
+        } else {
+            paths.join(",")
+        }
+    };
+    for d in deltas {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{:.*}\t{:.*}\t{:.*}\t{:.*}\t{:.*}\t{:.*}\t{}\t{}",
+            d.repeat,
+            d.paths_1,
+            d.paths_2,
+            d.paths_2 as i64 - d.paths_1 as i64,
+            precision,
+            d.entropy_1,
+            precision,
+            d.entropy_2,
+            precision,
+            d.entropy_change(),
+            precision,
+            d.rci_1,
+            precision,
+            d.rci_2,
+            precision,
+            d.rci_2 - d.rci_1,
+            list(&d.gained),
+            list(&d.lost)
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn profile(repeat: &str, paths: &[(&str, u64)], entropy: f64, rci: f64) -> RepeatProfile {
+        RepeatProfile {
+            repeat: repeat.to_string(),
+            paths: paths
+                .iter()
+                .map(|(p, c)| (p.to_string(), *c))
+                .collect::<HashMap<_, _>>(),
+            entropy,
+            rci,
+        }
+    }
+
+    #[test]
+    fn test_compare_profiles() {
+        let first = [
+            profile("u2", &[(">a>u2>b", 10), ("<b<u2<a", 10)], 1.0, 1.0),
+            profile("u5", &[(">c>u5>d", 10)], 0.0, 0.0),
+        ];
+        let second = [
+            profile("u2", &[(">a>u2>b", 10), ("<b<u2<a", 10)], 1.0, 1.0),
+            profile(
+                "u5",
+                &[(">c>u5>d", 10), ("<d<u5<c", 10), (">e>u5>d", 20)],
+                1.5,
+                1.0,
+            ),
+            profile("u9", &[(">f>u9>g", 4)], 0.0, 0.0),
+        ];
+
+        let deltas = compare(&first, &second);
+        let order: Vec<_> = deltas.iter().map(|d| d.repeat.as_str()).collect();
+        // u5 changed most, then the unchanged u2 and new u9 in ID order
+        assert_eq!(order, ["u5", "u2", "u9"]);
+
+        let u5 = &deltas[0];
+        assert_eq!((u5.paths_1, u5.paths_2), (1, 3));
+        assert_eq!(u5.entropy_change(), 1.5);
+        assert_eq!(u5.rci_2 - u5.rci_1, 1.0);
+        assert_eq!(u5.gained, ["<d<u5<c", ">e>u5>d"]);
+        assert!(u5.lost.is_empty());
+
+        let u9 = &deltas[2];
+        assert_eq!((u9.paths_1, u9.paths_2), (0, 1));
+        assert_eq!(u9.gained, [">f>u9>g"]);
+
+        let mut out = Vec::new();
+        write_deltas(&deltas[..1], 2, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap().lines().nth(1).unwrap(),
+            "u5\t1\t3\t2\t0.00\t1.50\t1.50\t0.00\t1.00\t1.00\t<d<u5<c,>e>u5>d\t-"
+        );
+    }
+}
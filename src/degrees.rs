@@ -0,0 +1,137 @@
+//! The `degrees` subcommand: list every segment of a GFA with its degree, without
+//! any repeat thresholds, to help choose `--inout`.
+
+use anyhow::Result;
+use bstr::ByteSlice;
+use clap::ArgMatches;
+use gfa::gfa::Orientation;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::gfa::GraphIndex;
+
+/// The links at either end of a segment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentDegree {
+    pub id: Vec<u8>,
+    pub size: usize,
+    /// Links at the start of the segment, as read forward.
+    pub in_degree: usize,
+    /// Links at the end of the segment, as read forward.
+    pub out_degree: usize,
+}
+
+impl SegmentDegree {
+    /// Links at either end, as compared with `--inout`.
+    pub fn degree(&self) -> usize {
+        self.in_degree + self.out_degree
+    }
+}
+
+/// Run the `degrees` subcommand.
+pub fn run(args: &ArgMatches) -> Result<()> {
+    let path = args.get_one::<PathBuf>("GFA").expect("GFA required");
+    let sort = args.get_one::<String>("sort").expect("sort has a default");
+    // keyed canonically, a link is listed under the end it leaves the segment from
+    let index = GraphIndex::load(path, args.get_flag("warn-duplicates"), None, true)?;
+    let mut degrees = segment_degrees(&index);
+    sort_degrees(&mut degrees, sort);
+
+    let mut out = io::stdout().lock();
+    writeln!(out, "ID\tSize\tin_degree\tout_degree\tdegree")?;
+    for segment in &degrees {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}",
+            segment.id.to_str_lossy(),
+            segment.size,
+            segment.in_degree,
+            segment.out_degree,
+            segment.degree()
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// The degrees of every segment of `index`, in GFA order. `index` must be keyed
+/// canonically (see [`GraphIndex::new`]), so that the links under a segment's
+/// forward orientation leave its end and those under its reverse leave its start.
+pub fn segment_degrees(index: &GraphIndex) -> Vec<SegmentDegree> {
+    debug_assert!(index.canonical_links);
+    index
+        .segments
+        .iter()
+        .map(|(id, size)| {
+            let orient_map = index.edge_map.get(id);
+            let links = |orient| orient_map.and_then(|m| m.get(&orient)).map_or(0, Vec::len);
+            SegmentDegree {
+                id: id.clone(),
+                size: *size,
+                in_degree: links(Orientation::Backward),
+                out_degree: links(Orientation::Forward),
+            }
+        })
+        .collect()
+}
+
+/// Order segments by decreasing `degree` or `size`, by `id`, or keep their `gfa`
+/// order. Ties keep their GFA order.
+fn sort_degrees(degrees: &mut [SegmentDegree], key: &str) {
+    match key {
+        "degree" => degrees.sort_by_key(|s| std::cmp::Reverse(s.degree())),
+        "size" => degrees.sort_by_key(|s| std::cmp::Reverse(s.size)),
+        "id" => degrees.sort_by(|a, b| a.id.cmp(&b.id)),
+        "gfa" => {}
+        _ => unreachable!("--sort only accepts degree, size, id and gfa"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfa::parser::GFAParser;
+
+    #[test]
+    fn test_segment_degrees() {
+        // a and c enter R, which leaves to b and (reversed) d; e is unlinked
+        let lines = [
+            "S\tR\tACGT",
+            "S\ta\tACGTACGT",
+            "S\tb\tACGTACGT",
+            "S\tc\tACGTACGT",
+            "S\td\tACGTACGT",
+            "S\te\tACGTACGTACGT",
+            "L\ta\t+\tR\t+\t0M",
+            "L\tR\t+\tb\t+\t0M",
+            // c+ R+ written from R's other end
+            "L\tR\t-\tc\t-\t0M",
+            "L\tR\t+\td\t-\t0M",
+        ];
+        let gfa: crate::gfa::Gfa = GFAParser::new()
+            .parse_lines(lines.iter().map(|l| l.as_bytes()))
+            .unwrap();
+        let index = GraphIndex::new(&gfa, false, None, true).unwrap();
+        let mut degrees = segment_degrees(&index);
+        let row = |s: &SegmentDegree| (s.id.to_str_lossy().into_owned(), s.in_degree, s.out_degree);
+        assert_eq!(
+            degrees.iter().map(row).collect::<Vec<_>>(),
+            [
+                ("R".to_string(), 2, 2),
+                ("a".to_string(), 0, 1),
+                ("b".to_string(), 1, 0),
+                ("c".to_string(), 0, 1),
+                // entered at its end, from R
+                ("d".to_string(), 0, 1),
+                ("e".to_string(), 0, 0),
+            ]
+        );
+
+        sort_degrees(&mut degrees, "degree");
+        assert_eq!(degrees[0].id, b"R");
+        assert_eq!(degrees[0].degree(), 4);
+        assert_eq!(degrees[5].id, b"e");
+        sort_degrees(&mut degrees, "size");
+        assert_eq!(degrees[0].id, b"e");
+    }
+}
@@ -1,695 +1,5998 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use bstr::{io::*, ByteSlice};
+use clap::ArgMatches;
 use gfa::{
     gafpaf::{parse_gaf, GAFPath, GAFStep},
     gfa::Orientation,
-    optfields::OptField,
+    optfields::{OptField, OptFieldVal},
 };
+use serde::Serialize;
 use std::f64::consts::LN_2;
-use std::io::{self, Write};
-use std::{collections::HashMap, fmt::Display, fs::File, io::BufReader, path::PathBuf};
+use std::io::{self, BufRead, Write};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+    time::Instant,
+};
+
+use crate::manifest::Manifest;
+use crate::report::{round, round_option};
+use crate::simulate::SplitMix64;
+use crate::timings::Timings;
 
-/// Parses a GAF file and extracts all 3-node paths through specified repeat nodes.
+/// How far the path probabilities of a repeat may sum from 1 before it's reported.
+const PROBABILITY_EPSILON: f64 = 1e-9;
+
+/// Parses GAF files and extracts all 3-node paths through specified repeat nodes.
 /// Counts and groups them by focal repeat segment for recombination analysis.
-pub fn count_gaf_paths(gaf_path: PathBuf, nodes: Vec<String>) -> Result<()> {
-    let file = File::open(gaf_path).unwrap();
-    let lines = BufReader::new(file).byte_lines();
+///
+/// With several GAFs, each is counted separately and the counts of a (focal node,
+/// path) are then merged according to `--combine`.
+///
+/// The tables are printed to stdout, unless `--json` is given, and returned as a
+/// [`GafReport`].
+pub fn count_gaf_paths(
+    gaf_paths: &[PathBuf],
+    nodes: Vec<String>,
+    index: &crate::gfa::GraphIndex,
+    inverted: Option<&HashSet<String>>,
+    args: &ArgMatches,
+    manifest: &mut Manifest,
+    timings: &mut Timings,
+) -> Result<GafReport> {
+    let segment_sizes = &index.segment_sizes;
+    let multi_focal = args.get_flag("multi-focal");
+    let min_flank = args
+        .get_one::<usize>("gaf-min-flank")
+        .map(|&min_size| FlankFilter {
+            sizes: segment_sizes,
+            min_size,
+        });
 
-    // store the paths
-    let mut paths = HashMap::new();
+    let long_reads = args.get_flag("long-reads");
+    let orientations = focal_orientations(args);
+    let options = CountOptions {
+        multi_focal,
+        long_reads,
+        min_flank: min_flank.as_ref(),
+        dialect: args
+            .get_one::<GafDialect>("gaf-dialect")
+            .copied()
+            .unwrap_or_default(),
+        subsample: args.get_one::<f64>("subsample").map(|&fraction| Subsample {
+            fraction,
+            seed: *args.get_one::<u64>("seed").expect("seed has a default"),
+        }),
+        group_tag: args.get_one::<String>("group-by-tag").map(String::as_bytes),
+        orientations: Some(&orientations),
+        no_revisit: args.get_flag("no-revisit"),
+        breakpoints: args.get_flag("breakpoints").then_some(segment_sizes),
+        head: args.get_one::<u64>("head").map(|&n| n as usize),
+        length_weights: args.get_flag("entropy-length-weight"),
+    };
 
-    for (i, line) in lines.enumerate() {
-        let line = line?;
-        let fields = line.split_str(b"\t");
-        if let Some(gaf) = parse_gaf::<_, Vec<OptField>>(fields) {
-            // get the path length
-            let path = gaf.path;
-            match &path {
-                GAFPath::StableId(_) => continue, // don't care about this
-                GAFPath::OrientIntv(vec) => {
-                    // only interested in those paths of length 3... if
-                    // we had longer read data (e.g. Nanopore), we might change this
-                    if vec.len() == 3 {
-                        let mut node = Default::default();
-                        if match vec[1].clone() {
-                            GAFStep::SegId(_, id) => {
-                                node = String::from_utf8(id.to_vec()).unwrap();
-                                nodes.contains(&node)
-                            }
-                            GAFStep::StableIntv(_, _, _, _) => false,
-                        } {
-                            *paths.entry((node, path.to_string())).or_insert(0) += 1;
-                        } else {
-                            continue;
-                        }
-                    }
-                }
-            };
-        } else {
-            eprintln!("Error parsing GAF line {}", i);
-            std::process::exit(1);
+    let combine = match args.get_one::<String>("combine").map(String::as_str) {
+        Some("max") => Combine::Max,
+        Some("mean") => Combine::Mean,
+        _ => Combine::Sum,
+    };
+
+    let start = Instant::now();
+    let mut files = Vec::new();
+    for gaf_path in gaf_paths {
+        let open = || {
+            File::open(gaf_path)
+                .with_context(|| format!("Failed to open GAF file: {:?}", gaf_path.as_os_str()))
+        };
+        if let Some(&max) = args.get_one::<f64>("max-unmatched-frac") {
+            check_namespace(BufReader::new(open()?), options.dialect, segment_sizes, max)
+                .with_context(|| format!("GAF file {:?}", gaf_path.as_os_str()))?;
         }
+        let file = open()?;
+        files.push(count_paths(BufReader::new(file), &nodes, &options)?);
+    }
+    let GafCounts {
+        support,
+        tagged,
+        source_lengths,
+        unaligned,
+        malformed,
+        alignments,
+        revisiting,
+    } = combine_counts(files, combine);
+    timings.record("GAF parse", start);
+    if let Some(head) = options.head {
+        eprintln!(
+            "Note: only the first {} line(s) of each GAF were read (--head)",
+            head
+        );
+    }
+    if alignments == 0 {
+        eprintln!("Warning: GAF contained no usable alignments");
+    }
+    if unaligned > 0 {
+        eprintln!(
+            "Note: {} GAF line(s) had no graph path (\"*\" or empty) and were counted as unaligned",
+            unaligned
+        );
+    }
+    if malformed > 0 {
+        eprintln!(
+            "Note: {} GAF line(s) had negative, non-numeric or inconsistent path coordinates, \
+             which give the read's length weight, and were skipped as malformed",
+            malformed
+        );
+    }
+    if revisiting > 0 {
+        eprintln!(
+            "Note: {} GAF path(s) revisiting a segment dropped by --no-revisit",
+            revisiting
+        );
+    }
+    if let Some(warning) = focal_mismatch_warning(&nodes, &support) {
+        eprintln!("{}", warning);
+    }
+    if long_reads && !args.get_flag("json") {
+        output_source_length_lines(&mut io::stdout(), &source_lengths);
     }
+    let start = Instant::now();
+    let mut report = report_paths(support, nodes, index, inverted, args, manifest)?;
+    if options.group_tag.is_some() {
+        let precision = *args.get_one::<usize>("precision").unwrap();
+        let mut out: Box<dyn Write> = if args.get_flag("json") {
+            Box::new(io::sink())
+        } else {
+            Box::new(io::stdout())
+        };
+        report.tag_groups = Some(output_tag_group_lines(
+            &mut out,
+            &tagged,
+            multi_focal,
+            precision,
+        ));
+    }
+    timings.record("aggregation", start);
+    if long_reads {
+        report.source_lengths = Some(
+            source_lengths
+                .into_iter()
+                .map(|(path_length, observations)| SourceLength {
+                    path_length,
+                    observations,
+                })
+                .collect(),
+        );
+    }
+    report.alignments = Some(alignments);
+    Ok(report)
+}
 
-    let paths: Vec<(&(String, String), &i32)> = paths.iter().collect();
+/// Like [`count_gaf_paths`], but takes the path counts from a previous run's
+/// `--dump-counts` table instead of a GAF. Only rows for the current focal `nodes`
+/// are used, so the statistics can be recomputed under different thresholds.
+pub fn load_gaf_counts(
+    counts_path: &std::path::Path,
+    nodes: Vec<String>,
+    index: &crate::gfa::GraphIndex,
+    inverted: Option<&HashSet<String>>,
+    args: &ArgMatches,
+    manifest: &mut Manifest,
+    timings: &mut Timings,
+) -> Result<GafReport> {
+    let start = Instant::now();
+    let notation = match args.get_one::<String>("path-notation").map(String::as_str) {
+        Some("pm") => PathNotation::PlusMinus,
+        _ => PathNotation::Arrow,
+    };
+    let counts = load_counts(counts_path, notation)?;
+    let orientations = focal_orientations(args);
+    let support: HashMap<PathKey, PathSupport> = counts
+        .into_iter()
+        .filter(|((node, _), _)| nodes.contains(node))
+        .filter(|((node, path), _)| match orientations.get(node) {
+            Some(wanted) => string_to_path(path.clone())
+                .ok()
+                .and_then(|p| Some(p.0[p.position_of(node)?].orientation))
+                .is_some_and(|o| o == *wanted),
+            None => true,
+        })
+        .map(|(key, count)| {
+            let support = PathSupport {
+                count,
+                ..Default::default()
+            };
+            (key, support)
+        })
+        .collect();
+    timings.record("counts load", start);
 
-    let paths = Paths::from_vec(paths).split_into_repeats();
+    let start = Instant::now();
+    let report = report_paths(support, nodes, index, inverted, args, manifest);
+    timings.record("aggregation", start);
+    report
+}
 
-    output_repeat_lines(&paths);
-    output_entropy_lines(&paths, false);
+/// The orientations required by `--focal`, empty if it wasn't given.
+fn focal_orientations(args: &ArgMatches) -> FocalOrientations {
+    match args.get_many::<String>("focal") {
+        Some(specs) => parse_focal_specs(&specs.cloned().collect::<Vec<_>>()).1,
+        None => FocalOrientations::new(),
+    }
+}
 
-    Ok(())
+/// Read a `--dump-counts` table from `path`.
+fn load_counts(path: &std::path::Path, notation: PathNotation) -> Result<HashMap<PathKey, u64>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open counts file: {:?}", path.as_os_str()))?;
+    read_counts(BufReader::new(file), notation)
+        .with_context(|| format!("Malformed counts file: {:?}", path.as_os_str()))
 }
 
-/// Compute the Shannon entropy of path usage for each repeat node.
-/// This reflects the diversity of path usage through each focal repeat.
-///
-/// For each repeat node:
-/// - Count all distinct 3-node paths
-/// - Sum their coverages
-/// - Compute entropy:
-///   H = -sum(p_i * log2(p_i)) over all paths i
-///
-/// Returns:
-/// - (mean_entropy, Vec<(repeat_id, path_count, entropy)>)
-fn output_entropy_lines(
-    groups: &[Paths],
-    test: bool,
-) -> Option<(f64, f64, Vec<(String, usize, f64)>)> {
-    let mut entropies = Vec::new();
+/// The aggregate statistics of a counts table (`summarize` subcommand).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CountsSummary {
+    pub mean_entropy: f64,
+    pub total_entropy: f64,
+    pub rci: f64,
+}
 
-    for group in groups {
-        if group.paths.is_empty() {
-            continue;
-        }
+/// The mean and total entropy and RCI of the `--dump-counts` table at `path`, as
+/// computed by the GAF stage with default options, without its tables.
+pub fn summarize_counts(path: &std::path::Path) -> Result<CountsSummary> {
+    let counts = load_counts(path, PathNotation::Arrow)?;
+    let groups = Paths::from_vec(counts.iter().collect()).split_into_repeats();
+    let pairs = output_repeat_lines(
+        &mut io::sink(),
+        &groups,
+        &PairOptions::default(),
+        &RciOptions::default(),
+        0,
+    );
+    let (mean_entropy, total_entropy, _) =
+        output_entropy_lines(&mut io::sink(), &groups, &EntropyOptions::default(), 0)
+            .unwrap_or_default();
+    Ok(CountsSummary {
+        mean_entropy,
+        total_entropy,
+        rci: pairs.rci,
+    })
+}
 
-        let repeat_id = &group.paths[0].0 .0; // all paths share this repeat node
-        let total_cov: f64 = group.paths.iter().map(|(_, c)| *c as f64).sum();
+/// The path usage of one repeat in a counts table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepeatProfile {
+    pub repeat: String,
+    /// Read count of each observed path.
+    pub paths: HashMap<String, u64>,
+    pub entropy: f64,
+    /// The repeat's `S_r * log2(P_r)` term of the RCI.
+    pub rci: f64,
+}
 
-        if total_cov == 0.0 {
-            continue;
-        }
+/// Per-repeat profiles of the `--dump-counts` table at `path`, sorted by repeat.
+pub fn load_profiles(path: &std::path::Path) -> Result<Vec<RepeatProfile>> {
+    Ok(repeat_profiles(&load_counts(path, PathNotation::Arrow)?))
+}
 
-        let mut entropy = 0.0;
-        for (_, cov) in &group.paths {
-            let p = *cov as f64 / total_cov;
-            if p > 0.0 {
-                entropy -= p * (p.ln() / LN_2);
+fn repeat_profiles(counts: &HashMap<PathKey, u64>) -> Vec<RepeatProfile> {
+    let groups = Paths::from_vec(counts.iter().collect()).split_into_repeats();
+    groups
+        .into_iter()
+        .map(|group| {
+            let group = [group];
+            // a single repeat's RCI is its own term
+            let pairs = output_repeat_lines(
+                &mut io::sink(),
+                &group,
+                &PairOptions::default(),
+                &RciOptions::default(),
+                0,
+            );
+            let entropy =
+                output_entropy_lines(&mut io::sink(), &group, &EntropyOptions::default(), 0)
+                    .map_or(0.0, |(mean, _, _)| mean);
+            let [group] = group;
+            RepeatProfile {
+                repeat: group.paths[0].0 .0.clone(),
+                paths: group
+                    .paths
+                    .iter()
+                    .map(|((_, p), c)| (p.to_string(), *c))
+                    .collect(),
+                entropy,
+                rci: pairs.rci,
             }
-        }
+        })
+        .collect()
+}
+
+/// Compute, print and return the statistics of the counted paths.
+///
+/// With `inverted` (`--only-inverted`), the pair table, recombination potential,
+/// recombination rate and RCI only use the repeats in it, the structurally
+/// inverted ones.
+fn report_paths(
+    support: HashMap<PathKey, PathSupport>,
+    nodes: Vec<String>,
+    index: &crate::gfa::GraphIndex,
+    inverted: Option<&HashSet<String>>,
+    args: &ArgMatches,
+    manifest: &mut Manifest,
+) -> Result<GafReport> {
+    let multi_focal = args.get_flag("multi-focal");
+    let precision = *args.get_one::<usize>("precision").unwrap();
+
+    let paths = path_counts(&support);
+    // per-path total aligned length, used in place of counts for entropy
+    let length_weights: Option<HashMap<PathKey, u64>> =
+        args.get_flag("entropy-length-weight").then(|| {
+            support
+                .iter()
+                .map(|(key, s)| (key.clone(), s.length))
+                .collect()
+        });
+
+    if let Some(dump) = args.get_one::<PathBuf>("dump-counts") {
+        let out = File::create(dump)
+            .with_context(|| format!("Failed to create counts file: {:?}", dump.as_os_str()))?;
+        write_counts(&paths, BufWriter::new(out))?;
+        manifest.add("counts", dump);
+    }
 
-        entropies.push((repeat_id.clone(), group.paths.len(), entropy));
+    let paths: Vec<(&(String, String), &u64)> = paths.iter().collect();
+
+    let mut paths = Paths::from_vec(paths).split_into_repeats();
+    if let Some(&min) = args.get_one::<u64>("min-repeat-cov") {
+        let excluded = drop_weak_repeats(&mut paths, min);
+        if excluded > 0 {
+            eprintln!(
+                "Note: {} repeat(s) with fewer than {} reads excluded by --min-repeat-cov",
+                excluded, min
+            );
+        }
+    }
+    if let Some(&fraction) = args.get_one::<f64>("min-path-frac") {
+        let excluded = drop_noise_paths(&mut paths, fraction);
+        if excluded > 0 {
+            eprintln!(
+                "Note: {} path(s) with less than {} of their repeat's top path coverage \
+                 excluded by --min-path-frac",
+                excluded, fraction
+            );
+        }
+    }
+    if args.get_flag("require-both-orientations-observed") {
+        let excluded = drop_single_orientation_repeats(&mut paths);
+        if excluded > 0 {
+            eprintln!(
+                "Note: {} repeat(s) observed in only one orientation excluded by \
+                 --require-both-orientations-observed",
+                excluded
+            );
+        }
     }
 
-    let total_entropy = entropies.iter().map(|(_, _, e)| *e).sum::<f64>();
-    let mean_entropy = if !entropies.is_empty() {
-        entropies.iter().map(|(_, _, e)| *e).sum::<f64>() / entropies.len() as f64
+    let mut out: Box<dyn Write> = if args.get_flag("json") {
+        Box::new(io::sink())
     } else {
-        0.0
+        Box::new(io::stdout())
     };
 
-    if !entropies.is_empty() {
-        let mut stdout = io::stdout();
-        let _ = writeln!(stdout, "\nrepeat_node\tpath_count\tentropy");
-        for (node, path_length, entropy) in &entropies {
-            let _ = writeln!(stdout, "{}\t{}\t{:.3}", node, path_length, entropy);
-        }
-        let _ = writeln!(stdout, "\nMean entropy: {:.3}", mean_entropy);
-        let _ = writeln!(stdout, "Total entropy: {:.3}", total_entropy);
-        // for internal testing
-        if test {
-            return Some((mean_entropy, total_entropy, entropies));
-        } else {
-            return None;
+    if let Some(query) = args.get_many::<String>("query") {
+        let query: Vec<&String> = query.collect();
+        let group = query_paths(&paths, query[0], query[1], query[2]);
+        let options = PairOptions {
+            print_pairs: true,
+            ..Default::default()
+        };
+        let pairs = output_repeat_lines(
+            &mut out,
+            &[group],
+            &options,
+            &RciOptions::default(),
+            precision,
+        );
+        if pairs.pairs.is_empty() {
+            eprintln!(
+                "Note: no reverse-complement pair of paths connects {} and {} through {}",
+                query[0], query[2], query[1]
+            );
         }
+        return Ok(GafReport {
+            pairs: pairs.pairs,
+            recombination_potential: pairs.recombination_potential,
+            rci: pairs.rci,
+            ..Default::default()
+        });
     }
 
-    None
-}
-
-/// Compute the Recombination Complexity Index (RCI) from a list of recombination path pairs.
-///
-/// Each tuple in `revcomps` represents a pair of paths that are reverse complements,
-/// along with their coverage values. The RCI summarizes both:
-///
-/// 1. **Coverage balance** between the forward and reverse paths
-/// 2. **Path diversity** through repeat nodes (more paths = higher isomeric potential)
-///
-/// The RCI is computed as:
-///
-/// ```text
-///     RCI = (1 / R) * Σ [S_r * log2(P_r)]
-/// ```
-///
-/// Where:
-/// - `R` is the number of repeat nodes (focal segments)
-/// - `P_r` is the number of distinct paths through repeat node `r`
-/// - `S_r` is the average recombination score for repeat `r`, where:
-///   `recomb_score = 2 * min(rel_cov1, rel_cov2)`
-///
-/// # Arguments
-/// * `revcomps` - A vector of tuples (path1, cov1, path2, cov2)
-///
-/// # Returns
-/// * `f64` - The recombination complexity index (RCI)
-///
-fn compute_rci(revcomps: &Vec<(String, i32, String, i32)>) -> f64 {
-    // Map of repeat node ID to its recombination scores and path count
-    let mut repeat_groups: HashMap<String, (Vec<f64>, usize)> = HashMap::new();
+    // structurally possible path counts, used as the RCI's P_r instead of observed
+    // counts, and as the expectation of --anomaly
+    let possible_rci = args
+        .get_one::<String>("rci-denominator")
+        .is_some_and(|mode| mode == "possible");
+    let structure = if possible_rci || args.get_flag("anomaly") {
+        let gfa_file = args.get_one::<PathBuf>("GFA").expect("GFA required");
+        Some(crate::gfa::load_possible_paths(gfa_file, &nodes)?)
+    } else {
+        None
+    };
+    let rci = RciOptions {
+        possible: structure.as_ref().filter(|_| possible_rci),
+        coverage_weighted: args
+            .get_one::<String>("rci-weight")
+            .is_some_and(|weight| weight == "coverage"),
+    };
+    let flank_sequences = match args.get_one::<u64>("emit-flank-seq") {
+        Some(&length) => {
+            let gfa_file = args.get_one::<PathBuf>("GFA").expect("GFA required");
+            Some(FlankSequences {
+                sequences: crate::gfa::load_sequences(gfa_file)?,
+                length: length as usize,
+            })
+        }
+        None => None,
+    };
+    // the detection graph, so links dropped by --min-link-tag aren't followed
+    let flank_distances = if args.get_flag("flank-distance") {
+        Some(FlankDistances {
+            index,
+            max_hops: *args
+                .get_one::<usize>("max-hops")
+                .expect("max-hops has a default"),
+        })
+    } else {
+        None
+    };
+    let families = match args.get_one::<PathBuf>("canonical-pair-key") {
+        Some(path) => Some(load_segment_families(path)?),
+        None => None,
+    };
+    let options = PairOptions {
+        multi_focal,
+        print_pairs: !args.get_flag("no-pairs"),
+        canonical: args.get_flag("reference-orientation"),
+        relative: args.get_flag("relative-coverage"),
+        pvalue: args.get_flag("pvalue"),
+        fdr: args.get_flag("fdr"),
+        direction: args.get_flag("direction"),
+        coverage_weighted: args
+            .get_one::<String>("potential-weight")
+            .is_some_and(|weight| weight == "coverage"),
+        breakpoints: args.get_flag("breakpoints").then_some(&support),
+        families: families.as_ref(),
+        flanks: flank_sequences.as_ref(),
+        distances: flank_distances.as_ref(),
+        inverted,
+    };
+    let pairs = output_repeat_lines(&mut out, &paths, &options, &rci, precision);
+    let threshold = *args
+        .get_one::<f64>("recomb-threshold")
+        .expect("recomb-threshold has a default");
+    let rate = recombination_rate(&pairs.pairs, &paths, inverted, threshold);
+    let _ = writeln!(out, "Recombination rate: {:.*}", precision, rate);
+    let score_histogram = args.get_one::<u64>("score-hist").map(|&bins| {
+        let hist = score_histogram(&pairs.recomb_scores, bins as usize);
+        output_histogram_lines(&mut out, &hist, precision);
+        hist
+    });
+    // with --paired-only, entropy uses the same paths as the pair table and RCI
+    let paired_only = args.get_flag("paired-only");
+    let entropy_paths: Vec<Paths> = if paired_only {
+        paths.iter().map(|p| p.paired(families.as_ref())).collect()
+    } else {
+        paths.clone()
+    };
+    let (mean_entropy, total_entropy, entropies) = output_entropy_lines(
+        &mut out,
+        &entropy_paths,
+        &EntropyOptions {
+            length_weights: length_weights.as_ref(),
+            unsupported: args
+                .get_flag("show-unsupported")
+                .then_some(nodes.as_slice()),
+            pair_counts: args.get_flag("n-pairs").then_some(&pairs.pair_counts),
+            multi_focal,
+            multi_path_mean: args.get_flag("exclude-single-path"),
+            flank_pairs: args
+                .get_one::<String>("entropy-unit")
+                .is_some_and(|unit| unit == "flankpair"),
+            check: args.get_flag("check"),
+        },
+        precision,
+    )
+    .unwrap_or_default();
 
-    for (p1, cov1, p2, cov2) in revcomps {
-        let total_cov = *cov1 as f64 + *cov2 as f64;
-        if total_cov == 0.0 {
-            continue; // skip zero-coverage cases
+    let anomalies = match &structure {
+        Some(structure) if args.get_flag("anomaly") => {
+            let cutoff = match args.get_one::<f64>("anomaly-z") {
+                Some(&z) => AnomalyCutoff::ZScore(z),
+                None => AnomalyCutoff::Deviation(
+                    *args
+                        .get_one::<f64>("anomaly-threshold")
+                        .expect("anomaly-threshold has a default"),
+                ),
+            };
+            let records = anomalies(&entropies, structure, cutoff);
+            if records.is_empty() {
+                eprintln!("Note: no repeat was flagged by --anomaly");
+            }
+            output_anomaly_lines(&mut out, &records, precision);
+            Some(records)
         }
+        _ => None,
+    };
 
-        let rel1 = *cov1 as f64 / total_cov;
-        let recomb_score = 2.0 * rel1.min(1.0 - rel1);
+    let multiway = args
+        .get_flag("multiway")
+        .then(|| output_multiway_lines(&mut out, &paths, precision));
 
-        // Parse the repeat node from the middle segment of either path
-        if let (Some(repeat_id), path_count) = extract_repeat_info(p1, p2) {
-            repeat_groups
-                .entry(repeat_id)
-                .and_modify(|(scores, count)| {
-                    scores.push(recomb_score);
-                    *count += path_count;
-                })
-                .or_insert((vec![recomb_score], path_count));
+    let (repeat_families, family_rci) = match args.get_one::<f64>("merge-shared-paths") {
+        Some(&threshold) => {
+            let (records, rci) =
+                output_family_lines(&mut out, &paths, threshold, families.as_ref(), precision);
+            (Some(records), Some(rci))
         }
+        None => (None, None),
+    };
+
+    let mut reference = None;
+    if args.get_flag("ref-paths") {
+        let gfa_file = args.get_one::<PathBuf>("GFA").expect("GFA required");
+        let windows = crate::gfa::load_reference_windows(gfa_file)?;
+        if windows.is_empty() {
+            eprintln!("Warning: --ref-paths given but the GFA has no P lines of 3+ segments");
+        }
+        reference = Some(output_reference_lines(&mut out, &paths, &windows));
     }
 
-    // Compute average S_r * log2(P_r) for each repeat
-    let mut total_score = 0.0;
-    let mut n_repeats = 0;
+    if let Some(profile) = args.get_one::<PathBuf>("path-profile") {
+        let out = File::create(profile)
+            .with_context(|| format!("Failed to create path profile: {:?}", profile.as_os_str()))?;
+        write_path_profiles(&paths, BufWriter::new(out))?;
+        manifest.add("path_profile", profile);
+    }
 
-    for (_repeat_id, (scores, path_count)) in repeat_groups {
-        if scores.is_empty() || path_count <= 1 {
-            continue;
+    if let Some(dir) = args.get_one::<PathBuf>("split-dir") {
+        let files = write_split_files(
+            dir,
+            &paths,
+            &options,
+            paired_only,
+            &rci,
+            length_weights.as_ref(),
+            precision,
+        )?;
+        for (repeat, path) in files {
+            manifest.add_repeat(&repeat, &path);
         }
+    }
 
-        let s_r: f64 = scores.iter().sum::<f64>() / scores.len() as f64;
-        let p_r: f64 = path_count as f64;
+    Ok(GafReport {
+        pairs: pairs.pairs,
+        recombination_potential: pairs.recombination_potential,
+        rci: pairs.rci,
+        recombination_rate: rate,
+        repeats: entropies,
+        mean_entropy,
+        total_entropy,
+        reference: reference.map(|summary| {
+            summary
+                .into_iter()
+                .map(|(repeat_node, support)| ReferenceRecord {
+                    repeat_node,
+                    support,
+                })
+                .collect()
+        }),
+        score_histogram,
+        source_lengths: None,
+        multiway,
+        anomalies,
+        repeat_families,
+        family_rci,
+        tag_groups: None,
+        alignments: None,
+    })
+}
 
-        let score = s_r * (p_r.ln() / LN_2); // log2
-        total_score += score;
-        n_repeats += 1;
-    }
+/// Everything the GAF stage reports, in output order.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GafReport {
+    pub pairs: Vec<PairRecord>,
+    #[serde(serialize_with = "round")]
+    pub recombination_potential: f64,
+    #[serde(serialize_with = "round")]
+    pub rci: f64,
+    /// Fraction of the focal repeats' reads in balanced pairs (`--recomb-threshold`).
+    #[serde(serialize_with = "round")]
+    pub recombination_rate: f64,
+    pub repeats: Vec<EntropyRecord>,
+    #[serde(serialize_with = "round")]
+    pub mean_entropy: f64,
+    #[serde(serialize_with = "round")]
+    pub total_entropy: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference: Option<Vec<ReferenceRecord>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score_histogram: Option<Vec<ScoreBin>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_lengths: Option<Vec<SourceLength>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multiway: Option<Vec<MultiwayRecord>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anomalies: Option<Vec<AnomalyRecord>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat_families: Option<Vec<RepeatFamilyRecord>>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "round_option"
+    )]
+    pub family_rci: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag_groups: Option<Vec<TagGroupRecord>>,
+    /// GAF lines with a graph path, `None` with `--load-counts`.
+    #[serde(skip)]
+    pub alignments: Option<u64>,
+}
 
-    if n_repeats > 0 {
-        total_score / n_repeats as f64
-    } else {
-        0.0
-    }
+/// A row of the path pair table.
+#[derive(Debug, Clone, Serialize)]
+pub struct PairRecord {
+    pub path_1: String,
+    pub cov_1: u64,
+    pub path_2: String,
+    pub cov_2: u64,
+    #[serde(serialize_with = "round")]
+    pub recomb_score: f64,
+    /// `cov_1` and `cov_2` as fractions of their sum, only set with
+    /// `--relative-coverage`.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "round_option"
+    )]
+    pub rel_1: Option<f64>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "round_option"
+    )]
+    pub rel_2: Option<f64>,
+    /// Two-sided binomial test of the coverage split, only set with `--pvalue`.
+    /// Not rounded, as small p-values are the point.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pvalue: Option<f64>,
+    /// `pvalue` adjusted by Benjamini-Hochberg over all pairs, only set with `--fdr`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub qvalue: Option<f64>,
+    /// Whether `path_1` was read forward or reverse relative to its reference
+    /// orientation (see [`path_direction`]), only set with `--direction`. `path_2`
+    /// is read the other way.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<&'static str>,
+    /// Mean offset of the reads' first difference within the focal segment, from
+    /// their `cs`/`ds` tags, only set with `--breakpoints`; `null` if no read has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breakpoint: Option<Option<u64>>,
+    /// Only set with `--multi-focal`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focal_node: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focal_pos: Option<usize>,
+    /// Only set with `--emit-flank-seq`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub left_flank: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub right_flank: Option<String>,
+    /// Only set with `--flank-distance`; `null` if the flanks aren't connected
+    /// within `--max-hops` besides through the repeat.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flank_distance: Option<Option<usize>>,
 }
 
-/// Extract the focal repeat segment and number of distinct paths from two path strings.
-///
-/// Assumes paths are of the form `<seg1>seg2<seg3` or similar.
-///
-/// Returns:
-/// - `Some(repeat_id)` and `2` if both paths share the same central repeat segment
-/// - `(None, 0)` if paths are malformed or mismatched
-fn extract_repeat_info(p1: &str, p2: &str) -> (Option<String>, usize) {
-    let repeat_1 = extract_middle_segment(p1);
-    let repeat_2 = extract_middle_segment(p2);
+/// Segment ID -> family, from `--canonical-pair-key`.
+type SegmentFamilies = HashMap<String, String>;
 
-    if let (Some(r1), Some(r2)) = (repeat_1, repeat_2) {
-        if r1 == r2 {
-            return (Some(r1), 2); // this pair contains 2 paths
-        }
+/// How [`output_repeat_lines`] forms and displays pairs.
+#[derive(Debug, Clone, Copy, Default)]
+struct PairOptions<'a> {
+    /// Report a pair under every focal node it contains, with its position.
+    multi_focal: bool,
+    /// Print the pair rows. Otherwise only the aggregates are printed, but the
+    /// pairs are still computed and returned.
+    print_pairs: bool,
+    /// Display paths in their reference orientation (see [`canonical_path`]).
+    canonical: bool,
+    /// Add the coverages relative to the pair's total, which the score is based on.
+    relative: bool,
+    /// Add a binomial test p-value of each pair's coverage split.
+    pvalue: bool,
+    /// Add the p-values adjusted for multiple testing.
+    fdr: bool,
+    /// Label paths as read forward or reverse relative to their reference orientation.
+    direction: bool,
+    /// Weight each pair's score by its total coverage in the recombination potential.
+    coverage_weighted: bool,
+    /// Add the mean breakpoint offset of the reads of both paths.
+    breakpoints: Option<&'a HashMap<PathKey, PathSupport>>,
+    /// Flanks in the same family count as the same segment when matching
+    /// reverse complements.
+    families: Option<&'a SegmentFamilies>,
+    /// Append the flank sequences of `path_1` to each pair row.
+    flanks: Option<&'a FlankSequences>,
+    /// Append the distance between the flanks, avoiding the repeat, to each pair row.
+    distances: Option<&'a FlankDistances<'a>>,
+    /// Only pair the paths of these repeats.
+    inverted: Option<&'a HashSet<String>>,
+}
+
+/// How [`output_entropy_lines`] computes and displays the entropy table.
+#[derive(Debug, Clone, Copy, Default)]
+struct EntropyOptions<'a> {
+    /// Weight paths by total aligned length instead of read count.
+    length_weights: Option<&'a HashMap<PathKey, u64>>,
+    /// Detected repeats to list even without observed paths.
+    unsupported: Option<&'a [String]>,
+    /// Reverse-complement pairs of each repeat, for an `n_pairs` column.
+    pair_counts: Option<&'a HashMap<String, usize>>,
+    /// One row per repeat and position of the repeat in its paths.
+    multi_focal: bool,
+    /// Leave repeats with a single path out of the mean entropy.
+    multi_path_mean: bool,
+    /// Entropy over unordered flank pairs instead of paths (`--entropy-unit`).
+    flank_pairs: bool,
+    /// Check that path probabilities sum to 1 in release builds too (`--check`).
+    check: bool,
+}
+
+/// How [`compute_rci`] averages the repeats' terms.
+#[derive(Debug, Clone, Copy, Default)]
+struct RciOptions<'a> {
+    /// Structurally possible path counts per repeat, used as `P_r` instead of the
+    /// observed ones (`--rci-denominator possible`).
+    possible: Option<&'a HashMap<String, usize>>,
+    /// Weight each repeat's term by its share of the total coverage (`--rci-weight`).
+    coverage_weighted: bool,
+}
+
+/// The graph and search bound for `--flank-distance`.
+#[derive(Debug)]
+struct FlankDistances<'a> {
+    index: &'a crate::gfa::GraphIndex,
+    max_hops: usize,
+}
+
+impl FlankDistances<'_> {
+    /// The fewest links between the flanks of `path` without going through its
+    /// repeat, see [`crate::gfa::hops_avoiding`].
+    fn distance(&self, path: &Path) -> Option<usize> {
+        crate::gfa::hops_avoiding(
+            self.index,
+            path.from().segid.as_bytes(),
+            path.to().segid.as_bytes(),
+            path.repeat().segid.as_bytes(),
+            self.max_hops,
+        )
     }
-    (None, 0)
 }
 
-/// Extract the middle (repeat) segment from a path string.
-/// Assumes the path is made of three segments separated by direction indicators `<` or `>`.
-///
-/// Example:
-/// - Input: `"<u27>u25<u28"` → Output: `Some("u25")`
-fn extract_middle_segment(path: &str) -> Option<String> {
-    let split: Vec<&str> = path.split(['<', '>']).filter(|s| !s.is_empty()).collect();
-    if split.len() == 3 {
-        Some(split[1].to_string())
-    } else {
-        None
+/// Segment sequences for `--emit-flank-seq`.
+#[derive(Debug)]
+struct FlankSequences {
+    sequences: HashMap<String, Vec<u8>>,
+    /// Bases kept from each flank, next to the repeat.
+    length: usize,
+}
+
+impl FlankSequences {
+    /// The sequences of the segments either side of position `focal` in `path` (the
+    /// first and third for the centre), as read along the path, i.e. reverse
+    /// complemented if traversed `<`. Only the `length` bases next to the repeat are
+    /// kept: the end of the left flank and the start of the right one. Segments
+    /// without a sequence, or beyond the ends of the path, give `NA`.
+    fn flanks(&self, path: &Path, focal: usize) -> (String, String) {
+        let oriented = |segment: Option<&Segment>| {
+            let segment = segment?;
+            let seq = self
+                .sequences
+                .get(&segment.segid)
+                .filter(|seq| seq.as_slice() != b"*")?;
+            Some(match segment.orientation {
+                Orientation::Forward => seq.clone(),
+                Orientation::Backward => crate::seq::reverse_complement(seq),
+            })
+        };
+        let left = oriented(focal.checked_sub(1).and_then(|i| path.0.get(i))).map_or(
+            "NA".to_string(),
+            |seq| {
+                String::from_utf8_lossy(&seq[seq.len().saturating_sub(self.length)..]).into_owned()
+            },
+        );
+        let right = oriented(path.0.get(focal + 1)).map_or("NA".to_string(), |seq| {
+            String::from_utf8_lossy(&seq[..seq.len().min(self.length)]).into_owned()
+        });
+        (left, right)
     }
 }
 
-/// Identifies reverse-complement path pairs within each repeat group, computes their
-/// recombination score, prints all path pairs with scores, and reports:
-/// - The mean recombination potential
-/// - The recombination complexity index (RCI)
-fn output_repeat_lines(all_paths: &[Paths]) {
-    let mut revcomps = Vec::new();
-    let mut stdout = io::stdout();
-
-    for paths in all_paths {
-        let mut node_checker = Vec::new();
-
-        let path_vec = &paths.paths;
-        let path_vec: Vec<_> = path_vec.to_vec();
-
-        for ((_, p), c) in path_vec.iter() {
-            for ((_, p2), c2) in path_vec.iter().skip(1) {
-                let is_reverse = p.is_reverse(p2);
-                // if we hit a reverse path, combine them
-                // and push to the node checker
-                if is_reverse
-                    && !node_checker.contains(&p.to_string())
-                    && !node_checker.contains(&p2.to_string())
-                {
-                    revcomps.push((p.to_string(), *c, p2.to_string(), *c2));
-                    node_checker.push(p.to_string());
-                    node_checker.push(p2.to_string());
+/// Read segment families for `--canonical-pair-key`, see [`parse_segment_families`].
+fn load_segment_families(path: &std::path::Path) -> Result<SegmentFamilies> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open segment families: {:?}", path.as_os_str()))?;
+    parse_segment_families(BufReader::new(file))
+        .with_context(|| format!("Malformed segment families: {:?}", path.as_os_str()))
+}
+
+/// Parse segment families, one per line as whitespace-separated segment IDs, e.g.
+/// `u27 u27b`. Blank lines and lines starting with `#` are ignored. Each family is
+/// keyed by its first segment, and a segment may only belong to one family.
+fn parse_segment_families<R: BufRead>(reader: R) -> Result<SegmentFamilies> {
+    let mut families = SegmentFamilies::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let family = line.split_whitespace().next().expect("line is not blank");
+        for id in line.split_whitespace() {
+            if let Some(other) = families.insert(id.to_string(), family.to_string()) {
+                if other != family {
+                    anyhow::bail!(
+                        "line {}: segment {} is in the families of both {} and {}",
+                        i + 1,
+                        id,
+                        other,
+                        family
+                    );
                 }
             }
         }
     }
-    if !revcomps.is_empty() {
-        let _ = writeln!(stdout, "path_1\tcov_1\tpath_2\tcov_2\trecomb_score");
-        let mut recomb_scores = Vec::new();
+    Ok(families)
+}
 
-        for (p1, cov1, p2, cov2) in &revcomps {
-            let total = *cov1 as f64 + *cov2 as f64;
-            if total == 0.0 {
-                continue; // avoid division by zero
-            }
-            let rel1 = *cov1 as f64 / total;
-            let rel2 = *cov2 as f64 / total;
-            let score = 2.0 * rel1.min(rel2);
-            recomb_scores.push(score);
-            let _ = writeln!(stdout, "{}\t{}\t{}\t{}\t{:.3}", p1, cov1, p2, cov2, score);
-        }
+/// The path pair table with its summary statistics.
+#[derive(Debug, Clone, Default)]
+struct PairReport {
+    pairs: Vec<PairRecord>,
+    recombination_potential: f64,
+    rci: f64,
+    recomb_scores: Vec<f64>,
+    /// Reverse-complement pairs of each focal repeat, for `--n-pairs`.
+    pair_counts: HashMap<String, usize>,
+}
 
-        let recomb_potential: f64 = if !recomb_scores.is_empty() {
-            recomb_scores.iter().sum::<f64>() / recomb_scores.len() as f64
-        } else {
-            0.0
-        };
+/// A bin of the `--score-hist` recombination score histogram.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScoreBin {
+    #[serde(serialize_with = "round")]
+    pub start: f64,
+    #[serde(serialize_with = "round")]
+    pub end: f64,
+    pub pairs: usize,
+}
 
-        let _ = writeln!(stdout, "\nRecombination potential: {:.3}", recomb_potential);
-        let rci = compute_rci(&revcomps);
-        let _ = writeln!(stdout, "RCI: {:.3}", rci);
-    }
+/// A row of the entropy table.
+#[derive(Debug, Clone, Serialize)]
+pub struct EntropyRecord {
+    pub repeat_node: String,
+    pub path_count: usize,
+    /// `None` for a detected repeat without observed paths (`--show-unsupported`).
+    #[serde(serialize_with = "round_option")]
+    pub entropy: Option<f64>,
+    /// Reads traversing the repeat forward.
+    pub fwd_cov: u64,
+    /// Reads traversing the repeat reverse complemented.
+    pub rev_cov: u64,
+    /// The path with the most reads, `None` without observed paths.
+    pub top_path: Option<String>,
+    /// The top path's fraction of the repeat's reads.
+    #[serde(serialize_with = "round_option")]
+    pub top_path_frac: Option<f64>,
+    /// With `--multi-focal`, the position of the repeat in the row's paths: 1 for
+    /// centred paths, 0 for paths starting or ending at it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focal_pos: Option<usize>,
+    /// Reverse-complement pairs of the repeat in the pair table, only set with
+    /// `--n-pairs`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n_pairs: Option<usize>,
 }
 
-#[derive(Debug, Clone)]
-struct Paths {
-    // node ID, path, count of the path
-    paths: Vec<((String, Path), i32)>,
+/// A row of the `--multiway` table.
+#[derive(Debug, Clone, Serialize)]
+pub struct MultiwayRecord {
+    pub repeat_node: String,
+    pub path_count: usize,
+    #[serde(serialize_with = "round")]
+    pub balance: f64,
 }
 
-impl Paths {
-    fn new(inner: Vec<((String, Path), i32)>) -> Self {
-        Self { paths: inner }
+/// A row of the `--group-by-tag` table.
+#[derive(Debug, Clone, Serialize)]
+pub struct TagGroupRecord {
+    pub tag: String,
+    pub repeats: usize,
+    /// Counted observations, summed over repeats.
+    pub reads: u64,
+    #[serde(serialize_with = "round")]
+    pub recombination_potential: f64,
+    #[serde(serialize_with = "round")]
+    pub rci: f64,
+    #[serde(serialize_with = "round")]
+    pub mean_entropy: f64,
+}
+
+/// A row of the `--anomaly` table.
+#[derive(Debug, Clone, Serialize)]
+pub struct AnomalyRecord {
+    pub repeat_node: String,
+    pub path_count: usize,
+    /// Paths the graph allows through the repeat.
+    pub possible_paths: usize,
+    /// Observed entropy divided by `log2(possible_paths)`.
+    #[serde(serialize_with = "round")]
+    pub normalized_entropy: f64,
+    /// `normalized_entropy` minus 1, its value under uniform use of the possible paths.
+    #[serde(serialize_with = "round")]
+    pub deviation: f64,
+    /// `None` if all deviations are equal.
+    #[serde(serialize_with = "round_option")]
+    pub z_score: Option<f64>,
+}
+
+/// A row of the `--merge-shared-paths` table.
+#[derive(Debug, Clone, Serialize)]
+pub struct RepeatFamilyRecord {
+    /// Member repeats, joined with `+`.
+    pub family: String,
+    pub repeat_count: usize,
+    pub path_count: usize,
+    #[serde(serialize_with = "round")]
+    pub entropy: f64,
+    /// This family's `S_f * log2(P_f)` term of the family RCI.
+    #[serde(serialize_with = "round")]
+    pub rci: f64,
+}
+
+/// A row of the `--ref-paths` summary table.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferenceRecord {
+    pub repeat_node: String,
+    #[serde(flatten)]
+    support: ReferenceSupport,
+}
+
+/// Bin recombination scores (0 to 1) into `bins` equal-width bins. A score of
+/// exactly 1 falls in the last bin.
+fn score_histogram(scores: &[f64], bins: usize) -> Vec<ScoreBin> {
+    let mut hist: Vec<ScoreBin> = (0..bins)
+        .map(|i| ScoreBin {
+            start: i as f64 / bins as f64,
+            end: (i + 1) as f64 / bins as f64,
+            pairs: 0,
+        })
+        .collect();
+    for score in scores {
+        let bin = ((score * bins as f64) as usize).min(bins - 1);
+        hist[bin].pairs += 1;
     }
-    fn from_vec(vec: Vec<(&(String, String), &i32)>) -> Self {
-        let mut paths = Vec::new();
-        for ((id, path), count) in vec {
-            let path = string_to_path(path.to_string()).unwrap();
-            paths.push(((id.clone(), path), *count));
-        }
+    hist
+}
 
-        Self::new(paths)
+/// Print the `--score-hist` table. Bins are half-open, `[start, end)`, except
+/// the last one, which includes 1.
+fn output_histogram_lines<W: Write>(out: &mut W, hist: &[ScoreBin], precision: usize) {
+    let _ = writeln!(out, "\nscore_start\tscore_end\tpairs");
+    for bin in hist {
+        let _ = writeln!(
+            out,
+            "{:.*}\t{:.*}\t{}",
+            precision, bin.start, precision, bin.end, bin.pairs
+        );
     }
+}
 
-    /// Groups path+coverage entries by focal repeat node ID.
-    /// Assumes the list is sorted and that paths from the same node appear contiguously.
-    fn split_into_repeats(&self) -> Vec<Paths> {
-        let mut grouped: HashMap<String, Vec<((String, Path), i32)>> = HashMap::new();
+/// With `--long-reads`, how many counted observations came from GAF paths of
+/// each length.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SourceLength {
+    pub path_length: usize,
+    pub observations: u64,
+}
 
-        for entry in &self.paths {
-            let repeat_id = entry.0 .0.clone();
-            grouped.entry(repeat_id).or_default().push(entry.clone());
-        }
+/// Observations counted per source GAF path length.
+type SourceLengths = BTreeMap<usize, u64>;
 
-        grouped.into_values().map(Paths::new).collect()
+/// Print the `--long-reads` observations per source path length.
+fn output_source_length_lines<W: Write>(out: &mut W, lengths: &SourceLengths) {
+    let _ = writeln!(out, "path_length\tobservations");
+    for (length, observations) in lengths {
+        let _ = writeln!(out, "{}\t{}", length, observations);
     }
+    let _ = writeln!(out);
 }
 
-#[derive(Clone, Debug)]
-struct Segment {
-    orientation: Orientation,
-    segid: String,
+/// A (focal node ID, path string) key.
+type PathKey = (String, String);
+
+/// The reads supporting a (focal node, path).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct PathSupport {
+    /// Number of reads.
+    count: u64,
+    /// Total aligned path length of those reads (GAF columns 8-9).
+    length: u64,
+    /// Total offset of the first difference within the focal segment, and the
+    /// number of reads it was found in (`--breakpoints`).
+    breakpoint_offsets: u64,
+    breakpoint_reads: u64,
 }
 
-#[derive(Debug, Clone)]
-struct Path([Segment; 3]);
+impl PathSupport {
+    /// Add the reads of `other`.
+    fn add(&mut self, other: &PathSupport) {
+        self.count += other.count;
+        self.length += other.length;
+        self.breakpoint_offsets += other.breakpoint_offsets;
+        self.breakpoint_reads += other.breakpoint_reads;
+    }
+}
 
-impl Display for Path {
+/// The mean breakpoint offset of the reads of `supports`, to the nearest base, or
+/// `None` if none of them has one.
+fn mean_breakpoint<'a>(supports: impl IntoIterator<Item = &'a PathSupport>) -> Option<u64> {
+    let (offsets, reads) = supports.into_iter().fold((0, 0), |(o, r), s| {
+        (o + s.breakpoint_offsets, r + s.breakpoint_reads)
+    });
+    (reads > 0).then(|| (offsets + reads / 2) / reads)
+}
+
+/// The read count of each (focal node, path).
+fn path_counts(support: &HashMap<PathKey, PathSupport>) -> HashMap<PathKey, u64> {
+    support
+        .iter()
+        .map(|(key, s)| (key.clone(), s.count))
+        .collect()
+}
+
+/// A warning if there are focal nodes but none of them was found in a 3-node GAF
+/// path, which usually means the GAF was aligned to a different graph.
+fn focal_mismatch_warning(
+    nodes: &[String],
+    support: &HashMap<PathKey, PathSupport>,
+) -> Option<String> {
+    let matched: HashSet<&String> = support.keys().map(|(node, _)| node).collect();
+    if nodes.is_empty() || !matched.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "Warning: {} focal nodes searched, 0 matched any 3-node GAF path. \
+         Was the GAF aligned to a different graph than the GFA?",
+        nodes.len()
+    ))
+}
+
+/// GAF lines sampled by [`check_namespace`].
+const NAMESPACE_SAMPLE_LINES: usize = 10_000;
+
+/// Fail if more than `max` of the centre segments of the paths in the first
+/// lines of `gaf` are missing from the GFA (`--max-unmatched-frac`), so a GAF
+/// aligned to another graph is caught before it is counted.
+fn check_namespace<R: BufRead>(
+    gaf: R,
+    dialect: GafDialect,
+    segment_sizes: &HashMap<Vec<u8>, usize>,
+    max: f64,
+) -> Result<()> {
+    let (mut checked, mut unmatched) = (0, 0);
+    for (i, raw) in gaf.byte_lines().take(NAMESPACE_SAMPLE_LINES).enumerate() {
+        let raw = raw?;
+        let raw = crate::gfa::clean_line(&raw, i == 0);
+        let line = dialect.normalize(raw);
+        let Some(GAFPath::OrientIntv(steps)) =
+            parse_gaf::<_, Vec<OptField>>(line.split_str(b"\t")).map(|gaf| gaf.path)
+        else {
+            continue;
+        };
+        if let Some(GAFStep::SegId(_, id)) = steps.get(steps.len() / 2) {
+            checked += 1;
+            if !segment_sizes.contains_key(id) {
+                unmatched += 1;
+            }
+        }
+    }
+    if checked == 0 {
+        return Ok(());
+    }
+    let fraction = unmatched as f64 / checked as f64;
+    if fraction > max {
+        anyhow::bail!(
+            "{} of {} sampled GAF path centre(s) ({:.1}%) are not segments of the GFA, \
+             more than --max-unmatched-frac {}. Was the GAF aligned to a different graph?",
+            unmatched,
+            checked,
+            fraction * 100.0,
+            max
+        );
+    }
+    Ok(())
+}
+
+/// Minimum size of the flanking segments of an observed path (`--gaf-min-flank`).
+#[derive(Debug)]
+struct FlankFilter<'a> {
+    sizes: &'a HashMap<Vec<u8>, usize>,
+    min_size: usize,
+}
+
+impl FlankFilter<'_> {
+    /// Whether all segments of `steps` other than the focal one at `focal` are at
+    /// least `min_size` long. Segments missing from the GFA count as too short.
+    fn passes(&self, steps: &[GAFStep], focal: usize) -> bool {
+        steps.iter().enumerate().all(|(i, step)| {
+            let GAFStep::SegId(_, id) = step else {
+                return false;
+            };
+            i == focal || self.sizes.get(id.as_slice()).copied().unwrap_or(0) >= self.min_size
+        })
+    }
+}
+
+/// A reproducible random subsample of GAF lines (`--subsample`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Subsample {
+    fraction: f64,
+    seed: u64,
+}
+
+impl Subsample {
+    /// Whether to keep line `line` of the GAF. The decision depends only on the
+    /// seed and the line number, not on which other lines were read before it.
+    fn keeps(&self, line: usize) -> bool {
+        let mixed = SplitMix64(self.seed).next() ^ line as u64;
+        let draw = SplitMix64(mixed).next() >> 11;
+        (draw as f64) < self.fraction * (1u64 << 53) as f64
+    }
+}
+
+/// How the path column of a GAF is serialized (`--gaf-dialect`). Lines are
+/// rewritten into the standard form before parsing.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum GafDialect {
+    /// `>u1>u2<u3`, as written by GraphAligner and minigraph.
+    #[default]
+    Standard,
+    /// Steps separated by commas, `>u1,>u2,<u3`.
+    Comma,
+    /// Steps separated by spaces, `>u1 >u2 <u3`.
+    Space,
+    /// GFA `P` line notation, `u1+,u2+,u3-`.
+    SegmentList,
+}
+
+impl GafDialect {
+    const ALL: [GafDialect; 4] = [
+        GafDialect::Standard,
+        GafDialect::Comma,
+        GafDialect::Space,
+        GafDialect::SegmentList,
+    ];
+
+    /// Rewrite the path column (the 6th) of a GAF line into the standard form.
+    /// Other columns, and lines with fewer columns, are left unchanged.
+    fn normalize(self, line: &[u8]) -> Cow<'_, [u8]> {
+        if self == GafDialect::Standard {
+            return Cow::Borrowed(line);
+        }
+        let mut fields: Vec<Vec<u8>> = line.split_str(b"\t").map(<[u8]>::to_vec).collect();
+        if let Some(path) = fields.get_mut(5) {
+            *path = self.normalize_path(path);
+        }
+        Cow::Owned(fields.join(&b'\t'))
+    }
+
+    fn normalize_path(self, path: &[u8]) -> Vec<u8> {
+        match self {
+            GafDialect::Standard => path.to_vec(),
+            GafDialect::Comma => path.replace(b",", b""),
+            GafDialect::Space => path.replace(b" ", b""),
+            GafDialect::SegmentList => {
+                let mut out = Vec::with_capacity(path.len());
+                for step in path.split_str(b",") {
+                    let signed = step
+                        .split_last()
+                        .filter(|(sign, _)| matches!(sign, b'+' | b'-'))
+                        .and_then(|(sign, name)| Some((parse_orientation(*sign)?, name)));
+                    match signed {
+                        Some((orientation, name)) => {
+                            out.push(orientation_symbol(orientation) as u8);
+                            out.extend_from_slice(name);
+                        }
+                        // not in this dialect, leave it for parse_gaf to reject
+                        None => out.extend_from_slice(step),
+                    }
+                }
+                out
+            }
+        }
+    }
+
+    /// Whether the path column `path` looks like it was written in this dialect.
+    fn matches(self, path: &[u8]) -> bool {
+        let oriented = |step: &[u8]| matches!(step.first(), Some(b'>' | b'<'));
+        let steps = |sep: &str| {
+            let steps: Vec<&[u8]> = path.split_str(sep).collect();
+            steps.len() > 1 && steps.iter().all(|s| oriented(s))
+        };
+        match self {
+            GafDialect::Standard => {
+                oriented(path) && !path.contains(&b',') && !path.contains(&b' ')
+            }
+            GafDialect::Comma => steps(","),
+            GafDialect::Space => steps(" "),
+            GafDialect::SegmentList => path
+                .split_str(b",")
+                .all(|s| s.len() > 1 && matches!(s.last(), Some(b'+' | b'-'))),
+        }
+    }
+}
+
+impl Display for GafDialect {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut out = String::new();
-        for segment in self.0.iter() {
-            out.push_str(&format!(
-                "{}{}",
-                match segment.orientation {
-                    Orientation::Forward => ">",
-                    Orientation::Backward => "<",
-                },
-                segment.segid
-            ));
+        let name = match self {
+            GafDialect::Standard => "standard",
+            GafDialect::Comma => "comma",
+            GafDialect::Space => "space",
+            GafDialect::SegmentList => "segment-list",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for GafDialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        GafDialect::ALL
+            .into_iter()
+            .find(|d| d.to_string() == s)
+            .ok_or_else(|| {
+                let names: Vec<String> = GafDialect::ALL.iter().map(|d| d.to_string()).collect();
+                format!("expected one of {}, found \"{}\"", names.join(", "), s)
+            })
+    }
+}
+
+/// If `line` failed to parse as `dialect`, the other dialect its path column
+/// looks like, if any.
+fn dialect_mismatch(line: &[u8], dialect: GafDialect) -> Option<GafDialect> {
+    let path = line.split_str(b"\t").nth(5)?;
+    GafDialect::ALL
+        .into_iter()
+        .find(|d| *d != dialect && d.matches(path))
+}
+
+/// The paths counted in a GAF by [`count_paths`].
+#[derive(Debug, Default)]
+struct GafCounts {
+    support: HashMap<PathKey, PathSupport>,
+    /// The same counts split by the value of the `group_tag` field, if given.
+    tagged: TaggedSupport,
+    /// Counted observations per source GAF path length.
+    source_lengths: SourceLengths,
+    /// Lines without a graph path (`*` or empty).
+    unaligned: u64,
+    /// Lines skipped for bad path coordinates, see [`valid_path_range`].
+    malformed: u64,
+    /// Lines with a graph path, whether or not it was counted.
+    alignments: u64,
+    /// Paths dropped for revisiting a segment (`no_revisit`).
+    revisiting: u64,
+}
+
+impl GafCounts {
+    /// Add the counts of `other`, e.g. of another part of the same GAF.
+    fn add(&mut self, other: GafCounts) {
+        let add_support = |into: &mut HashMap<PathKey, PathSupport>,
+                           from: HashMap<PathKey, PathSupport>| {
+            for (key, s) in from {
+                into.entry(key).or_default().add(&s);
+            }
+        };
+        add_support(&mut self.support, other.support);
+        for (tag, support) in other.tagged {
+            add_support(self.tagged.entry(tag).or_default(), support);
         }
-        write!(f, "{}", out)
+        for (length, observations) in other.source_lengths {
+            *self.source_lengths.entry(length).or_default() += observations;
+        }
+        self.unaligned += other.unaligned;
+        self.malformed += other.malformed;
+        self.alignments += other.alignments;
+        self.revisiting += other.revisiting;
     }
 }
 
-impl Path {
-    fn from(&self) -> Segment {
-        self.0[0].clone()
+/// A segment ID, numbered by a [`SegmentInterner`].
+type SegmentIndex = u32;
+
+/// A (focal node, path) key with numbered segment IDs, as counted by
+/// [`count_line`]: counting a path seen before allocates nothing, and the
+/// [`PathKey`] strings are only made once per distinct path, by
+/// [`InternedCounts::resolve`].
+type InternedKey = (SegmentIndex, [(Orientation, SegmentIndex); 3]);
+
+/// Segment IDs numbered in the order they are first seen.
+#[derive(Debug, Default)]
+struct SegmentInterner {
+    indices: HashMap<Vec<u8>, SegmentIndex>,
+    ids: Vec<Vec<u8>>,
+}
+
+impl SegmentInterner {
+    fn intern(&mut self, id: &[u8]) -> SegmentIndex {
+        if let Some(&index) = self.indices.get(id) {
+            return index;
+        }
+        let index = self.ids.len() as SegmentIndex;
+        self.indices.insert(id.to_vec(), index);
+        self.ids.push(id.to_vec());
+        index
     }
 
-    fn repeat(&self) -> Segment {
-        self.0[1].clone()
+    /// A path step as its orientation and its numbered segment ID, or for a stable
+    /// interval, its `id:from-to`.
+    fn intern_step(&mut self, step: &GAFStep) -> (Orientation, SegmentIndex) {
+        match step {
+            GAFStep::SegId(orientation, id) => (*orientation, self.intern(id)),
+            GAFStep::StableIntv(orientation, id, from, to) => {
+                let interval = format!("{}:{}-{}", id.as_bstr(), from, to);
+                (*orientation, self.intern(interval.as_bytes()))
+            }
+        }
     }
+}
 
-    fn to(&self) -> Segment {
-        self.0[2].clone()
+/// [`GafCounts`] while a GAF is being counted, keyed by [`InternedKey`].
+#[derive(Debug, Default)]
+struct InternedCounts {
+    segments: SegmentInterner,
+    support: HashMap<InternedKey, PathSupport>,
+    tagged: BTreeMap<String, HashMap<InternedKey, PathSupport>>,
+    /// The read tallies, without support.
+    tallies: GafCounts,
+}
+
+impl InternedCounts {
+    /// Add the counts of `other`, numbering its segments as in `self`.
+    fn add(&mut self, other: InternedCounts) {
+        let renumbered: Vec<SegmentIndex> = other
+            .segments
+            .ids
+            .iter()
+            .map(|id| self.segments.intern(id))
+            .collect();
+        let rekey = |(focal, steps): InternedKey| -> InternedKey {
+            (
+                renumbered[focal as usize],
+                steps.map(|(o, id)| (o, renumbered[id as usize])),
+            )
+        };
+        let add_support = |into: &mut HashMap<InternedKey, PathSupport>,
+                           from: HashMap<InternedKey, PathSupport>| {
+            for (key, s) in from {
+                into.entry(rekey(key)).or_default().add(&s);
+            }
+        };
+        add_support(&mut self.support, other.support);
+        for (tag, support) in other.tagged {
+            add_support(self.tagged.entry(tag).or_default(), support);
+        }
+        self.tallies.add(other.tallies);
     }
-    // u25	132	<u28<u25>u27 (reverse)
-    // u25	126	<u27>u25>u28 (forward)
-    // these two paths are reverses of one another
-    fn is_reverse(&self, other: &Path) -> bool {
-        // the first and third orientations should differ, but should both have
-        // the same segid
-        let first_third_o = self.from().orientation != other.to().orientation;
-        let first_third_p = self.from().segid == other.to().segid;
 
-        // but also need to compare the third and the first
-        let third_first_o = self.to().orientation != other.from().orientation;
-        let third_first_p = self.to().segid == other.from().segid;
+    /// The counts keyed by focal node ID and path string.
+    fn resolve(self) -> GafCounts {
+        let ids: Vec<String> = self
+            .segments
+            .ids
+            .iter()
+            .map(|id| String::from_utf8_lossy(id).into_owned())
+            .collect();
+        let resolve_key = |(focal, steps): InternedKey| -> PathKey {
+            let path: String = steps
+                .iter()
+                .map(|&(o, id)| format!("{}{}", orientation_symbol(o), ids[id as usize]))
+                .collect();
+            (ids[focal as usize].clone(), path)
+        };
+        let resolve_support = |support: HashMap<InternedKey, PathSupport>| {
+            support
+                .into_iter()
+                .map(|(key, s)| (resolve_key(key), s))
+                .collect()
+        };
+        GafCounts {
+            support: resolve_support(self.support),
+            tagged: self
+                .tagged
+                .into_iter()
+                .map(|(tag, support)| (tag, resolve_support(support)))
+                .collect(),
+            ..self.tallies
+        }
+    }
+}
 
-        // the repeat should be the same, but the orientation should be different
-        let repeat_same = self.repeat().segid == other.repeat().segid;
-        let repeat_diff = self.repeat().orientation != other.repeat().orientation;
+/// Path counts per value of a GAF optional field (`--group-by-tag`).
+type TaggedSupport = BTreeMap<String, HashMap<PathKey, PathSupport>>;
 
-        // return the bool
-        first_third_o
-            && first_third_p
-            && third_first_o
-            && third_first_p
-            && repeat_same
-            && repeat_diff
+/// How the counts of a (focal node, path) in several GAFs are merged (`--combine`).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum Combine {
+    /// Add them up, as for pooled data.
+    #[default]
+    Sum,
+    /// Keep the largest.
+    Max,
+    /// Average them, rounded to the nearest read, as for replicates. A file
+    /// without the path counts as 0.
+    Mean,
+}
+
+/// Merge the counts of several GAFs. The path support, overall and per tag, is
+/// merged by `combine`, while the read tallies (source lengths, unaligned reads,
+/// alignments and revisiting paths) are always summed. Breakpoint offsets are
+/// summed too, so their mean is over the reads of every file.
+fn combine_counts(files: Vec<GafCounts>, combine: Combine) -> GafCounts {
+    let n = files.len() as u64;
+    if n == 1 {
+        return files.into_iter().next().expect("one file");
+    }
+
+    let merge = |supports: Vec<&HashMap<PathKey, PathSupport>>| {
+        let mut merged: HashMap<PathKey, PathSupport> = HashMap::new();
+        for support in supports {
+            for (key, s) in support {
+                let entry = merged.entry(key.clone()).or_default();
+                match combine {
+                    Combine::Sum | Combine::Mean => entry.add(s),
+                    Combine::Max => {
+                        entry.count = entry.count.max(s.count);
+                        entry.length = entry.length.max(s.length);
+                        entry.breakpoint_offsets += s.breakpoint_offsets;
+                        entry.breakpoint_reads += s.breakpoint_reads;
+                    }
+                }
+            }
+        }
+        if combine == Combine::Mean {
+            for s in merged.values_mut() {
+                s.count = (s.count + n / 2) / n;
+                s.length = (s.length + n / 2) / n;
+            }
+            merged.retain(|_, s| s.count > 0);
+        }
+        merged
+    };
+
+    let mut counts = GafCounts {
+        support: merge(files.iter().map(|f| &f.support).collect()),
+        ..Default::default()
+    };
+    let tags: BTreeSet<&String> = files.iter().flat_map(|f| f.tagged.keys()).collect();
+    for tag in tags {
+        let supports = files.iter().filter_map(|f| f.tagged.get(tag)).collect();
+        counts.tagged.insert(tag.clone(), merge(supports));
+    }
+    for file in &files {
+        for (length, observations) in &file.source_lengths {
+            *counts.source_lengths.entry(*length).or_default() += observations;
+        }
+        counts.unaligned += file.unaligned;
+        counts.malformed += file.malformed;
+        counts.alignments += file.alignments;
+        counts.revisiting += file.revisiting;
     }
+    counts
 }
 
-impl Default for Segment {
-    fn default() -> Self {
-        Segment {
-            orientation: Orientation::Forward,
-            segid: String::new(),
+/// How [`count_paths`] reads and counts a GAF.
+#[derive(Debug, Clone, Copy, Default)]
+struct CountOptions<'a> {
+    multi_focal: bool,
+    long_reads: bool,
+    min_flank: Option<&'a FlankFilter<'a>>,
+    dialect: GafDialect,
+    subsample: Option<Subsample>,
+    /// Optional field to also split the counts by, e.g. `b"BC"`.
+    group_tag: Option<&'a [u8]>,
+    /// Only count focal nodes traversed in this orientation, if they have one.
+    orientations: Option<&'a FocalOrientations>,
+    /// Drop paths visiting a segment more than once, e.g. `>a>a>b`.
+    no_revisit: bool,
+    /// Segment sizes, to locate the focal segment in the `cs`/`ds` difference
+    /// string of each read (`--breakpoints`).
+    breakpoints: Option<&'a HashMap<Vec<u8>, usize>>,
+    /// Stop after this many lines of the GAF, whether they count or not (`--head`).
+    head: Option<usize>,
+    /// Skip and tally the lines whose path coordinates can't give the read length
+    /// that weights the entropy (`--entropy-length-weight`).
+    length_weights: bool,
+}
+
+/// Required orientation of focal nodes given as `u25+` or `u25-` (`--focal`).
+type FocalOrientations = HashMap<String, Orientation>;
+
+/// Split `--focal` specs into node IDs and their required orientations. A spec
+/// ending in `+` or `-` only matches that orientation, while a plain ID matches
+/// either; a node given in both orientations matches either.
+pub fn parse_focal_specs(specs: &[String]) -> (Vec<String>, FocalOrientations) {
+    let mut ids: Vec<String> = Vec::new();
+    let mut orientations = FocalOrientations::new();
+    let mut unoriented = HashSet::new();
+    for spec in specs {
+        let (id, orientation) = match spec.as_bytes().split_last() {
+            Some((&sign @ (b'+' | b'-'), id)) if !id.is_empty() => {
+                (&spec[..id.len()], parse_orientation(sign))
+            }
+            _ => (spec.as_str(), None),
+        };
+        if !ids.iter().any(|known| known == id) {
+            ids.push(id.to_string());
+        }
+        match orientation {
+            Some(o) if !unoriented.contains(id) => {
+                if orientations.get(id).is_some_and(|known| *known != o) {
+                    orientations.remove(id);
+                    unoriented.insert(id.to_string());
+                } else {
+                    orientations.insert(id.to_string(), o);
+                }
+            }
+            _ => {
+                orientations.remove(id);
+                unoriented.insert(id.to_string());
+            }
         }
     }
+    (ids, orientations)
 }
 
-/// Parses a string representation of a 3-segment path (e.g. ">u28<u25>u26") into a Path object.
-/// Validates directionality (< or >) and ensures exactly 3 segments. Returns an error if malformed.
-fn string_to_path(s: String) -> Result<Path> {
-    let mut out: [Segment; 3] = core::array::from_fn(|_| Segment::default());
+/// The value of the `tag` optional field of a GAF line, without its `TG:T:`
+/// prefix, or `NA` if the line doesn't have it. Array values are comma-separated.
+fn tag_value(optional: &[OptField], tag: &[u8]) -> String {
+    let Some(field) = optional.iter().find(|field| field.tag == tag) else {
+        return "NA".to_string();
+    };
+    let join = |values: Vec<String>| values.join(",");
+    match &field.value {
+        OptFieldVal::A(c) => char::from(*c).to_string(),
+        OptFieldVal::Int(i) => i.to_string(),
+        OptFieldVal::Float(f) => f.to_string(),
+        OptFieldVal::Z(s) | OptFieldVal::J(s) => String::from_utf8_lossy(s).into_owned(),
+        OptFieldVal::H(h) => h.iter().map(|x| format!("{x:x}")).collect(),
+        OptFieldVal::BInt(a) => join(a.iter().map(i64::to_string).collect()),
+        OptFieldVal::BFloat(a) => join(a.iter().map(f32::to_string).collect()),
+    }
+}
+
+/// The path positions (0-based, on the concatenated segments of the GAF path) of
+/// the differences in a `cs` or `ds` difference string whose alignment starts at
+/// `path_start`. Substitutions and deletions are at the first differing path base,
+/// insertions at the base before which they are inserted. The `ds` brackets around
+/// uncertain indel sequences are ignored. `None` if the string is malformed.
+fn difference_positions(cs: &str, path_start: usize) -> Option<Vec<usize>> {
+    let cs: Vec<u8> = cs.bytes().filter(|b| !matches!(b, b'[' | b']')).collect();
+    let mut positions = Vec::new();
+    let mut position = path_start;
+    let mut i = 0;
+    // the bases or digits after an operator, `None` past the end of the string
+    let run = |i: usize, digits: bool| {
+        let n = cs
+            .get(i..)?
+            .iter()
+            .take_while(|b| {
+                if digits {
+                    b.is_ascii_digit()
+                } else {
+                    b.is_ascii_alphabetic()
+                }
+            })
+            .count();
+        Some(n)
+    };
+    while i < cs.len() {
+        let op = cs[i];
+        i += 1;
+        match op {
+            b':' => {
+                let n = run(i, true)?;
+                position += std::str::from_utf8(cs.get(i..i + n)?)
+                    .ok()?
+                    .parse::<usize>()
+                    .ok()?;
+                i += n;
+            }
+            b'=' => {
+                let n = run(i, false)?;
+                position += n;
+                i += n;
+            }
+            b'*' => {
+                if run(i, false)? < 2 {
+                    return None;
+                }
+                positions.push(position);
+                position += 1;
+                i += 2;
+            }
+            b'+' => {
+                positions.push(position);
+                i += run(i, false)?;
+            }
+            b'-' => {
+                let n = run(i, false)?;
+                positions.push(position);
+                position += n;
+                i += n;
+            }
+            b'~' => {
+                // a splice: two bases, the intron length, two bases
+                let n = run(i + 2, true)?;
+                position += std::str::from_utf8(cs.get(i + 2..i + 2 + n)?)
+                    .ok()?
+                    .parse::<usize>()
+                    .ok()?;
+                // the closing bases must be there too
+                cs.get(i + 2 + n..i + 2 + n + 2)?;
+                i += 2 + n + 2;
+            }
+            _ => return None,
+        }
+    }
+    Some(positions)
+}
+
+/// The offset within the focal segment, at `focal` in the GAF path `steps`, of the
+/// read's first difference inside it, if any. The offset is on the segment's
+/// forward strand, so reads through it either way are comparable. `None` too if a
+/// segment up to the focal one has no size.
+fn focal_breakpoint(
+    steps: &[GAFStep],
+    focal: usize,
+    sizes: &HashMap<Vec<u8>, usize>,
+    differences: &[usize],
+) -> Option<u64> {
+    let size = |step: &GAFStep| match step {
+        GAFStep::SegId(_, id) => sizes.get(id.as_slice()).copied(),
+        GAFStep::StableIntv(_, _, from, to) => Some(to.saturating_sub(*from)),
+    };
+    let mut start = 0;
+    for step in &steps[..focal] {
+        start += size(step)?;
+    }
+    let focal_size = size(&steps[focal])?;
+    let offset = differences
+        .iter()
+        .find(|&&position| (start..start + focal_size).contains(&position))
+        .map(|position| position - start)?;
+    let offset = match &steps[focal] {
+        GAFStep::SegId(Orientation::Backward, _)
+        | GAFStep::StableIntv(Orientation::Backward, ..) => focal_size - 1 - offset,
+        _ => offset,
+    };
+    Some(offset as u64)
+}
+
+/// Count the 3-node paths in a GAF stream, keyed by (focal node, path).
+///
+/// By default a path is recorded only under its centre segment. With `multi_focal`,
+/// it is recorded once under every focal segment it contains, so a read spanning
+/// two adjacent repeats counts towards both. With `min_flank`, a path is skipped
+/// for a focal node if either of its other two segments is too short.
+///
+/// Paths longer than 3 segments are skipped, unless `long_reads` is set, in which
+/// case each of their 3-segment windows is counted like a 3-node path. A read
+/// supports the same (focal node, window) at most once. The number of counted
+/// observations per source path length is returned alongside the counts.
+///
+/// With `no_revisit`, paths visiting a segment more than once are dropped and
+/// tallied instead, whole paths rather than windows with `long_reads`, so cycles
+/// are caught too.
+///
+/// Lines whose path is `*` or empty are counted as unaligned, not parsed. With
+/// `length_weights`, lines whose path coordinates can't give a read length (see
+/// [`valid_path_range`]) are skipped and counted as malformed. Blank lines and
+/// lines starting with `#` are skipped.
+///
+/// With a `group_tag`, each observation is also counted under the line's value of
+/// that optional field, or `NA` if it's missing.
+///
+/// Lines are read in batches of [`BATCH_LINES`], and each batch is counted in
+/// chunks of [`CHUNK_LINES`], concurrently with the `parallel` feature. Errors are
+/// reported for the first bad line either way.
+///
+/// Paths are counted by [`InternedKey`], and only turned into strings at the end.
+fn count_paths<R: BufRead>(
+    reader: R,
+    nodes: &[String],
+    options: &CountOptions,
+) -> Result<GafCounts> {
+    let mut counts = InternedCounts::default();
+    let mut batch: Vec<(usize, Vec<u8>)> = Vec::with_capacity(BATCH_LINES);
+    let mut lines = reader
+        .byte_lines()
+        .enumerate()
+        .take(options.head.unwrap_or(usize::MAX));
+    loop {
+        let mut read_error = None;
+        for (i, line) in lines.by_ref() {
+            match line {
+                Ok(line) => batch.push((i, line)),
+                Err(e) => {
+                    read_error = Some(e);
+                    break;
+                }
+            }
+            if batch.len() == BATCH_LINES {
+                break;
+            }
+        }
+        if batch.is_empty() && read_error.is_none() {
+            return Ok(counts.resolve());
+        }
+
+        let chunks: Vec<&[(usize, Vec<u8>)]> = batch.chunks(CHUNK_LINES).collect();
+        let chunk_counts = crate::parallel::map(&chunks, |chunk| {
+            let mut counts = InternedCounts::default();
+            for (i, line) in chunk.iter() {
+                count_line(*i, line, nodes, options, &mut counts)?;
+            }
+            Ok::<_, anyhow::Error>(counts)
+        });
+        for chunk in chunk_counts {
+            counts.add(chunk?);
+        }
+        if let Some(e) = read_error {
+            return Err(e.into());
+        }
+        batch.clear();
+    }
+}
+
+/// Whether a GAF path visits a segment more than once, in either orientation,
+/// as a tandem, a cycle or a mapping artifact would.
+fn revisits(steps: &[GAFStep]) -> bool {
+    let mut ids = HashSet::new();
+    steps.iter().any(|step| match step {
+        GAFStep::SegId(_, id) => !ids.insert(id),
+        _ => false,
+    })
+}
+
+/// Whether the path length, start and end columns of a GAF line are non-negative
+/// integers with `start <= end <= length`, so the aligned length `end - start`
+/// is a valid read weight.
+fn valid_path_range(columns: &[&[u8]]) -> bool {
+    let number = |i: usize| columns.get(i)?.to_str().ok()?.parse::<u64>().ok();
+    match (number(6), number(7), number(8)) {
+        (Some(length), Some(start), Some(end)) => start <= end && end <= length,
+        _ => false,
+    }
+}
+
+/// GAF lines read at once by [`count_paths`].
+const BATCH_LINES: usize = 1 << 16;
+/// GAF lines counted together, serially, within a batch.
+const CHUNK_LINES: usize = 1 << 10;
+
+/// Count the paths of the GAF line `raw`, the `i`th of its file (0-based), into
+/// `counts`. See [`count_paths`].
+fn count_line(
+    i: usize,
+    raw: &[u8],
+    nodes: &[String],
+    options: &CountOptions,
+    counts: &mut InternedCounts,
+) -> Result<()> {
+    let CountOptions {
+        multi_focal,
+        long_reads,
+        min_flank,
+        dialect,
+        subsample,
+        group_tag,
+        orientations,
+        no_revisit,
+        breakpoints,
+        head: _,
+        length_weights,
+    } = *options;
+
+    let raw = crate::gfa::clean_line(raw, i == 0);
+    if raw.trim().is_empty() || raw.starts_with(b"#") {
+        return Ok(());
+    }
+    if subsample.is_some_and(|subsample| !subsample.keeps(i)) {
+        return Ok(());
+    }
+    let line = dialect.normalize(raw);
+    let columns: Vec<&[u8]> = line.split_str(b"\t").collect();
+    // zero-step paths are not aligned to the graph at all
+    if matches!(columns.get(5).copied(), Some(b"" | b"*")) {
+        counts.tallies.unaligned += 1;
+        return Ok(());
+    }
+    // with length weights, a complete line with bad path coordinates is skipped
+    // rather than counted with a garbage weight
+    if length_weights && columns.len() >= 12 && !valid_path_range(&columns) {
+        counts.tallies.malformed += 1;
+        return Ok(());
+    }
+    let gaf = parse_gaf::<_, Vec<OptField>>(columns.iter().copied());
+    // a path in another dialect may still parse, e.g. as a stable ID, so check
+    // any path column that isn't in the standard form
+    let standard = columns
+        .get(5)
+        .is_some_and(|path| GafDialect::Standard.matches(path));
+    if gaf.is_none() || !standard {
+        if let Some(detected) = dialect_mismatch(raw, dialect) {
+            anyhow::bail!(
+                "Error parsing GAF line {}: the path column looks like the {} dialect, \
+                 not {}; try --gaf-dialect {}",
+                i,
+                detected,
+                dialect,
+                detected
+            );
+        }
+    }
+    let Some(gaf) = gaf else {
+        anyhow::bail!("Error parsing GAF line {}", i);
+    };
+    counts.tallies.alignments += 1;
+    let tag = group_tag.map(|tag| tag_value(&gaf.optional, tag));
+    let length = gaf.path_range.1.saturating_sub(gaf.path_range.0) as u64;
+    let differences = breakpoints.and_then(|_| {
+        let field = [b"cs", b"ds"]
+            .into_iter()
+            .find_map(|tag| gaf.optional.iter().find(|field| field.tag == *tag))?;
+        let OptFieldVal::Z(cs) = &field.value else {
+            return None;
+        };
+        difference_positions(cs.to_str().ok()?, gaf.path_range.0)
+    });
+    // get the path length
+    let path = gaf.path;
+    match &path {
+        GAFPath::StableId(_) => return Ok(()), // don't care about this
+        GAFPath::OrientIntv(vec) => {
+            // paths of length 3, or the 3-node windows of longer (e.g. Nanopore)
+            // reads with --long-reads
+            if vec.len() < 3 || (vec.len() > 3 && !long_reads) {
+                return Ok(());
+            }
+            if no_revisit && revisits(vec) {
+                counts.tallies.revisiting += 1;
+                return Ok(());
+            }
+            let mut seen = Vec::new();
+            for (start, window) in vec.windows(3).enumerate() {
+                let positions = if multi_focal { 0..3 } else { 1..2 };
+                for pos in positions {
+                    let GAFStep::SegId(orientation, id) = &window[pos] else {
+                        continue;
+                    };
+                    let node = std::str::from_utf8(id)?;
+                    if orientations
+                        .and_then(|o| o.get(node))
+                        .is_some_and(|wanted| wanted != orientation)
+                    {
+                        continue;
+                    }
+                    if min_flank.is_some_and(|filter| !filter.passes(window, pos)) {
+                        continue;
+                    }
+                    if !nodes.iter().any(|focal| focal == node) {
+                        continue;
+                    }
+                    let segments = &mut counts.segments;
+                    let key = (
+                        segments.intern(id),
+                        std::array::from_fn(|i| segments.intern_step(&window[i])),
+                    );
+                    // a path revisiting a focal node is still only counted once for it
+                    if !seen.contains(&key) {
+                        let mut read = PathSupport {
+                            count: 1,
+                            length,
+                            ..Default::default()
+                        };
+                        if let (Some(sizes), Some(differences)) = (breakpoints, &differences) {
+                            if let Some(offset) =
+                                focal_breakpoint(vec, start + pos, sizes, differences)
+                            {
+                                read.breakpoint_offsets = offset;
+                                read.breakpoint_reads = 1;
+                            }
+                        }
+                        counts.support.entry(key).or_default().add(&read);
+                        if let Some(tag) = &tag {
+                            counts
+                                .tagged
+                                .entry(tag.clone())
+                                .or_default()
+                                .entry(key)
+                                .or_default()
+                                .add(&read);
+                        }
+                        *counts.tallies.source_lengths.entry(vec.len()).or_default() += 1;
+                        seen.push(key);
+                    }
+                }
+            }
+        }
+    };
+
+    Ok(())
+}
+
+/// Print the `--group-by-tag` table: the recombination potential, RCI and mean
+/// entropy of the reads carrying each value of the tag, as if each group were a
+/// GAF of its own.
+fn output_tag_group_lines<W: Write>(
+    out: &mut W,
+    tagged: &TaggedSupport,
+    multi_focal: bool,
+    precision: usize,
+) -> Vec<TagGroupRecord> {
+    let options = PairOptions {
+        multi_focal,
+        ..Default::default()
+    };
+    let records: Vec<TagGroupRecord> = tagged
+        .iter()
+        .map(|(tag, support)| {
+            let counts = path_counts(support);
+            let groups = Paths::from_vec(counts.iter().collect()).split_into_repeats();
+            let pairs = output_repeat_lines(
+                &mut io::sink(),
+                &groups,
+                &options,
+                &RciOptions::default(),
+                precision,
+            );
+            let (mean_entropy, _, _) = output_entropy_lines(
+                &mut io::sink(),
+                &groups,
+                &EntropyOptions {
+                    multi_focal,
+                    ..Default::default()
+                },
+                precision,
+            )
+            .unwrap_or_default();
+            TagGroupRecord {
+                tag: tag.clone(),
+                repeats: groups.len(),
+                reads: counts.values().sum(),
+                recombination_potential: pairs.recombination_potential,
+                rci: pairs.rci,
+                mean_entropy,
+            }
+        })
+        .collect();
+
+    if !records.is_empty() {
+        let _ = writeln!(
+            out,
+            "\ntag\trepeats\treads\trecombination_potential\trci\tmean_entropy"
+        );
+        for r in &records {
+            let _ = writeln!(
+                out,
+                "{}\t{}\t{}\t{:.*}\t{:.*}\t{:.*}",
+                r.tag,
+                r.repeats,
+                r.reads,
+                precision,
+                r.recombination_potential,
+                precision,
+                r.rci,
+                precision,
+                r.mean_entropy
+            );
+        }
+    }
+    records
+}
+
+/// Write the raw (repeat, path, count) table, sorted by repeat and then path.
+fn write_counts<W: Write>(counts: &HashMap<(String, String), u64>, mut out: W) -> Result<()> {
+    let mut rows: Vec<_> = counts.iter().collect();
+    rows.sort();
+
+    writeln!(out, "repeat_node\tpath\tcount")?;
+    for ((node, path), count) in rows {
+        writeln!(out, "{}\t{}\t{}", node, path, count)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// A repeat's observed paths with their counts as one string, sorted by path, e.g.
+/// `<u3<u2<u1=4,>u1>u2>u3=5`. Repeats using the same paths equally often have
+/// identical profiles, whatever order the reads came in.
+fn path_profile(group: &Paths) -> String {
+    let mut paths: Vec<(String, u64)> = group
+        .paths
+        .iter()
+        .map(|((_, p), c)| (p.to_string(), *c))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|(path, count)| format!("{}={}", path, count))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Write a `repeat_node`, `profile` table with the [`path_profile`] of each repeat,
+/// sorted by repeat.
+fn write_path_profiles<W: Write>(groups: &[Paths], mut out: W) -> Result<()> {
+    writeln!(out, "repeat_node\tprofile")?;
+    for group in groups {
+        let Some(((repeat_id, _), _)) = group.paths.first() else {
+            continue;
+        };
+        writeln!(out, "{}\t{}", repeat_id, path_profile(group))?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Read a (repeat, path, count) table written by [`write_counts`], or by another
+/// tool with paths in `notation`. Paths are keyed in arrow notation either way.
+fn read_counts<R: BufRead>(reader: R, notation: PathNotation) -> Result<HashMap<PathKey, u64>> {
+    let mut lines = reader.byte_lines();
+    let header = lines.next().transpose()?.unwrap_or_default();
+    if header.as_slice() != b"repeat_node\tpath\tcount" {
+        anyhow::bail!(
+            "expected header \"repeat_node\\tpath\\tcount\", found \"{}\"",
+            header.to_str_lossy()
+        );
+    }
+
+    let mut counts = HashMap::new();
+    for (i, line) in lines.enumerate() {
+        let line = line?;
+        // line numbers are 1-based, after the header
+        let line_no = i + 2;
+        let fields: Vec<&[u8]> = line.split_str("\t").collect();
+        let [node, path, count] = fields.as_slice() else {
+            anyhow::bail!(
+                "line {}: expected 3 columns (repeat_node, path, count), found {}",
+                line_no,
+                fields.len()
+            );
+        };
+        let count: u64 = count
+            .to_str()
+            .ok()
+            .and_then(|c| c.parse().ok())
+            .with_context(|| {
+                format!(
+                    "line {}: invalid count \"{}\"",
+                    line_no,
+                    count.to_str_lossy()
+                )
+            })?;
+        let path = parse_path(path.to_str()?, notation)
+            .with_context(|| format!("line {}", line_no))?
+            .to_string();
+        let key = (node.to_str()?.to_string(), path);
+        if counts.contains_key(&key) {
+            anyhow::bail!(
+                "line {}: duplicate row for repeat {} and path {}",
+                line_no,
+                key.0,
+                key.1
+            );
+        }
+        counts.insert(key, count);
+    }
+    Ok(counts)
+}
+
+/// Observed paths through a repeat that follow a reference (`P` line) traversal,
+/// and those that deviate from it.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+struct ReferenceSupport {
+    ref_paths: usize,
+    ref_reads: u64,
+    recomb_paths: usize,
+    recomb_reads: u64,
+}
+
+/// Flag each observed path as reference-consistent (it is a 3-segment window of a
+/// GFA `P` line, on either strand) or recombinant, then summarise per repeat.
+///
+/// Returns the per-repeat summary, sorted by repeat ID.
+fn output_reference_lines<W: Write>(
+    out: &mut W,
+    groups: &[Paths],
+    reference: &HashSet<String>,
+) -> Vec<(String, ReferenceSupport)> {
+    let mut summary = Vec::new();
+    let mut rows = Vec::new();
+
+    for group in groups {
+        let Some(((repeat_id, _), _)) = group.paths.first() else {
+            continue;
+        };
+        let mut support = ReferenceSupport::default();
+        for ((_, path), count) in &group.paths {
+            let path = path.to_string();
+            let is_reference = reference.contains(&path);
+            if is_reference {
+                support.ref_paths += 1;
+                support.ref_reads += count;
+            } else {
+                support.recomb_paths += 1;
+                support.recomb_reads += count;
+            }
+            rows.push((repeat_id.clone(), path, *count, is_reference));
+        }
+        summary.push((repeat_id.clone(), support));
+    }
+    rows.sort();
+    summary.sort_by(|a, b| a.0.cmp(&b.0));
+
+    if !rows.is_empty() {
+        let _ = writeln!(out, "\nrepeat_node\tpath\tcount\treference");
+        for (node, path, count, is_reference) in &rows {
+            let flag = if *is_reference {
+                "reference"
+            } else {
+                "recombinant"
+            };
+            let _ = writeln!(out, "{}\t{}\t{}\t{}", node, path, count, flag);
+        }
+        let _ = writeln!(
+            out,
+            "\nrepeat_node\tref_paths\tref_reads\trecomb_paths\trecomb_reads"
+        );
+        for (node, s) in &summary {
+            let _ = writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}",
+                node, s.ref_paths, s.ref_reads, s.recomb_paths, s.recomb_reads
+            );
+        }
+    }
+
+    summary
+}
+
+/// Write one self-contained `<repeat_id>.tsv` per repeat group into `dir`, holding
+/// the repeat's path counts, its reverse-complement pairs and its entropy line.
+/// Returns the (repeat, path) of each file written.
+///
+/// Characters in segment IDs other than ASCII alphanumerics, `.`, `-` and `_` are
+/// replaced with `_` in file names.
+fn write_split_files(
+    dir: &std::path::Path,
+    groups: &[Paths],
+    options: &PairOptions,
+    paired_only: bool,
+    rci: &RciOptions,
+    length_weights: Option<&HashMap<PathKey, u64>>,
+    precision: usize,
+) -> Result<Vec<(String, PathBuf)>> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create split directory: {:?}", dir.as_os_str()))?;
+
+    let mut written: HashMap<String, String> = HashMap::new();
+    let mut files = Vec::new();
+    for group in groups {
+        let Some(((repeat_id, _), _)) = group.paths.first() else {
+            continue;
+        };
+        let file_name = format!("{}.tsv", sanitize_file_name(repeat_id));
+        if let Some(other) = written.insert(file_name.clone(), repeat_id.clone()) {
+            anyhow::bail!(
+                "Repeats {} and {} both map to split file {}",
+                other,
+                repeat_id,
+                file_name
+            );
+        }
+
+        let path = dir.join(&file_name);
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create split file: {:?}", path.as_os_str()))?;
+        let mut out = BufWriter::new(file);
+
+        let mut counts: Vec<_> = group
+            .paths
+            .iter()
+            .map(|((_, p), c)| (p.to_string(), *c))
+            .collect();
+        counts.sort();
+        if options.direction {
+            writeln!(out, "path\tcount\tdirection")?;
+            for (path, count) in counts {
+                writeln!(out, "{}\t{}\t{}", path, count, path_direction(&path))?;
+            }
+        } else {
+            writeln!(out, "path\tcount")?;
+            for (path, count) in counts {
+                writeln!(out, "{}\t{}", path, count)?;
+            }
+        }
+        writeln!(out)?;
+
+        let group = std::slice::from_ref(group);
+        // split files always list their pairs, as observed
+        let options = PairOptions {
+            print_pairs: true,
+            canonical: false,
+            ..*options
+        };
+        output_repeat_lines(&mut out, group, &options, rci, precision);
+        if paired_only {
+            let paired = [group[0].paired(options.families)];
+            output_entropy_lines(
+                &mut out,
+                &paired,
+                &EntropyOptions {
+                    length_weights,
+                    multi_focal: options.multi_focal,
+                    ..Default::default()
+                },
+                precision,
+            );
+        } else {
+            output_entropy_lines(
+                &mut out,
+                group,
+                &EntropyOptions {
+                    length_weights,
+                    multi_focal: options.multi_focal,
+                    ..Default::default()
+                },
+                precision,
+            );
+        }
+        out.flush()?;
+        files.push((repeat_id.clone(), path));
+    }
+
+    Ok(files)
+}
+
+/// Make a segment ID safe to use as a file name.
+fn sanitize_file_name(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// A (focal node ID, path) key with the number of reads supporting it.
+type PathEntry = ((String, Path), u64);
+
+/// The summed `weights` of `paths` per unordered pair of flanks, the segments other
+/// than the focal one, ignoring orientation: `>a>r>b`, `<b<r<a` and `>a<r>b` all
+/// count towards {a, b}. Classes are in flank order.
+fn flank_pair_weights(paths: &[PathEntry], weights: &[f64]) -> Vec<f64> {
+    let mut classes: BTreeMap<(&str, &str), f64> = BTreeMap::new();
+    for (((focal, path), _), weight) in paths.iter().zip(weights) {
+        let pos = path.position_of(focal).unwrap_or(1);
+        let mut flanks = (0..3)
+            .filter(|&i| i != pos)
+            .map(|i| path.0[i].segid.as_str());
+        let (a, b) = (flanks.next().unwrap(), flanks.next().unwrap());
+        *classes.entry((a.min(b), a.max(b))).or_default() += weight;
+    }
+    classes.into_values().collect()
+}
+
+/// Mean entropy, total entropy and per-repeat rows.
+type EntropyReport = (f64, f64, Vec<EntropyRecord>);
+
+/// How evenly a repeat's reads are spread over all of its observed paths: the
+/// Shannon entropy of path usage divided by its maximum, `log2(path_count)`. 1
+/// means all paths are equally used, e.g. three balanced alleles, and values near
+/// 0 mean one path dominates. A repeat with a single path has a balance of 0.
+fn multiway_balance(group: &Paths) -> Option<f64> {
+    let total: u64 = group.paths.iter().map(|(_, c)| c).sum();
+    if total == 0 {
+        return None;
+    }
+    let n = group.paths.iter().filter(|(_, c)| *c > 0).count();
+    if n < 2 {
+        return Some(0.0);
+    }
+    let entropy: f64 = group
+        .paths
+        .iter()
+        .map(|(_, c)| *c as f64 / total as f64)
+        .filter(|p| *p > 0.0)
+        .map(|p| -p * p.log2())
+        .sum();
+    Some(entropy / (n as f64).log2())
+}
+
+/// Print the `--multiway` table of each repeat's [`multiway_balance`] over all
+/// observed paths, with the mean over repeats.
+fn output_multiway_lines<W: Write>(
+    out: &mut W,
+    groups: &[Paths],
+    precision: usize,
+) -> Vec<MultiwayRecord> {
+    let records: Vec<MultiwayRecord> = groups
+        .iter()
+        .filter_map(|group| {
+            let ((repeat_id, _), _) = group.paths.first()?;
+            Some(MultiwayRecord {
+                repeat_node: repeat_id.clone(),
+                path_count: group.paths.len(),
+                balance: multiway_balance(group)?,
+            })
+        })
+        .collect();
+
+    if !records.is_empty() {
+        let _ = writeln!(out, "\nrepeat_node\tpath_count\tmultiway_balance");
+        for r in &records {
+            let _ = writeln!(
+                out,
+                "{}\t{}\t{:.*}",
+                r.repeat_node, r.path_count, precision, r.balance
+            );
+        }
+        let mean = records.iter().map(|r| r.balance).sum::<f64>() / records.len() as f64;
+        let _ = writeln!(out, "\nMean multiway balance: {:.*}", precision, mean);
+    }
+    records
+}
+
+/// When a repeat's deviation from its structural expectation is an `--anomaly`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AnomalyCutoff {
+    /// Its absolute deviation is above this.
+    Deviation(f64),
+    /// The absolute z-score of its deviation among all repeats is above this.
+    ZScore(f64),
+}
+
+/// The repeats whose path diversity is surprisingly high or low for the paths the
+/// graph allows through them (`--anomaly`).
+///
+/// A repeat using all of its `possible` paths equally has an entropy of
+/// `log2(possible)`. The deviation is the observed entropy divided by that, minus
+/// 1: negative if few of the possible paths are used, or used unevenly, and
+/// positive if reads follow more paths than the graph has (e.g. a collapsed
+/// repeat). Repeats without an entropy or with fewer than 2 possible paths are
+/// skipped, and only those beyond `cutoff` are returned, in `entropies` order.
+fn anomalies(
+    entropies: &[EntropyRecord],
+    possible: &HashMap<String, usize>,
+    cutoff: AnomalyCutoff,
+) -> Vec<AnomalyRecord> {
+    let mut records: Vec<AnomalyRecord> = entropies
+        .iter()
+        // the possible paths are those centred on the repeat
+        .filter(|e| e.focal_pos.is_none_or(|pos| pos == 1))
+        .filter_map(|e| {
+            let possible_paths = possible.get(&e.repeat_node).copied().unwrap_or(0);
+            if possible_paths < 2 {
+                return None;
+            }
+            let normalized_entropy = e.entropy? / (possible_paths as f64).log2();
+            Some(AnomalyRecord {
+                repeat_node: e.repeat_node.clone(),
+                path_count: e.path_count,
+                possible_paths,
+                normalized_entropy,
+                deviation: normalized_entropy - 1.0,
+                z_score: None,
+            })
+        })
+        .collect();
+
+    let n = records.len() as f64;
+    let mean = records.iter().map(|r| r.deviation).sum::<f64>() / n;
+    let sd = (records
+        .iter()
+        .map(|r| (r.deviation - mean).powi(2))
+        .sum::<f64>()
+        / n)
+        .sqrt();
+    for r in &mut records {
+        r.z_score = (sd > 0.0).then(|| (r.deviation - mean) / sd);
+    }
+
+    records.retain(|r| match cutoff {
+        AnomalyCutoff::Deviation(max) => r.deviation.abs() > max,
+        AnomalyCutoff::ZScore(max) => r.z_score.is_some_and(|z| z.abs() > max),
+    });
+    records
+}
+
+/// Print the `--anomaly` table, if any repeat was flagged.
+fn output_anomaly_lines<W: Write>(out: &mut W, records: &[AnomalyRecord], precision: usize) {
+    if records.is_empty() {
+        return;
+    }
+    let _ = writeln!(
+        out,
+        "\nrepeat_node\tpath_count\tpossible_paths\tnormalized_entropy\tdeviation\tz_score"
+    );
+    for r in records {
+        let z = r
+            .z_score
+            .map_or("NA".to_string(), |z| format!("{:.*}", precision, z));
+        let _ = writeln!(
+            out,
+            "{}\t{}\t{}\t{:.*}\t{:.*}\t{}",
+            r.repeat_node,
+            r.path_count,
+            r.possible_paths,
+            precision,
+            r.normalized_entropy,
+            precision,
+            r.deviation,
+            z
+        );
+    }
+}
+
+/// Jaccard overlap of two repeats' observed path sets.
+fn path_set_jaccard(a: &Paths, b: &Paths) -> f64 {
+    let set = |group: &Paths| -> HashSet<String> {
+        group
+            .paths
+            .iter()
+            .map(|((_, p), _)| p.to_string())
+            .collect()
+    };
+    let (a, b) = (set(a), set(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/// Cluster repeats whose path sets have a [`path_set_jaccard`] above `threshold`,
+/// transitively. Returns the indices into `groups` of each family, in the order of
+/// their first member.
+fn shared_path_families(groups: &[&Paths], threshold: f64) -> Vec<Vec<usize>> {
+    let mut family: Vec<usize> = (0..groups.len()).collect();
+    for i in 0..groups.len() {
+        for j in i + 1..groups.len() {
+            if family[i] != family[j] && path_set_jaccard(groups[i], groups[j]) > threshold {
+                let (keep, merge) = (family[i].min(family[j]), family[i].max(family[j]));
+                for f in family.iter_mut().filter(|f| **f == merge) {
+                    *f = keep;
+                }
+            }
+        }
+    }
+    let mut families: Vec<Vec<usize>> = Vec::new();
+    for (i, f) in family.iter().enumerate() {
+        match families.iter_mut().find(|members| family[members[0]] == *f) {
+            Some(members) => members.push(i),
+            None => families.push(vec![i]),
+        }
+    }
+    families
+}
+
+/// Print the `--merge-shared-paths` table: the entropy and RCI term of each family
+/// of repeats with overlapping path sets, so reads spanning adjacent repeats are
+/// counted once.
+///
+/// A family's paths are the union of its members' paths, each with the highest
+/// count any member saw it with; its pairs are the members'
+/// pairs, deduplicated. The family RCI is the mean `S_f * log2(P_f)` over families
+/// with a pair and more than one path, as for [`compute_rci`].
+///
+/// Returns:
+/// - (per-family rows, family RCI)
+fn output_family_lines<W: Write>(
+    out: &mut W,
+    groups: &[Paths],
+    threshold: f64,
+    families: Option<&SegmentFamilies>,
+    precision: usize,
+) -> (Vec<RepeatFamilyRecord>, f64) {
+    let groups: Vec<&Paths> = groups.iter().filter(|g| !g.paths.is_empty()).collect();
+    let mut records = Vec::new();
+    let mut rci_terms = Vec::new();
+
+    for members in shared_path_families(&groups, threshold) {
+        let mut counts: BTreeMap<String, u64> = BTreeMap::new();
+        let mut pairs: BTreeMap<(String, String), f64> = BTreeMap::new();
+        let mut names = Vec::new();
+        for &m in &members {
+            let group = groups[m];
+            names.push(group.paths[0].0 .0.clone());
+            for ((_, p), c) in &group.paths {
+                let count = counts.entry(p.to_string()).or_default();
+                *count = (*count).max(*c);
+            }
+            for ((p1, c1, p2, c2), _) in pair_paths(group, families) {
+                let total = (c1 + c2) as f64;
+                if total > 0.0 {
+                    let key = (p1.clone().min(p2.clone()), p1.max(p2));
+                    pairs.insert(key, 2.0 * (c1.min(c2) as f64 / total));
+                }
+            }
+        }
+
+        let total: u64 = counts.values().sum();
+        let entropy: f64 = counts
+            .values()
+            .map(|c| *c as f64 / total as f64)
+            .filter(|p| *p > 0.0)
+            .map(|p| -p * p.log2())
+            .sum();
+        let path_count = counts.len();
+        let rci = if !pairs.is_empty() && path_count > 1 {
+            let s_f = pairs.values().sum::<f64>() / pairs.len() as f64;
+            let term = s_f * (path_count as f64).log2();
+            rci_terms.push(term);
+            term
+        } else {
+            0.0
+        };
+        records.push(RepeatFamilyRecord {
+            family: names.join("+"),
+            repeat_count: members.len(),
+            path_count,
+            entropy,
+            rci,
+        });
+    }
+
+    let rci = if rci_terms.is_empty() {
+        0.0
+    } else {
+        rci_terms.iter().sum::<f64>() / rci_terms.len() as f64
+    };
+    if !records.is_empty() {
+        let _ = writeln!(out, "\nfamily\trepeat_count\tpath_count\tentropy\trci");
+        for r in &records {
+            let _ = writeln!(
+                out,
+                "{}\t{}\t{}\t{:.*}\t{:.*}",
+                r.family, r.repeat_count, r.path_count, precision, r.entropy, precision, r.rci
+            );
+        }
+        let mean = records.iter().map(|r| r.entropy).sum::<f64>() / records.len() as f64;
+        let _ = writeln!(out, "\nMean family entropy: {:.*}", precision, mean);
+        let _ = writeln!(out, "Family RCI: {:.*}", precision, rci);
+    }
+    (records, rci)
+}
+
+/// Compute the Shannon entropy of path usage for each repeat node.
+/// This reflects the diversity of path usage through each focal repeat.
+///
+/// For each repeat node:
+/// - Count all distinct 3-node paths
+/// - Sum their coverages
+/// - Compute entropy:
+///   H = -sum(p_i * log2(p_i)) over all paths i
+///
+/// Floats are printed with `precision` decimal places.
+///
+/// With `length_weights`, each path's probability is its share of the total aligned
+/// read length through the repeat rather than its share of reads, so longer reads
+/// carry more weight.
+///
+/// `fwd_cov` and `rev_cov` split the repeat's read count by the orientation of the
+/// repeat in each path; a strong strand bias suggests artifacts rather than genuine
+/// recombination.
+///
+/// `top_path` is the repeat's most-read path, and `top_path_frac` its share of the
+/// reads (not weighted by length): near 1 for a repeat without recombination, near
+/// `1 / path_count` for even usage.
+///
+/// Paths with a weight of 0, e.g. a length of 0 with `length_weights`, are left
+/// out of `path_count` and the entropy, so they don't overstate path diversity.
+///
+/// With `unsupported`, every node in it without a row, i.e. detected but without
+/// observed paths, gets one with a `path_count` of 0 and an `NA` entropy. These
+/// rows don't count towards the mean and total entropy.
+///
+/// With `multi_focal`, a repeat's paths are split by the position of the repeat in
+/// them (see [`Paths::split_by_focal_pos`]), with one row per position and a
+/// `focal_pos` column, so paths merely starting or ending at the repeat aren't
+/// mixed with those centred on it.
+///
+/// With `flank_pairs`, the paths of a repeat are first collapsed into their
+/// unordered flank pairs (see [`flank_pair_weights`]), so `path_count` and the
+/// entropy count distinct flank partners rather than oriented paths.
+///
+/// With `multi_path_mean`, rows with a single path are still listed, with their
+/// entropy of 0, but left out of the mean entropy, as the RCI leaves out repeats
+/// with `path_count <= 1`.
+///
+/// Returns:
+/// - (mean_entropy, total_entropy, per-repeat rows)
+fn output_entropy_lines<W: Write>(
+    out: &mut W,
+    groups: &[Paths],
+    options: &EntropyOptions,
+    precision: usize,
+) -> Option<EntropyReport> {
+    let EntropyOptions {
+        length_weights,
+        unsupported,
+        pair_counts,
+        multi_focal,
+        multi_path_mean,
+        flank_pairs,
+        check,
+    } = *options;
+    let mut entropies = Vec::new();
+
+    let groups: Vec<(Option<usize>, Paths)> = if multi_focal {
+        groups
+            .iter()
+            .flat_map(|group| group.split_by_focal_pos())
+            .map(|(pos, group)| (Some(pos), group))
+            .collect()
+    } else {
+        groups.iter().map(|group| (None, group.clone())).collect()
+    };
+    for (focal_pos, group) in &groups {
+        if group.paths.is_empty() {
+            continue;
+        }
+
+        let repeat_id = &group.paths[0].0 .0; // all paths share this repeat node
+        let raw_weights: Vec<u64> = group
+            .paths
+            .iter()
+            .map(|((id, path), c)| match length_weights {
+                Some(lengths) => lengths
+                    .get(&(id.clone(), path.to_string()))
+                    .copied()
+                    .unwrap_or(0),
+                None => *c,
+            })
+            .collect();
+        let weights: Vec<f64> = raw_weights.iter().map(|&w| w as f64).collect();
+        let weights = if flank_pairs {
+            flank_pair_weights(&group.paths, &weights)
+        } else {
+            weights
+        };
+        let total_cov: f64 = weights.iter().sum();
+
+        if total_cov == 0.0 {
+            continue;
+        }
+        if cfg!(debug_assertions) || check {
+            // the integer support, summed apart from the (possibly collapsed) weights
+            let support: u64 = raw_weights.iter().sum();
+            check_probabilities(repeat_id, &weights, support as f64);
+        }
+
+        // unweighted paths are not supported, so neither count nor carry entropy
+        let supported_paths: Vec<f64> = weights.into_iter().filter(|w| *w > 0.0).collect();
+        let mut entropy = 0.0;
+        for cov in &supported_paths {
+            let p = *cov / total_cov;
+            entropy -= p * (p.ln() / LN_2);
+        }
+
+        let (fwd_cov, rev_cov) = group.orientation_coverage();
+        let (top_path, top_path_frac) = group.top_path();
+        entropies.push(EntropyRecord {
+            repeat_node: repeat_id.clone(),
+            path_count: supported_paths.len(),
+            entropy: Some(entropy),
+            fwd_cov,
+            rev_cov,
+            top_path: Some(top_path),
+            top_path_frac: Some(top_path_frac),
+            focal_pos: *focal_pos,
+            n_pairs: pair_counts.map(|counts| counts.get(repeat_id).copied().unwrap_or(0)),
+        });
+    }
+
+    // a single path has an entropy of 0, which says nothing about diversity
+    let supported = entropies
+        .iter()
+        .filter(|e| !multi_path_mean || e.path_count > 1)
+        .count();
+    let total_entropy = entropies.iter().filter_map(|e| e.entropy).sum::<f64>();
+    let mean_entropy = if supported > 0 {
+        total_entropy / supported as f64
+    } else {
+        0.0
+    };
+
+    if let Some(nodes) = unsupported {
+        for node in nodes {
+            if !entropies.iter().any(|e| &e.repeat_node == node) {
+                entropies.push(EntropyRecord {
+                    repeat_node: node.clone(),
+                    path_count: 0,
+                    entropy: None,
+                    fwd_cov: 0,
+                    rev_cov: 0,
+                    top_path: None,
+                    top_path_frac: None,
+                    focal_pos: None,
+                    n_pairs: pair_counts.map(|_| 0),
+                });
+            }
+        }
+        entropies.sort_by(|a, b| a.repeat_node.cmp(&b.repeat_node));
+    }
+
+    if !entropies.is_empty() {
+        let _ = write!(
+            out,
+            "\nrepeat_node\tpath_count\tentropy\tfwd_cov\trev_cov\ttop_path\ttop_path_frac"
+        );
+        if multi_focal {
+            let _ = write!(out, "\tfocal_pos");
+        }
+        if pair_counts.is_some() {
+            let _ = write!(out, "\tn_pairs");
+        }
+        let _ = writeln!(out);
+        for e in &entropies {
+            let entropy = e
+                .entropy
+                .map_or("NA".to_string(), |h| format!("{:.*}", precision, h));
+            let top_path_frac = e
+                .top_path_frac
+                .map_or("NA".to_string(), |f| format!("{:.*}", precision, f));
+            let _ = write!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                e.repeat_node,
+                e.path_count,
+                entropy,
+                e.fwd_cov,
+                e.rev_cov,
+                e.top_path.as_deref().unwrap_or("NA"),
+                top_path_frac
+            );
+            if multi_focal {
+                let pos = e.focal_pos.map_or("NA".to_string(), |pos| pos.to_string());
+                let _ = write!(out, "\t{}", pos);
+            }
+            if let Some(n_pairs) = e.n_pairs {
+                let _ = write!(out, "\t{}", n_pairs);
+            }
+            let _ = writeln!(out);
+        }
+        let _ = writeln!(out, "\nMean entropy: {:.*}", precision, mean_entropy);
+        let _ = writeln!(out, "Total entropy: {:.*}", precision, total_entropy);
+        return Some((mean_entropy, total_entropy, entropies));
+    }
+
+    None
+}
+
+/// Check that the path probabilities `weight / total` of `repeat` sum to 1, warning
+/// on stderr with the observed sum if they drift further than [`PROBABILITY_EPSILON`],
+/// which means a counting or overflow bug. Returns the sum if it drifted.
+///
+/// `total` must be accumulated independently of `weights`, e.g. as the integer sum
+/// of the raw support: checked against `weights.sum()` itself, only float rounding
+/// could ever be caught, not weight lost or duplicated on the way to `weights`.
+///
+/// Always runs in debug builds, and with `--check` in release builds.
+fn check_probabilities(repeat: &str, weights: &[f64], total: f64) -> Option<f64> {
+    let sum = weights.iter().map(|w| w / total).sum::<f64>();
+    if (sum - 1.0).abs() <= PROBABILITY_EPSILON {
+        return None;
+    }
+    eprintln!(
+        "Warning: path probabilities of repeat {} sum to {}, not 1",
+        repeat, sum
+    );
+    Some(sum)
+}
+
+/// Compute the Recombination Complexity Index (RCI) from a list of recombination path pairs.
+///
+/// Each tuple in `revcomps` represents a pair of paths that are reverse complements,
+/// along with their coverage values. The RCI summarizes both:
+///
+/// 1. **Coverage balance** between the forward and reverse paths
+/// 2. **Path diversity** through repeat nodes (more paths = higher isomeric potential)
+///
+/// The RCI is computed as:
+///
+/// ```text
+///     RCI = (1 / R) * Σ [S_r * log2(P_r)]
+/// ```
+///
+/// Where:
+/// - `R` is the number of repeat nodes (focal segments)
+/// - `P_r` is the number of distinct paths through repeat node `r`, or, if `possible`
+///   is given, the number of paths the graph allows through it
+/// - `S_r` is the average recombination score for repeat `r`, where:
+///   `recomb_score = 2 * min(rel_cov1, rel_cov2)`
+///
+/// With `coverage_weighted`, the mean over repeats is instead weighted by each
+/// repeat's share of the total coverage of the pairs:
+///
+/// ```text
+///     RCI = Σ [(C_r / C) * S_r * log2(P_r)]
+/// ```
+///
+/// where `C_r` is the summed `cov1 + cov2` of the pairs of `r`, and `C` that of
+/// all repeats in the sum. The weights are fractions, so scaling every coverage
+/// by a constant leaves the RCI unchanged.
+///
+/// # Arguments
+/// * `revcomps` - A vector of tuples (path1, cov1, path2, cov2)
+/// * `options` - The possible path counts per repeat node (`--rci-denominator possible`)
+///   and whether to weight repeats by their share of the coverage (`--rci-weight coverage`)
+///
+/// # Returns
+/// * `f64` - The recombination complexity index (RCI)
+///
+fn compute_rci(revcomps: &Vec<(String, u64, String, u64)>, options: &RciOptions) -> f64 {
+    let RciOptions {
+        possible,
+        coverage_weighted,
+    } = *options;
+    // Map of repeat node ID to its recombination scores, path count and coverage
+    let mut repeat_groups: HashMap<String, (Vec<f64>, usize, f64)> = HashMap::new();
+
+    for (p1, cov1, p2, cov2) in revcomps {
+        let total_cov = *cov1 as f64 + *cov2 as f64;
+        if total_cov == 0.0 {
+            continue; // skip zero-coverage cases
+        }
+
+        let rel1 = *cov1 as f64 / total_cov;
+        let recomb_score = 2.0 * rel1.min(1.0 - rel1);
+
+        // Parse the repeat node from the middle segment of either path
+        if let (Some(repeat_id), path_count) = extract_repeat_info(p1, p2) {
+            repeat_groups
+                .entry(repeat_id)
+                .and_modify(|(scores, count, cov)| {
+                    scores.push(recomb_score);
+                    *count += path_count;
+                    *cov += total_cov;
+                })
+                .or_insert((vec![recomb_score], path_count, total_cov));
+        }
+    }
+
+    // Compute average S_r * log2(P_r) for each repeat
+    let mut total_score = 0.0;
+    let mut total_weight = 0.0;
+
+    for (repeat_id, (scores, path_count, cov)) in repeat_groups {
+        let path_count = match possible {
+            Some(possible) => possible.get(&repeat_id).copied().unwrap_or(0),
+            None => path_count,
+        };
+        if scores.is_empty() || path_count <= 1 {
+            continue;
+        }
+
+        let s_r: f64 = scores.iter().sum::<f64>() / scores.len() as f64;
+        let p_r: f64 = path_count as f64;
+
+        let score = s_r * (p_r.ln() / LN_2); // log2
+        let weight = if coverage_weighted { cov } else { 1.0 };
+        total_score += weight * score;
+        total_weight += weight;
+    }
+
+    if total_weight > 0.0 {
+        total_score / total_weight
+    } else {
+        0.0
+    }
+}
+
+impl PairRecord {
+    /// The pair's focal repeat: its `focal_node`, or else the middle segment both its
+    /// paths share.
+    pub fn repeat(&self) -> Option<String> {
+        self.focal_node
+            .clone()
+            .or_else(|| extract_repeat_info(&self.path_1, &self.path_2).0)
+    }
+}
+
+/// Extract the focal repeat segment and number of distinct paths from two path strings.
+///
+/// Assumes paths are of the form `<seg1>seg2<seg3` or similar.
+///
+/// Returns:
+/// - `Some(repeat_id)` and `2` if both paths share the same central repeat segment
+/// - `(None, 0)` if paths are malformed or mismatched
+fn extract_repeat_info(p1: &str, p2: &str) -> (Option<String>, usize) {
+    let repeat_1 = extract_middle_segment(p1);
+    let repeat_2 = extract_middle_segment(p2);
+
+    if let (Some(r1), Some(r2)) = (repeat_1, repeat_2) {
+        if r1 == r2 {
+            return (Some(r1), 2); // this pair contains 2 paths
+        }
+    }
+    (None, 0)
+}
+
+/// Extract the middle (repeat) segment from a path string.
+/// Assumes the path is made of three segments separated by direction indicators `<` or `>`.
+///
+/// Example:
+/// - Input: `"<u27>u25<u28"` → Output: `Some("u25")`
+fn extract_middle_segment(path: &str) -> Option<String> {
+    let split: Vec<&str> = path.split(['<', '>']).filter(|s| !s.is_empty()).collect();
+    if split.len() == 3 {
+        Some(split[1].to_string())
+    } else {
+        None
+    }
+}
+
+/// Identifies reverse-complement path pairs within each repeat group, computes their
+/// recombination score, prints all path pairs with scores, and reports:
+/// - The mean recombination potential
+/// - The recombination complexity index (RCI)
+///
+/// With `multi_focal`, a pair is listed once per focal node it passes through, with
+/// `focal_node` and `focal_pos` (0-based position in `path_1`) columns added. The
+/// recombination potential averages over every listed pair, while the RCI only uses
+/// pairs whose focal node is the path centre, so it is unaffected by `multi_focal`.
+///
+/// See [`PairOptions`] for how pairs are formed and displayed.
+///
+/// Returns the reported pairs with the recombination potential and RCI.
+fn output_repeat_lines<W: Write>(
+    out: &mut W,
+    all_paths: &[Paths],
+    options: &PairOptions,
+    rci: &RciOptions,
+    precision: usize,
+) -> PairReport {
+    let PairOptions {
+        multi_focal,
+        print_pairs,
+        canonical,
+        relative,
+        pvalue,
+        fdr,
+        direction,
+        coverage_weighted,
+        breakpoints,
+        families,
+        flanks,
+        distances,
+        inverted,
+    } = *options;
+    let mut revcomps = Vec::new();
+    // focal node and its position in path_1, for each entry in `revcomps`
+    let mut focals = Vec::new();
+
+    let all_paths: Vec<&Paths> = all_paths
+        .iter()
+        .filter(|paths| keeps_repeat(paths, inverted))
+        .collect();
+    // repeats are paired independently, so concurrently with the parallel feature
+    for pairs in crate::parallel::map(&all_paths, |paths| pair_paths(paths, families)) {
+        for (pair, focal) in pairs {
+            revcomps.push(pair);
+            focals.push(focal);
+        }
+    }
+    let mut report = PairReport::default();
+    if !revcomps.is_empty() {
+        // the correction needs every p-value before any row is printed
+        let pvalues: Vec<Option<f64>> = revcomps
+            .iter()
+            .map(|(_, cov1, _, cov2)| {
+                let total = cov1 + cov2;
+                (pvalue && total > 0).then(|| crate::stats::binomial_pvalue(*cov1, total))
+            })
+            .collect();
+        let mut qvalues = vec![None; pvalues.len()];
+        if fdr {
+            let tested: Vec<f64> = pvalues.iter().flatten().copied().collect();
+            let mut adjusted = crate::stats::benjamini_hochberg(&tested).into_iter();
+            for (q, p) in qvalues.iter_mut().zip(&pvalues) {
+                if p.is_some() {
+                    *q = adjusted.next();
+                }
+            }
+        }
+
+        if print_pairs {
+            let _ = write!(out, "path_1\tcov_1\tpath_2\tcov_2\trecomb_score");
+            if relative {
+                let _ = write!(out, "\trel_1\trel_2");
+            }
+            if pvalue {
+                let _ = write!(out, "\tpvalue");
+            }
+            if fdr {
+                let _ = write!(out, "\tqvalue");
+            }
+            if direction {
+                let _ = write!(out, "\tdirection");
+            }
+            if breakpoints.is_some() {
+                let _ = write!(out, "\tbreakpoint");
+            }
+            if multi_focal {
+                let _ = write!(out, "\tfocal_node\tfocal_pos");
+            }
+            if flanks.is_some() {
+                let _ = write!(out, "\tleft_flank\tright_flank");
+            }
+            if distances.is_some() {
+                let _ = write!(out, "\tflank_distance");
+            }
+            let _ = writeln!(out);
+        }
+        let mut recomb_scores = Vec::new();
+        // the total coverage of each scored pair, for a weighted potential
+        let mut weights = Vec::new();
+
+        let tests = pvalues.into_iter().zip(qvalues);
+        for (((p1, cov1, p2, cov2), (focal, pos)), (pvalue, qvalue)) in
+            revcomps.iter().zip(&focals).zip(tests)
+        {
+            let total = *cov1 as f64 + *cov2 as f64;
+            if total == 0.0 {
+                continue; // avoid division by zero
+            }
+            let rel1 = *cov1 as f64 / total;
+            let rel2 = *cov2 as f64 / total;
+            let score = 2.0 * rel1.min(rel2);
+            recomb_scores.push(score);
+            weights.push(if coverage_weighted { total } else { 1.0 });
+            *report.pair_counts.entry(focal.clone()).or_default() += 1;
+
+            let path_1_direction = direction.then(|| path_direction(p1));
+            let breakpoint = breakpoints.map(|support| {
+                let key = |path: &String| (focal.clone(), path.clone());
+                mean_breakpoint([p1, p2].into_iter().filter_map(|p| support.get(&key(p))))
+            });
+            // only the display changes, the focal position follows path_1 if it flips
+            let (p1, pos, p2) = if canonical {
+                let (p1, flipped) = canonical_path(p1);
+                let pos = if flipped {
+                    pos.map(|pos| 2 - pos)
+                } else {
+                    *pos
+                };
+                (p1, pos, canonical_path(p2).0)
+            } else {
+                (p1.clone(), *pos, p2.clone())
+            };
+            let path = string_to_path(p1.clone()).expect("pairs are valid paths");
+            let flank_seqs = flanks.map(|flanks| flanks.flanks(&path, pos.unwrap_or(1)));
+            let distance = distances.map(|distances| distances.distance(&path));
+            if print_pairs {
+                let _ = write!(
+                    out,
+                    "{}\t{}\t{}\t{}\t{:.*}",
+                    p1, cov1, p2, cov2, precision, score
+                );
+                if relative {
+                    let _ = write!(out, "\t{:.*}\t{:.*}", precision, rel1, precision, rel2);
+                }
+                if let Some(pvalue) = pvalue {
+                    let _ = write!(out, "\t{:.*e}", precision, pvalue);
+                }
+                if let Some(qvalue) = qvalue {
+                    let _ = write!(out, "\t{:.*e}", precision, qvalue);
+                }
+                if let Some(direction) = path_1_direction {
+                    let _ = write!(out, "\t{}", direction);
+                }
+                if let Some(breakpoint) = breakpoint {
+                    let breakpoint = breakpoint.map_or("NA".to_string(), |b| b.to_string());
+                    let _ = write!(out, "\t{}", breakpoint);
+                }
+                if multi_focal {
+                    let pos = pos.map_or("NA".to_string(), |pos| pos.to_string());
+                    let _ = write!(out, "\t{}\t{}", focal, pos);
+                }
+                if let Some((left, right)) = &flank_seqs {
+                    let _ = write!(out, "\t{}\t{}", left, right);
+                }
+                if let Some(distance) = distance {
+                    let distance = distance.map_or("none".to_string(), |d| d.to_string());
+                    let _ = write!(out, "\t{}", distance);
+                }
+                let _ = writeln!(out);
+            }
+            report.pairs.push(PairRecord {
+                path_1: p1,
+                cov_1: *cov1,
+                path_2: p2,
+                cov_2: *cov2,
+                recomb_score: score,
+                rel_1: relative.then_some(rel1),
+                rel_2: relative.then_some(rel2),
+                pvalue,
+                qvalue,
+                direction: path_1_direction,
+                breakpoint,
+                focal_node: multi_focal.then(|| focal.clone()),
+                focal_pos: if multi_focal { pos } else { None },
+                left_flank: flank_seqs.as_ref().map(|(left, _)| left.clone()),
+                right_flank: flank_seqs.map(|(_, right)| right),
+                flank_distance: distance,
+            });
+        }
+
+        let recomb_potential: f64 = if !recomb_scores.is_empty() {
+            let weighted: f64 = recomb_scores.iter().zip(&weights).map(|(s, w)| s * w).sum();
+            weighted / weights.iter().sum::<f64>()
+        } else {
+            0.0
+        };
+
+        if print_pairs {
+            let _ = writeln!(out);
+        }
+        let _ = writeln!(
+            out,
+            "Recombination potential: {:.*}",
+            precision, recomb_potential
+        );
+        let centred: Vec<_> = revcomps
+            .iter()
+            .zip(&focals)
+            .filter(|(_, (_, pos))| *pos == Some(1))
+            .map(|(pair, _)| pair.clone())
+            .collect();
+        let rci = compute_rci(&centred, rci);
+        let _ = writeln!(out, "RCI: {:.*}", precision, rci);
+        report.recombination_potential = recomb_potential;
+        report.rci = rci;
+        report.recomb_scores = recomb_scores;
+    }
+
+    report
+}
+
+/// Whether the repeat of `paths` is kept by `--only-inverted`, i.e. is in
+/// `inverted` if given.
+fn keeps_repeat(paths: &Paths, inverted: Option<&HashSet<String>>) -> bool {
+    inverted.is_none_or(|inverted| {
+        paths
+            .paths
+            .first()
+            .is_some_and(|((repeat, _), _)| inverted.contains(repeat))
+    })
+}
+
+/// The genome-wide recombination rate: the reads of the pairs scoring at least
+/// `threshold`, i.e. balanced enough to count as recombining, as a fraction of all
+/// the reads through the focal repeats in `groups`. With `inverted`
+/// (`--only-inverted`), only its repeats count towards the total, as only their
+/// pairs are in `pairs`. 0 without reads.
+fn recombination_rate(
+    pairs: &[PairRecord],
+    groups: &[Paths],
+    inverted: Option<&HashSet<String>>,
+    threshold: f64,
+) -> f64 {
+    let total: u64 = groups
+        .iter()
+        .filter(|group| keeps_repeat(group, inverted))
+        .flat_map(|group| &group.paths)
+        .map(|(_, count)| count)
+        .sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let balanced: u64 = pairs
+        .iter()
+        .filter(|pair| pair.recomb_score >= threshold)
+        .map(|pair| pair.cov_1 + pair.cov_2)
+        .sum();
+    balanced as f64 / total as f64
+}
+
+/// Remove the repeats whose paths total fewer than `min` reads
+/// (`--min-repeat-cov`), returning how many were removed.
+fn drop_weak_repeats(groups: &mut Vec<Paths>, min: u64) -> usize {
+    let before = groups.len();
+    groups.retain(|group| group.paths.iter().map(|(_, c)| c).sum::<u64>() >= min);
+    before - groups.len()
+}
+
+/// Remove the paths read less than `fraction` times as often as the top path of
+/// their repeat (`--min-path-frac`), returning how many were removed. The cutoff
+/// follows each repeat's depth, so a shallow repeat keeps paths a deep one drops.
+fn drop_noise_paths(groups: &mut [Paths], fraction: f64) -> usize {
+    let mut excluded = 0;
+    for group in groups {
+        let top = group.paths.iter().map(|(_, c)| *c).max().unwrap_or(0);
+        let cutoff = fraction * top as f64;
+        let before = group.paths.len();
+        group.paths.retain(|(_, count)| *count as f64 >= cutoff);
+        excluded += before - group.paths.len();
+    }
+    excluded
+}
+
+/// Remove the repeats only ever traversed in one orientation by their reads
+/// (`--require-both-orientations-observed`), returning how many were removed. The
+/// orientation is that of the focal segment in each path, its centre unless with
+/// `--multi-focal`.
+fn drop_single_orientation_repeats(groups: &mut Vec<Paths>) -> usize {
+    let before = groups.len();
+    groups.retain(|group| {
+        let orientations: HashSet<Orientation> = group
+            .paths
+            .iter()
+            .filter(|(_, count)| *count > 0)
+            .flat_map(|((focal, path), _)| path.0.iter().filter(move |s| &s.segid == focal))
+            .map(|segment| segment.orientation)
+            .collect();
+        orientations.len() == 2
+    });
+    before - groups.len()
+}
+
+/// The paths of `repeat` whose flanks are exactly `flank_a` and `flank_b`, in
+/// either order and orientation, for `--query`.
+fn query_paths(groups: &[Paths], flank_a: &str, repeat: &str, flank_b: &str) -> Paths {
+    let paths = groups
+        .iter()
+        .flat_map(|group| &group.paths)
+        .filter(|((focal, p), _)| {
+            let (from, to) = (p.from().segid, p.to().segid);
+            focal == repeat
+                && p.repeat().segid == repeat
+                && ((from == flank_a && to == flank_b) || (from == flank_b && to == flank_a))
+        })
+        .cloned()
+        .collect();
+    Paths::new(paths)
+}
+
+/// A reverse-complement pair `(path_1, cov_1, path_2, cov_2)`, with the focal
+/// node and its position in `path_1`.
+type PathPair = ((String, u64, String, u64), (String, Option<usize>));
+
+/// The reverse-complement pairs among one repeat's paths.
+fn pair_paths(paths: &Paths, families: Option<&SegmentFamilies>) -> Vec<PathPair> {
+    let mut pairs = Vec::new();
+    let mut node_checker = Vec::new();
+
+    // pair in order of decreasing coverage, then path, whatever the input order;
+    // if a path has several unpaired reverse partners, the first in that order wins
+    let mut path_vec: Vec<_> = paths.paths.iter().collect();
+    path_vec.sort_by_cached_key(|((_, p), c)| (std::cmp::Reverse(*c), p.to_string()));
+
+    for (i, ((focal, p), c)) in path_vec.iter().enumerate() {
+        if node_checker.contains(&p.to_string()) {
+            continue;
+        }
+        let partner = path_vec.iter().enumerate().find(|(j, ((_, p2), _))| {
+            *j != i && p.is_reverse(p2, families) && !node_checker.contains(&p2.to_string())
+        });
+        // if we hit a reverse path, combine them
+        // and push to the node checker
+        if let Some((_, ((_, p2), c2))) = partner {
+            pairs.push((
+                (p.to_string(), *c, p2.to_string(), *c2),
+                (focal.clone(), p.position_of(focal)),
+            ));
+            node_checker.push(p.to_string());
+            node_checker.push(p2.to_string());
+        }
+    }
+    pairs
+}
+
+#[derive(Debug, Clone)]
+struct Paths {
+    // node ID, path, count of the path
+    paths: Vec<PathEntry>,
+}
+
+impl Paths {
+    fn new(inner: Vec<PathEntry>) -> Self {
+        Self { paths: inner }
+    }
+    fn from_vec(vec: Vec<(&(String, String), &u64)>) -> Self {
+        let mut paths = Vec::new();
+        for ((id, path), count) in vec {
+            let path = string_to_path(path.to_string()).unwrap();
+            paths.push(((id.clone(), path), *count));
+        }
+
+        Self::new(paths)
+    }
+
+    /// Only the paths whose reverse complement was also observed, i.e. those that
+    /// can appear in the pair table.
+    fn paired(&self, families: Option<&SegmentFamilies>) -> Paths {
+        let paired = self
+            .paths
+            .iter()
+            .filter(|((_, p), _)| {
+                self.paths
+                    .iter()
+                    .any(|((_, p2), _)| p.is_reverse(p2, families))
+            })
+            .cloned()
+            .collect();
+        Paths::new(paired)
+    }
+
+    /// The path with the most reads, ties going to the first path in string order,
+    /// with its fraction of all the reads. There must be at least one path.
+    fn top_path(&self) -> (String, f64) {
+        let total: u64 = self.paths.iter().map(|(_, c)| c).sum();
+        let (path, count) = self
+            .paths
+            .iter()
+            .map(|((_, p), c)| (p.to_string(), *c))
+            .min_by(|(p1, c1), (p2, c2)| c2.cmp(c1).then_with(|| p1.cmp(p2)))
+            .expect("a repeat has paths");
+        let frac = if total > 0 {
+            count as f64 / total as f64
+        } else {
+            0.0
+        };
+        (path, frac)
+    }
+
+    /// Read counts of the paths traversing the focal node forward, and reverse.
+    fn orientation_coverage(&self) -> (u64, u64) {
+        let mut fwd = 0;
+        let mut rev = 0;
+        for ((focal, path), count) in &self.paths {
+            let Some(pos) = path.position_of(focal) else {
+                continue;
+            };
+            match path.0[pos].orientation {
+                Orientation::Forward => fwd += count,
+                Orientation::Backward => rev += count,
+            }
+        }
+        (fwd, rev)
+    }
+
+    /// Splits the paths of one repeat by the position of the focal node in them, as
+    /// `(position, paths)` sorted by position. A path and its reverse complement
+    /// have the focal node at positions `p` and `2 - p`, so the smaller one is used
+    /// to keep pairs together: 1 for paths centred on the focal node, 0 for paths
+    /// starting or ending at it.
+    fn split_by_focal_pos(&self) -> Vec<(usize, Paths)> {
+        let mut split: BTreeMap<usize, Vec<PathEntry>> = BTreeMap::new();
+        for entry in &self.paths {
+            let ((focal, path), _) = entry;
+            let pos = path.position_of(focal).map_or(1, |pos| pos.min(2 - pos));
+            split.entry(pos).or_default().push(entry.clone());
+        }
+        split
+            .into_iter()
+            .map(|(pos, entries)| (pos, Paths::new(entries)))
+            .collect()
+    }
+
+    /// Groups path+coverage entries by focal repeat node ID.
+    ///
+    /// Groups are sorted by repeat ID, and paths within a group by decreasing
+    /// coverage and then path, so that reports are stable across runs.
+    fn split_into_repeats(&self) -> Vec<Paths> {
+        let mut grouped: HashMap<String, Vec<PathEntry>> = HashMap::new();
+
+        for entry in &self.paths {
+            let repeat_id = entry.0 .0.clone();
+            grouped.entry(repeat_id).or_default().push(entry.clone());
+        }
+
+        let mut grouped: Vec<_> = grouped.into_iter().collect();
+        grouped.sort_by(|a, b| a.0.cmp(&b.0));
+
+        grouped
+            .into_iter()
+            .map(|(_, mut entries)| {
+                entries.sort_by_cached_key(|((_, p), c)| (std::cmp::Reverse(*c), p.to_string()));
+                Paths::new(entries)
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Segment {
+    orientation: Orientation,
+    segid: String,
+}
+
+#[derive(Debug, Clone)]
+struct Path([Segment; 3]);
+
+impl Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut out = String::new();
+        for segment in self.0.iter() {
+            out.push(orientation_symbol(segment.orientation));
+            out.push_str(&segment.segid);
+        }
+        write!(f, "{}", out)
+    }
+}
+
+impl Path {
+    fn from(&self) -> Segment {
+        self.0[0].clone()
+    }
+
+    fn repeat(&self) -> Segment {
+        self.0[1].clone()
+    }
+
+    fn to(&self) -> Segment {
+        self.0[2].clone()
+    }
+
+    /// The 0-based position of `segid` in the path, if present.
+    fn position_of(&self, segid: &str) -> Option<usize> {
+        self.0.iter().position(|s| s.segid == segid)
+    }
+    // u25	132	<u28<u25>u27 (reverse)
+    // u25	126	<u27>u25>u28 (forward)
+    // these two paths are reverses of one another
+    //
+    // with `families`, flanks in the same family count as the same segment
+    fn is_reverse(&self, other: &Path, families: Option<&SegmentFamilies>) -> bool {
+        let same_flank = |a: &str, b: &str| {
+            a == b
+                || families.is_some_and(|f| f.get(a).is_some_and(|family| f.get(b) == Some(family)))
+        };
+        // the first and third orientations should differ, but should both have
+        // the same segid
+        let first_third_o = self.from().orientation != other.to().orientation;
+        let first_third_p = same_flank(&self.from().segid, &other.to().segid);
+
+        // but also need to compare the third and the first
+        let third_first_o = self.to().orientation != other.from().orientation;
+        let third_first_p = same_flank(&self.to().segid, &other.from().segid);
+
+        // the repeat should be the same, but the orientation should be different
+        let repeat_same = self.repeat().segid == other.repeat().segid;
+        let repeat_diff = self.repeat().orientation != other.repeat().orientation;
+
+        // return the bool
+        first_third_o
+            && first_third_p
+            && third_first_o
+            && third_first_p
+            && repeat_same
+            && repeat_diff
+    }
+}
+
+impl Default for Segment {
+    fn default() -> Self {
+        Segment {
+            orientation: Orientation::Forward,
+            segid: String::new(),
+        }
+    }
+}
+
+/// A path in its reference orientation, and whether it had to be reverse
+/// complemented. The strand whose first segment is forward is used, e.g. `<b<r<a`
+/// becomes `>a>r>b`. If both strands start forward (or both backward), as for
+/// `>a>r<b` and `>b<r<a`, the lexicographically smaller one is used.
+fn canonical_path(path: &str) -> (String, bool) {
+    let Ok(p) = string_to_path(path.to_string()) else {
+        return (path.to_string(), false);
+    };
+    let flipped: String =
+        p.0.iter()
+            .rev()
+            .map(|s| {
+                let flipped = match s.orientation {
+                    Orientation::Forward => Orientation::Backward,
+                    Orientation::Backward => Orientation::Forward,
+                };
+                format!("{}{}", orientation_symbol(flipped), s.segid)
+            })
+            .collect();
+    let flip = match (p.from().orientation, p.to().orientation) {
+        (Orientation::Backward, Orientation::Backward) => true,
+        (Orientation::Forward, Orientation::Forward) => false,
+        _ => flipped.as_str() < path,
+    };
+    if flip {
+        (flipped, true)
+    } else {
+        (path.to_string(), false)
+    }
+}
+
+/// `forward` if `path` is written in its reference orientation (see
+/// [`canonical_path`]), and `reverse` if it is its reverse complement.
+fn path_direction(path: &str) -> &'static str {
+    if canonical_path(path).1 {
+        "reverse"
+    } else {
+        "forward"
+    }
+}
+
+/// The orientation of a path step or link end written as `>`/`+` (forward) or
+/// `<`/`-` (backward). Every notation for orientations is read through this, so
+/// the arrow and signed forms always agree.
+pub(crate) fn parse_orientation(symbol: u8) -> Option<Orientation> {
+    match symbol {
+        b'>' | b'+' => Some(Orientation::Forward),
+        b'<' | b'-' => Some(Orientation::Backward),
+        _ => None,
+    }
+}
+
+/// The arrow (`>` or `<`) paths are written with, the inverse of
+/// [`parse_orientation`].
+fn orientation_symbol(orientation: Orientation) -> char {
+    match orientation {
+        Orientation::Forward => '>',
+        Orientation::Backward => '<',
+    }
+}
+
+/// How the paths of a counts table are written (`--path-notation`).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum PathNotation {
+    /// `<u28<u25>u27`, an arrow before each segment.
+    #[default]
+    Arrow,
+    /// `u28-u25-u27+`, a sign after each segment.
+    PlusMinus,
+}
+
+/// Parse a path written in `notation` into a [`Path`], the same for either.
+fn parse_path(s: &str, notation: PathNotation) -> Result<Path> {
+    match notation {
+        PathNotation::Arrow => string_to_path(s.to_string()),
+        PathNotation::PlusMinus => string_to_path(signs_to_arrows(s)?),
+    }
+}
+
+/// Rewrite a path in `+`/`-` suffix notation into arrow notation, e.g.
+/// `u28-u25-u27+` into `<u28<u25>u27`.
+fn signs_to_arrows(s: &str) -> Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut start = 0;
+    for (i, sign) in s.match_indices(['+', '-']) {
+        let name = &s[start..i];
+        if name.is_empty() {
+            anyhow::bail!("segment without a name in path: {}", s);
+        }
+        out.push(if sign == "+" { '>' } else { '<' });
+        out.push_str(name);
+        start = i + 1;
+    }
+    if start != s.len() {
+        anyhow::bail!("segment without an orientation in path: {}", s);
+    }
+    Ok(out)
+}
+
+/// Parses a string representation of a 3-segment path (e.g. ">u28<u25>u26") into a Path object.
+/// Validates directionality (< or >) and ensures exactly 3 segments. Returns an error if malformed.
+fn string_to_path(s: String) -> Result<Path> {
+    let mut out: [Segment; 3] = core::array::from_fn(|_| Segment::default());
+
+    // split the string on < or >
+    let split: Vec<&str> = s.split(['<', '>']).collect();
+    // remove empty strings in vec
+    let split: Vec<&str> = split.into_iter().filter(|&x| !x.is_empty()).collect();
+    // also collect a vec of the delimiters
+    let delimiters: Vec<&str> = s.split(|c| c != '<' && c != '>').collect();
+    // remove empty strings in vec
+    let delimiters: Vec<&str> = delimiters.into_iter().filter(|&x| !x.is_empty()).collect();
+    if split.len() != 3 || delimiters.len() != 3 || !s.starts_with(['<', '>']) {
+        anyhow::bail!("not a 3-segment path: {}", s);
+    }
+
+    let mut inner_segment = Segment::default();
+    for (i, (path, orientation)) in split.iter().zip(delimiters).enumerate() {
+        inner_segment.orientation = match orientation.as_bytes() {
+            [symbol] => parse_orientation(*symbol).context("no orientation")?,
+            _ => return Err(anyhow::anyhow!("no orientation")), // default
+        };
+        inner_segment.segid = path.to_string();
+        out[i] = inner_segment;
+
+        // reset the inner segment
+        inner_segment = Segment::default();
+    }
+
+    Ok(Path(out))
+}
+
+// tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRINT_PAIRS: PairOptions = PairOptions {
+        multi_focal: false,
+        print_pairs: true,
+        canonical: false,
+        relative: false,
+        pvalue: false,
+        fdr: false,
+        direction: false,
+        coverage_weighted: false,
+        breakpoints: None,
+        families: None,
+        flanks: None,
+        distances: None,
+        inverted: None,
+    };
+
+    #[test]
+    fn test_path_parse() {
+        let s = "<u28<u25>u27";
+        let path = string_to_path(s.to_string()).unwrap();
+        assert_eq!(path.from().segid, "u28");
+        assert_eq!(path.from().orientation, Orientation::Backward);
+        assert_eq!(path.repeat().segid, "u25");
+        assert_eq!(path.repeat().orientation, Orientation::Backward);
+        assert_eq!(path.to().segid, "u27");
+        assert_eq!(path.to().orientation, Orientation::Forward);
+    }
+
+    #[test]
+    fn test_is_reverse() {
+        let s = "<u28<u25>u27";
+        let path = string_to_path(s.to_string()).unwrap();
+        let s2 = "<u27>u25>u28";
+        let path2 = string_to_path(s2.to_string()).unwrap();
+        assert!(path.is_reverse(&path2, None));
+    }
+
+    #[test]
+    fn test_is_reverse_2() {
+        let s = ">u27>u29<u30";
+        let s2 = "<u30<u29<u27";
+
+        let path = string_to_path(s.to_string()).unwrap();
+        let path2 = string_to_path(s2.to_string()).unwrap();
+
+        assert!(!path.is_reverse(&path2, None));
+    }
+
+    // test split into repeats
+    // u25	132	<u28<u25>u27 (reverse)
+    // u25	126	<u27>u25<u28 (forward)
+    // u25	126	<u27>u25>u28 (forward)
+    // u25	120	>u28<u25>u26 (reverse)
+    // u25	119	>u28<u25>u27 (reverse)
+    // u25	115	<u26>u25<u28 (forward)
+    // u25	107	<u28<u25>u26 (reverse)
+    // u25	101	<u26>u25>u28 (forward)
+    #[test]
+    fn test_split_repeats() {
+        let paths = Paths::from_vec(vec![
+            (&("u25".into(), "<u28<u25>u27".into()), &132),
+            (&("u25".into(), "<u27>u25<u28".into()), &126),
+            (&("u25".into(), "<u27>u25>u28".into()), &126),
+            (&("u25".into(), ">u28<u25>u26".into()), &120),
+            (&("u25".into(), ">u28<u25>u27".into()), &119),
+            (&("u25".into(), "<u26>u25<u28".into()), &115),
+            (&("u25".into(), "<u28<u25>u26".into()), &107),
+            (&("u25".into(), "<u26>u25>u28".into()), &101),
+        ]);
+
+        let split = paths.split_into_repeats();
+
+        assert!(split.len() == 1);
+    }
+
+    #[test]
+    fn test_split_repeats_multiple() {
+        // +u30-u29-u26 -
+        // -u30-u29-u26 -
+        // +u26+u29-u30 -
+        // -u30-u29-u27 -
+        // +u27+u29+u30 -
+        // +u30-u29-u27 -
+        // +u26+u29+u30 -
+        let paths = Paths::from_vec(vec![
+            (&("u25".into(), "<u28<u25>u27".into()), &132),
+            (&("u25".into(), "<u27>u25<u28".into()), &126),
+            (&("u25".into(), "<u27>u25>u28".into()), &126),
+            (&("u25".into(), ">u28<u25>u26".into()), &120),
+            (&("u25".into(), ">u28<u25>u27".into()), &119),
+            (&("u25".into(), "<u26>u25<u28".into()), &115),
+            (&("u25".into(), "<u28<u25>u26".into()), &107),
+            (&("u25".into(), "<u26>u25>u28".into()), &101),
+            (&("u29".into(), ">u27>u29<u30".into()), &87),
+            (&("u29".into(), ">u30<u29<u26".into()), &67),
+            (&("u29".into(), "<u30<u29<u26".into()), &66),
+            (&("u29".into(), ">u26>u29<u30".into()), &65),
+            (&("u29".into(), "<u30<u29<u27".into()), &63),
+            (&("u29".into(), ">u27>u29>u30".into()), &61),
+            (&("u29".into(), ">u30<u29<u27".into()), &57),
+            (&("u29".into(), ">u26>u29>u30".into()), &55),
+        ]);
+
+        let split = paths.split_into_repeats();
+
+        assert!(split.len() == 2);
+        assert_eq!(split[0].paths.len(), 8);
+        assert_eq!(split[1].paths.len(), 8);
+    }
+
+    #[test]
+    fn test_extract_middle_segment_valid() {
+        let p = "<u27>u25<u28";
+        assert_eq!(extract_middle_segment(p), Some("u25".to_string()));
+    }
+
+    #[test]
+    fn test_extract_middle_segment_invalid() {
+        let p = ">u27<u25"; // Only two segments
+        assert_eq!(extract_middle_segment(p), None);
+    }
+
+    #[test]
+    fn test_extract_repeat_info_valid_pair() {
+        let (id, count) = extract_repeat_info("<u27>u25<u28", ">u26<u25>u27");
+        assert_eq!(id, Some("u25".to_string()));
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_extract_repeat_info_mismatch() {
+        let (id, count) = extract_repeat_info("<u27>u25<u28", ">u26<u24>u27");
+        assert_eq!(id, None);
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_compute_rci_balanced() {
+        let revcomps = vec![
+            // repeat node: u66
+            (
+                "<u67<u66>u65".to_string(),
+                100,
+                "<u65>u66>u67".to_string(),
+                100,
+            ),
+            // repeat node: u69
+            (
+                ">u65<u69<u68".to_string(),
+                80,
+                ">u68>u69<u65".to_string(),
+                80,
+            ),
+        ];
+
+        let rci = compute_rci(&revcomps, &RciOptions::default());
+        eprintln!("RCI: {rci}");
+        assert!(rci > 0.0);
+        assert!((rci - 1.0).abs() < 0.1);
+
+        let revcomps = vec![
+            // Repeat node u66 (2 pairs → 4 paths total)
+            (
+                "<u67<u66>u65".to_string(),
+                100,
+                "<u65>u66>u67".to_string(),
+                100,
+            ),
+            (
+                "<u68<u66>u64".to_string(),
+                90,
+                "<u64>u66>u68".to_string(),
+                90,
+            ),
+            // Repeat node u69 (2 pairs → 4 paths total)
+            (
+                ">u65<u69<u68".to_string(),
+                80,
+                ">u68>u69<u65".to_string(),
+                80,
+            ),
+            (
+                ">u64<u69<u67".to_string(),
+                70,
+                ">u67>u69<u64".to_string(),
+                70,
+            ),
+        ];
+
+        let rci = compute_rci(&revcomps, &RciOptions::default());
+        eprintln!("RCI: {rci}");
+        assert!(rci > 0.0);
+        assert!((rci - 2.0).abs() < 0.1); // 2 repeat nodes × log2(2) × score ~ 1.0
+    }
+
+    #[test]
+    fn test_compute_rci_unbalanced() {
+        let revcomps = vec![
+            (
+                "<u27>u25<u28".to_string(),
+                190,
+                ">u26<u25>u27".to_string(),
+                10,
+            ), // very unbalanced
+        ];
+
+        let rci = compute_rci(&revcomps, &RciOptions::default());
+        assert!(rci < 0.5);
+    }
+
+    #[test]
+    fn test_compute_rci_possible_denominator() {
+        // 4 repeat flanks on each side, but only one pair of paths observed
+        let gfa = gfa::parser::GFAParser::new()
+            .parse_lines(
+                [
+                    "S\tR\tACGT",
+                    "L\ta1\t+\tR\t+\t0M",
+                    "L\ta2\t+\tR\t+\t0M",
+                    "L\ta3\t+\tR\t+\t0M",
+                    "L\ta4\t+\tR\t+\t0M",
+                    "L\tR\t+\tb1\t+\t0M",
+                    "L\tR\t+\tb2\t+\t0M",
+                    "L\tR\t+\tb3\t+\t0M",
+                    "L\tR\t+\tb4\t+\t0M",
+                ]
+                .iter()
+                .map(|l| l.as_bytes()),
+            )
+            .unwrap();
+        let possible = crate::gfa::possible_paths(&gfa, &["R".to_string()]);
+        assert_eq!(possible["R"], 32);
+
+        let revcomps = vec![(">a1>R>b1".to_string(), 50, "<b1<R<a1".to_string(), 50)];
+        let observed = compute_rci(&revcomps, &RciOptions::default());
+        let possible = compute_rci(
+            &revcomps,
+            &RciOptions {
+                possible: Some(&possible),
+                ..Default::default()
+            },
+        );
+        assert!((observed - 1.0).abs() < 1e-9); // log2(2)
+        assert!((possible - 5.0).abs() < 1e-9); // log2(32)
+    }
+
+    #[test]
+    fn test_compute_rci_coverage_weighted() {
+        // a balanced repeat with 4 paths, and an unbalanced one with 2 and 3x the reads
+        let revcomps = |scale: u64| {
+            vec![
+                (
+                    ">a>R>b".to_string(),
+                    10 * scale,
+                    "<b<R<a".to_string(),
+                    10 * scale,
+                ),
+                (
+                    ">c>R>d".to_string(),
+                    10 * scale,
+                    "<d<R<c".to_string(),
+                    10 * scale,
+                ),
+                (
+                    ">e>Q>f".to_string(),
+                    45 * scale,
+                    "<f<Q<e".to_string(),
+                    15 * scale,
+                ),
+            ]
+        };
+        // R: S = 1, log2(4) = 2; Q: S = 0.5, log2(2) = 1
+        let equal = compute_rci(&revcomps(1), &RciOptions::default());
+        assert!((equal - 1.25).abs() < 1e-9);
+        let weighted_options = RciOptions {
+            coverage_weighted: true,
+            ..Default::default()
+        };
+        let weighted = compute_rci(&revcomps(1), &weighted_options);
+        assert!((weighted - (40.0 * 2.0 + 60.0 * 0.5) / 100.0).abs() < 1e-9);
+
+        // the same at ten times the depth
+        let deeper = compute_rci(&revcomps(10), &weighted_options);
+        assert!((deeper - weighted).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_compute_rci_empty() {
+        let revcomps = vec![];
+        let rci = compute_rci(&revcomps, &RciOptions::default());
+        assert_eq!(rci, 0.0);
+    }
+
+    #[test]
+    fn test_compute_path_entropy_single_node() {
+        let paths = Paths::new(vec![
+            (
+                ("u66".into(), string_to_path("<u67<u66>u65".into()).unwrap()),
+                100,
+            ),
+            (
+                ("u66".into(), string_to_path("<u65>u66>u67".into()).unwrap()),
+                100,
+            ),
+            (
+                ("u66".into(), string_to_path("<u68<u66>u64".into()).unwrap()),
+                50,
+            ),
+        ]);
+
+        let (mean_entropy, _total_entropy, details) =
+            output_entropy_lines(&mut io::sink(), &[paths], &EntropyOptions::default(), 3).unwrap();
+
+        assert!((mean_entropy > 1.0) && (mean_entropy < 2.0));
+        assert_eq!(details.len(), 1);
+        assert_eq!(details[0].repeat_node, "u66");
+        assert_eq!(details[0].path_count, 3);
+        // entropy should be positive
+        assert!(details[0].entropy.unwrap() > 1.0);
+        // u66 is reversed in the 1st and 3rd paths
+        assert_eq!((details[0].fwd_cov, details[0].rev_cov), (100, 150));
+    }
+
+    #[test]
+    fn test_score_histogram() {
+        let scores = [0.0, 0.1, 0.249, 0.25, 0.5, 0.74, 0.9, 0.95, 1.0];
+        let hist = score_histogram(&scores, 4);
+        assert_eq!(
+            hist.iter().map(|b| b.pairs).collect::<Vec<_>>(),
+            vec![3, 1, 2, 3]
+        );
+        assert_eq!((hist[1].start, hist[1].end), (0.25, 0.5));
+
+        let hist = score_histogram(&scores, 1);
+        assert_eq!(hist[0].pairs, scores.len());
+        assert!(score_histogram(&[], 3).iter().all(|b| b.pairs == 0));
+
+        let mut out = Vec::new();
+        output_histogram_lines(&mut out, &score_histogram(&[0.2, 1.0], 2), 1);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\nscore_start\tscore_end\tpairs\n0.0\t0.5\t1\n0.5\t1.0\t1\n"
+        );
+    }
+
+    #[test]
+    fn test_path_notation() {
+        let arrow = parse_path("<u28<u25>u27", PathNotation::Arrow).unwrap();
+        let signed = parse_path("u28-u25-u27+", PathNotation::PlusMinus).unwrap();
+        assert_eq!(signed.to_string(), arrow.to_string());
+        for (a, b) in arrow.0.iter().zip(&signed.0) {
+            assert_eq!((&a.segid, a.orientation), (&b.segid, b.orientation));
+        }
+
+        assert!(parse_path("u28-u25-u27", PathNotation::PlusMinus).is_err());
+        assert!(parse_path("u28--u27+", PathNotation::PlusMinus).is_err());
+        assert!(parse_path("u28-u25-u27+", PathNotation::Arrow).is_err());
+
+        // counts tables are keyed in arrow notation either way
+        let table = "repeat_node\tpath\tcount\nu25\tu28-u25-u27+\t4\n";
+        let counts = read_counts(table.as_bytes(), PathNotation::PlusMinus).unwrap();
+        assert_eq!(counts[&("u25".to_string(), "<u28<u25>u27".to_string())], 4);
+    }
+
+    #[test]
+    fn test_canonical_path() {
+        assert_eq!(canonical_path("<b<r<a"), (">a>r>b".to_string(), true));
+        assert_eq!(canonical_path(">a>r>b"), (">a>r>b".to_string(), false));
+        assert_eq!(
+            canonical_path("<u27>u25<u28"),
+            (">u28<u25>u27".to_string(), true)
+        );
+
+        assert_eq!(canonical_path(">b<r<a"), (">a>r<b".to_string(), true));
+        assert_eq!(canonical_path(">a>r<b"), (">a>r<b".to_string(), false));
+
+        // both strands of a pair display identically
+        let entry = |p: &str| (("u2".to_string(), string_to_path(p.into()).unwrap()), 5);
+        for (fwd, rev) in [(">u1>u2>u3", "<u3<u2<u1"), (">u1>u2<u3", ">u3<u2<u1")] {
+            let group = Paths::new(vec![entry(rev), entry(fwd)]);
+            let report = output_repeat_lines(
+                &mut io::sink(),
+                &[group],
+                &PairOptions {
+                    multi_focal: true,
+                    print_pairs: true,
+                    canonical: true,
+                    ..Default::default()
+                },
+                &RciOptions::default(),
+                3,
+            );
+            let pair = &report.pairs[0];
+            assert_eq!(pair.path_1, fwd);
+            assert_eq!(pair.path_2, fwd);
+            assert_eq!(pair.focal_pos, Some(1));
+        }
+    }
+
+    #[test]
+    fn test_segment_families() {
+        let families =
+            parse_segment_families("# near-identical flanks\nu3 u3b\n\nu7\tu7b u7c\n".as_bytes())
+                .unwrap();
+        assert_eq!(families["u3b"], "u3");
+        assert_eq!(families["u7c"], "u7");
+        assert!(parse_segment_families("u3 u3b\nu4 u3b\n".as_bytes()).is_err());
+
+        // the reverse of >u1>u2>u3 ends in u3b, a member of u3's family
+        let entry = |p: &str| (("u2".to_string(), string_to_path(p.into()).unwrap()), 5);
+        let group = [Paths::new(vec![entry(">u1>u2>u3"), entry("<u3b<u2<u1")])];
+        let report = output_repeat_lines(
+            &mut io::sink(),
+            &group,
+            &PRINT_PAIRS,
+            &RciOptions::default(),
+            3,
+        );
+        assert!(report.pairs.is_empty());
+        assert!(group[0].paired(None).paths.is_empty());
+
+        let options = PairOptions {
+            families: Some(&families),
+            ..PRINT_PAIRS
+        };
+        let report =
+            output_repeat_lines(&mut io::sink(), &group, &options, &RciOptions::default(), 3);
+        assert_eq!(report.pairs.len(), 1);
+        assert_eq!(
+            (
+                report.pairs[0].path_1.as_str(),
+                report.pairs[0].path_2.as_str()
+            ),
+            ("<u3b<u2<u1", ">u1>u2>u3")
+        );
+        assert_eq!(group[0].paired(Some(&families)).paths.len(), 2);
+
+        // the repeat itself must still match exactly
+        let other = string_to_path(">u3<u9<u1".into()).unwrap();
+        let path = string_to_path(">u1>u2>u3".into()).unwrap();
+        let families =
+            SegmentFamilies::from([("u2".into(), "u2".into()), ("u9".into(), "u2".into())]);
+        assert!(!path.is_reverse(&other, Some(&families)));
+    }
+
+    #[test]
+    fn test_pairing_ties_are_deterministic() {
+        let entry = |p: &str, c| (("u2".to_string(), string_to_path(p.into()).unwrap()), c);
+        // u3 and u6 are one flank family, so <u1<u2<u3 has two equally covered
+        // reverse partners, >u3>u2>u1 and >u6>u2>u1
+        let families: SegmentFamilies = [("u3", "F"), ("u6", "F")]
+            .into_iter()
+            .map(|(id, family)| (id.to_string(), family.to_string()))
+            .collect();
+        let entries = vec![
+            entry("<u1<u2<u3", 10),
+            entry(">u6>u2>u1", 10),
+            entry(">u3>u2>u1", 10),
+            entry(">u4>u2>u5", 10),
+            entry("<u5<u2<u4", 10),
+        ];
+        let options = PairOptions {
+            families: Some(&families),
+            ..PRINT_PAIRS
+        };
+
+        let pairs = |entries: Vec<PathEntry>| {
+            let report = output_repeat_lines(
+                &mut io::sink(),
+                &[Paths::new(entries)],
+                &options,
+                &RciOptions::default(),
+                3,
+            );
+            report
+                .pairs
+                .into_iter()
+                .map(|p| (p.path_1, p.path_2))
+                .collect::<Vec<_>>()
+        };
+        // the partner first in path order wins, whatever the input order
+        let expected = vec![
+            ("<u1<u2<u3".to_string(), ">u3>u2>u1".to_string()),
+            ("<u5<u2<u4".to_string(), ">u4>u2>u5".to_string()),
+        ];
+        assert_eq!(pairs(entries.clone()), expected);
+        let mut reversed = entries;
+        reversed.reverse();
+        assert_eq!(pairs(reversed), expected);
+    }
+
+    #[test]
+    fn test_paired_only_entropy() {
+        let counts: HashMap<PathKey, u64> = [
+            (">u1>u2>u3", 10),
+            ("<u3<u2<u1", 10),
+            // no reverse observed
+            (">u4>u2>u5", 20),
+        ]
+        .into_iter()
+        .map(|(p, c)| (("u2".to_string(), p.to_string()), c))
+        .collect();
+        let paths = Paths::from_vec(counts.iter().collect()).split_into_repeats();
+
+        let (all, _, _) =
+            output_entropy_lines(&mut io::sink(), &paths, &EntropyOptions::default(), 3).unwrap();
+        // 0.25, 0.25, 0.5
+        assert!((all - 1.5).abs() < 1e-9);
+
+        let paired: Vec<Paths> = paths.iter().map(|p| p.paired(None)).collect();
+        assert_eq!(paired[0].paths.len(), 2);
+        let (paired, _, _) =
+            output_entropy_lines(&mut io::sink(), &paired, &EntropyOptions::default(), 3).unwrap();
+        assert!((paired - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_split_into_repeats_merges_by_repeat_id() {
+        let paths = Paths::new(vec![
+            (
+                ("u66".into(), string_to_path("<u67<u66>u65".into()).unwrap()),
+                100,
+            ),
+            (
+                ("u69".into(), string_to_path(">u65<u69<u67".into()).unwrap()),
+                175,
+            ),
+            (
+                ("u66".into(), string_to_path("<u65>u66>u67".into()).unwrap()),
+                180,
+            ),
+            (
+                ("u69".into(), string_to_path(">u67>u69<u65".into()).unwrap()),
+                171,
+            ),
+        ]);
+
+        let merged = paths.split_into_repeats();
+
+        assert_eq!(merged.len(), 2);
+        let mut ids: Vec<String> = merged.iter().map(|p| p.paths[0].0 .0.clone()).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["u66", "u69"]);
+    }
+
+    /// A minimal GAF record aligning a read to `path`.
+    fn gaf_line(path: &str) -> String {
+        gaf_line_with_length(path, 100)
+    }
+
+    /// A minimal GAF record aligning a read over `length` bases of `path`.
+    fn gaf_line_with_length(path: &str, length: usize) -> String {
+        format!(
+            "read\t{length}\t0\t{length}\t+\t{path}\t300\t0\t{length}\t{length}\t{length}\t60\n"
+        )
+    }
+
+    #[test]
+    fn test_exclude_single_path() {
+        let gaf = [
+            // u2: two paths in equal use, an entropy of 1
+            vec![gaf_line(">u1>u2>u3"); 3],
+            vec![gaf_line("<u3<u2<u1"); 3],
+            // u5: a single path, an entropy of 0
+            vec![gaf_line(">u4>u5>u6"); 5],
+        ]
+        .concat()
+        .concat();
+        let nodes = vec!["u2".to_string(), "u5".to_string()];
+        let counts = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default()).unwrap();
+        let paths = path_counts(&counts.support);
+        let groups = Paths::from_vec(paths.iter().collect()).split_into_repeats();
+
+        let (all, total, rows) =
+            output_entropy_lines(&mut io::sink(), &groups, &EntropyOptions::default(), 3).unwrap();
+        assert!((all - 0.5).abs() < 1e-9);
+        assert_eq!(rows.len(), 2);
+
+        let options = EntropyOptions {
+            multi_path_mean: true,
+            ..Default::default()
+        };
+        let (multi, multi_total, multi_rows) =
+            output_entropy_lines(&mut io::sink(), &groups, &options, 3).unwrap();
+        assert!((multi - 1.0).abs() < 1e-9);
+        // the single-path repeat is still listed, and the total unchanged
+        assert_eq!(multi_rows.len(), 2);
+        assert_eq!(multi_rows[1].entropy, Some(0.0));
+        assert_eq!(multi_total, total);
+    }
+
+    #[test]
+    fn test_head() {
+        let gaf = [
+            gaf_line(">u1>u2>u3"),
+            "# a comment counts as a line\n".to_string(),
+            gaf_line("<u3<u2<u1"),
+            "not a GAF line\n".to_string(),
+            gaf_line(">u1>u2>u3"),
+        ]
+        .concat();
+        let nodes = vec!["u2".to_string()];
+        let options = CountOptions {
+            head: Some(3),
+            ..Default::default()
+        };
+        // the malformed 4th line is never read
+        let counts = count_paths(gaf.as_bytes(), &nodes, &options).unwrap();
+        assert_eq!(counts.alignments, 2);
+        assert_eq!(counts.support.values().map(|s| s.count).sum::<u64>(), 2);
+
+        let options = CountOptions {
+            head: Some(4),
+            ..Default::default()
+        };
+        assert!(count_paths(gaf.as_bytes(), &nodes, &options).is_err());
+    }
+
+    #[test]
+    fn test_windows_line_endings() {
+        let gaf = format!(
+            "\u{FEFF}{}\r\n{}\r",
+            gaf_line(">u1>u2>u3").trim_end(),
+            gaf_line("<u3<u2<u1").trim_end()
+        );
+        let nodes = vec!["u2".to_string()];
+        let counts = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default()).unwrap();
+        let mut paths: Vec<_> = counts.support.keys().map(|(_, p)| p.as_str()).collect();
+        paths.sort();
+        assert_eq!(paths, ["<u3<u2<u1", ">u1>u2>u3"]);
+    }
+
+    #[test]
+    fn test_interned_counts_match_path_strings() {
+        // long IDs, revisits and several focal nodes per window
+        let id = |i: usize| format!("contig_{}_{}", "x".repeat(200), i);
+        let paths: Vec<String> = [
+            vec![(">", 1), (">", 2), (">", 3)],
+            vec![("<", 3), ("<", 2), ("<", 1)],
+            vec![(">", 4), (">", 2), ("<", 5), (">", 2), (">", 4)],
+            vec![(">", 2), (">", 2), (">", 2)],
+        ]
+        .iter()
+        .map(|steps| {
+            steps
+                .iter()
+                .map(|(o, i)| format!("{}{}", o, id(*i)))
+                .collect()
+        })
+        .collect();
+        let gaf: String = (0..50).map(|i| gaf_line(&paths[i % paths.len()])).collect();
+        let nodes = vec![id(2), id(4)];
+        let options = CountOptions {
+            multi_focal: true,
+            long_reads: true,
+            ..Default::default()
+        };
+        let counts = count_paths(gaf.as_bytes(), &nodes, &options).unwrap();
+
+        // the same counts, keyed by strings from the start
+        let mut expected: HashMap<PathKey, u64> = HashMap::new();
+        for i in 0..50 {
+            let path = &paths[i % paths.len()];
+            let mut starts: Vec<usize> = path
+                .match_indices(&['>', '<'][..])
+                .map(|(i, _)| i)
+                .collect();
+            starts.push(path.len());
+            let steps: Vec<&str> = starts.windows(2).map(|w| &path[w[0]..w[1]]).collect();
+            let mut seen = HashSet::new();
+            for window in steps.windows(3) {
+                for step in window {
+                    let key = (step[1..].to_string(), window.concat());
+                    if nodes.contains(&key.0) && seen.insert(key.clone()) {
+                        *expected.entry(key).or_default() += 1;
+                    }
+                }
+            }
+        }
+        assert_eq!(path_counts(&counts.support), expected);
+        // 12 five-step reads, each counted for 5 (focal node, window)s
+        assert_eq!(counts.source_lengths[&5], 60);
+    }
+
+    #[test]
+    fn test_batched_counts_match_line_by_line() {
+        // enough lines for several chunks, whether or not they're counted in parallel
+        let paths = [">u1>u2>u3", "<u3<u2<u1", ">u4>u2>u5>u6", "*", ">u2>u5>u6"];
+        let gaf: String = (0..3 * CHUNK_LINES + 7)
+            .map(|i| match i % 97 {
+                0 => "# comment\n".to_string(),
+                _ => gaf_line_with_length(paths[i % paths.len()], 10 + i % 13),
+            })
+            .collect();
+        let nodes = vec!["u2".to_string(), "u5".to_string()];
+        let options = CountOptions {
+            multi_focal: true,
+            long_reads: true,
+            ..Default::default()
+        };
+
+        let batched = count_paths(gaf.as_bytes(), &nodes, &options).unwrap();
+        let mut serial = InternedCounts::default();
+        for (i, line) in gaf.lines().enumerate() {
+            count_line(i, line.as_bytes(), &nodes, &options, &mut serial).unwrap();
+        }
+        let serial = serial.resolve();
+        assert_eq!(batched.support, serial.support);
+        assert_eq!(batched.source_lengths, serial.source_lengths);
+        assert_eq!(batched.unaligned, serial.unaligned);
+        assert_eq!(batched.malformed, serial.malformed);
+        assert_eq!(batched.alignments, serial.alignments);
+        assert!(batched.support[&("u2".to_string(), ">u1>u2>u3".to_string())].count > 0);
+
+        // the first bad line is reported, even if a later chunk also has one
+        let mut lines: Vec<&str> = gaf.lines().collect();
+        lines[2 * CHUNK_LINES + 1] = "bad";
+        lines[CHUNK_LINES + 3] = "bad";
+        let gaf = lines.join("\n");
+        let err = count_paths(gaf.as_bytes(), &nodes, &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("Error parsing GAF line {}", CHUNK_LINES + 3)
+        );
+    }
+
+    #[test]
+    fn test_count_paths_centre_only() {
+        let gaf = [gaf_line(">u1>u2>u3"), gaf_line(">u1>u2>u3")].concat();
+        let nodes = vec!["u2".to_string(), "u3".to_string()];
+
+        let counts = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default())
+            .unwrap()
+            .support;
+
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts[&("u2".into(), ">u1>u2>u3".into())].count, 2);
+        assert_eq!(counts[&("u2".into(), ">u1>u2>u3".into())].length, 200);
+    }
+
+    #[test]
+    fn test_count_paths_long_reads() {
+        let gaf = [gaf_line(">u1>u2>u3"), gaf_line(">u1>u2>u3>u4>u5")].concat();
+        let nodes = vec!["u2".to_string(), "u3".to_string()];
+
+        // by default the 5-node read is skipped
+        let GafCounts {
+            support: counts,
+            source_lengths: lengths,
+            ..
+        } = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default()).unwrap();
+        assert_eq!(counts.len(), 1);
+        assert_eq!(lengths, SourceLengths::from([(3, 1)]));
+
+        let GafCounts {
+            support: counts,
+            source_lengths: lengths,
+            ..
+        } = count_paths(
+            gaf.as_bytes(),
+            &nodes,
+            &CountOptions {
+                long_reads: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[&("u2".into(), ">u1>u2>u3".into())].count, 2);
+        assert_eq!(counts[&("u3".into(), ">u2>u3>u4".into())].count, 1);
+        // the >u3>u4>u5 window is centred on a non-focal node
+        assert_eq!(lengths, SourceLengths::from([(3, 1), (5, 2)]));
+    }
+
+    #[test]
+    fn test_gaf_dialects() {
+        let standard = gaf_line(">u1>u2<u3");
+        for (dialect, path) in [
+            (GafDialect::Comma, ">u1,>u2,<u3"),
+            (GafDialect::Space, ">u1 >u2 <u3"),
+            (GafDialect::SegmentList, "u1+,u2+,u3-"),
+        ] {
+            let line = gaf_line(path);
+            assert_eq!(
+                dialect.normalize(line.trim_end().as_bytes()).as_ref(),
+                standard.trim_end().as_bytes(),
+                "{}",
+                dialect
+            );
+            assert_eq!(dialect.to_string().parse::<GafDialect>(), Ok(dialect));
+        }
+        assert!("minimap".parse::<GafDialect>().is_err());
+
+        let nodes = vec!["u2".to_string()];
+        let gaf = gaf_line(">u1,>u2,<u3");
+        let counts = count_paths(
+            gaf.as_bytes(),
+            &nodes,
+            &CountOptions {
+                dialect: GafDialect::Comma,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .support;
+        assert_eq!(counts[&("u2".into(), ">u1>u2<u3".into())].count, 1);
+
+        // without the dialect, the error names the one that would have worked
+        let err = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("looks like the comma dialect"), "{}", err);
+        assert!(err.contains("try --gaf-dialect comma"), "{}", err);
+
+        let gaf = gaf_line("u1+,u2+,u3-");
+        let err = count_paths(
+            gaf.as_bytes(),
+            &nodes,
+            &CountOptions {
+                dialect: GafDialect::Comma,
+                ..Default::default()
+            },
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            err.contains("looks like the segment-list dialect, not comma"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_no_revisit() {
+        let gaf = [
+            gaf_line(">u1>u2>u3"),
+            gaf_line(">u2>u2>u3"),
+            gaf_line(">u1>u2<u1"),
+            gaf_line(">u4>u2>u5>u4"),
+        ]
+        .concat();
+        let nodes = vec!["u2".to_string()];
+        let options = CountOptions {
+            long_reads: true,
+            ..Default::default()
+        };
+        let counts = count_paths(gaf.as_bytes(), &nodes, &options).unwrap();
+        assert_eq!(counts.support.len(), 4);
+        assert_eq!(counts.revisiting, 0);
+
+        let options = CountOptions {
+            no_revisit: true,
+            ..options
+        };
+        let counts = count_paths(gaf.as_bytes(), &nodes, &options).unwrap();
+        // revisits in either orientation, and the cycle of the long read, are dropped
+        assert_eq!(counts.revisiting, 3);
+        let paths: Vec<&PathKey> = counts.support.keys().collect();
+        assert_eq!(paths, [&("u2".to_string(), ">u1>u2>u3".to_string())]);
+    }
+
+    #[test]
+    fn test_count_paths_malformed() {
+        let gaf = [
+            gaf_line(">u1>u2>u3"),
+            // a negative start, a non-numeric end, and an end before the start
+            "read\t100\t0\t100\t+\t>u4>u2>u3\t300\t-5\t100\t100\t100\t60\n".to_string(),
+            "read\t100\t0\t100\t+\t>u4>u2>u3\t300\t0\tabc\t100\t100\t60\n".to_string(),
+            "read\t100\t0\t100\t+\t>u4>u2>u3\t300\t200\t100\t100\t100\t60\n".to_string(),
+            // past the end of the path
+            "read\t100\t0\t100\t+\t>u4>u2>u3\t300\t0\t400\t100\t100\t60\n".to_string(),
+        ]
+        .concat();
+        let nodes = vec!["u2".to_string()];
+        let options = CountOptions {
+            length_weights: true,
+            ..Default::default()
+        };
+
+        let counts = count_paths(gaf.as_bytes(), &nodes, &options).unwrap();
+        assert_eq!(counts.malformed, 4);
+        assert_eq!(counts.alignments, 1);
+        // only the good read is counted, and weighted
+        assert_eq!(counts.support.len(), 1);
+        let good = &counts.support[&("u2".to_string(), ">u1>u2>u3".to_string())];
+        assert_eq!((good.count, good.length), (1, 100));
+
+        // without length weights, the inconsistent but parseable lines still count
+        let gaf = [
+            gaf_line(">u1>u2>u3"),
+            "read\t100\t0\t100\t+\t>u4>u2>u3\t300\t200\t100\t100\t100\t60\n".to_string(),
+            "read\t100\t0\t100\t+\t>u4>u2>u3\t300\t0\t400\t100\t100\t60\n".to_string(),
+        ]
+        .concat();
+        let counts = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default()).unwrap();
+        assert_eq!(counts.malformed, 0);
+        assert_eq!(counts.alignments, 3);
+        assert_eq!(
+            counts.support[&("u2".to_string(), ">u4>u2>u3".to_string())].count,
+            2
+        );
+    }
+
+    #[test]
+    fn test_count_paths_unaligned() {
+        let gaf = [
+            gaf_line(">u1>u2>u3"),
+            gaf_line("*"),
+            "read\t100\t0\t100\t+\t\t300\t0\t100\t100\t100\t60\n".to_string(),
+        ]
+        .concat();
+        let nodes = vec!["u2".to_string()];
+
+        let counts = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default()).unwrap();
+        assert_eq!(counts.unaligned, 2);
+        assert_eq!(counts.support.len(), 1);
+        assert_eq!(counts.source_lengths, SourceLengths::from([(3, 1)]));
+    }
+
+    #[test]
+    fn test_subsample_is_deterministic() {
+        let gaf = (0..1000).map(|_| gaf_line(">u1>u2>u3")).collect::<String>();
+        let nodes = vec!["u2".to_string()];
+        let count = |fraction, seed| {
+            let subsample = Subsample { fraction, seed };
+            count_paths(
+                gaf.as_bytes(),
+                &nodes,
+                &CountOptions {
+                    subsample: Some(subsample),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .alignments
+        };
+        let kept: Vec<usize> = (0..1000)
+            .filter(|&i| {
+                Subsample {
+                    fraction: 0.1,
+                    seed: 42,
+                }
+                .keeps(i)
+            })
+            .collect();
+
+        assert_eq!(count(0.1, 42), count(0.1, 42));
+        assert_eq!(count(0.1, 42), kept.len() as u64);
+        assert!((50..150).contains(&kept.len()), "{}", kept.len());
+        let other: Vec<usize> = (0..1000)
+            .filter(|&i| {
+                Subsample {
+                    fraction: 0.1,
+                    seed: 7,
+                }
+                .keeps(i)
+            })
+            .collect();
+        assert_ne!(kept, other);
+        assert_eq!(count(1.0, 42), 1000);
+        assert_eq!(count(0.0, 42), 0);
+    }
+
+    #[test]
+    fn test_group_by_tag() {
+        let tagged = |path: &str, barcode: &str| {
+            gaf_line(path).replace('\n', &format!("\tBC:Z:{barcode}\n"))
+        };
+        // sample A recombines evenly, sample B hardly at all
+        let gaf = [
+            vec![tagged(">u1>u2>u3", "A"); 5],
+            vec![tagged("<u3<u2<u1", "A"); 5],
+            vec![tagged(">u1>u2>u3", "B"); 9],
+            vec![tagged("<u3<u2<u1", "B"); 1],
+            vec![gaf_line(">u1>u2>u3")],
+        ]
+        .concat()
+        .concat();
+        let nodes = vec!["u2".to_string()];
+        let options = CountOptions {
+            group_tag: Some(b"BC"),
+            ..Default::default()
+        };
+        let counts = count_paths(gaf.as_bytes(), &nodes, &options).unwrap();
+        assert_eq!(counts.support[&("u2".into(), ">u1>u2>u3".into())].count, 15);
+        assert_eq!(counts.tagged.keys().collect::<Vec<_>>(), ["A", "B", "NA"]);
+
+        let mut out = Vec::new();
+        let records = output_tag_group_lines(&mut out, &counts.tagged, false, 3);
+        assert_eq!(records.len(), 3);
+        assert_eq!((records[0].tag.as_str(), records[0].reads), ("A", 10));
+        assert!((records[0].recombination_potential - 1.0).abs() < 1e-9);
+        assert!((records[0].mean_entropy - 1.0).abs() < 1e-9);
+        assert_eq!((records[1].tag.as_str(), records[1].reads), ("B", 10));
+        assert!((records[1].recombination_potential - 0.2).abs() < 1e-9);
+        assert_eq!(records[2].rci, 0.0);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("tag\trepeats\treads\trecombination_potential\trci\tmean_entropy\n"));
+        assert!(out.contains("A\t1\t10\t1.000\t1.000\t1.000\n"));
+    }
+
+    #[test]
+    fn test_tag_value() {
+        let line = b"r1\t100\t0\t100\t+\t>u1>u2>u3\t300\t0\t100\t100\t100\t60\tBC:Z:ACGT:1\tHP:i:2";
+        let gaf = parse_gaf::<_, Vec<OptField>>(line.split_str(b"\t")).unwrap();
+        // a `:` in a string value is kept, other types are formatted as written
+        assert_eq!(tag_value(&gaf.optional, b"BC"), "ACGT:1");
+        assert_eq!(tag_value(&gaf.optional, b"HP"), "2");
+        assert_eq!(tag_value(&gaf.optional, b"PS"), "NA");
+    }
+
+    #[test]
+    fn test_orientation_symbols() {
+        assert_eq!(parse_orientation(b'>'), Some(Orientation::Forward));
+        assert_eq!(parse_orientation(b'+'), Some(Orientation::Forward));
+        assert_eq!(parse_orientation(b'<'), Some(Orientation::Backward));
+        assert_eq!(parse_orientation(b'-'), Some(Orientation::Backward));
+        assert_eq!(parse_orientation(b'*'), None);
+        for orientation in [Orientation::Forward, Orientation::Backward] {
+            assert_eq!(
+                parse_orientation(orientation_symbol(orientation) as u8),
+                Some(orientation)
+            );
+        }
+
+        // arrow and signed notations of the same path agree
+        let arrows = b">u1<u2>u3";
+        let signed = GafDialect::SegmentList.normalize_path(b"u1+,u2-,u3+");
+        assert_eq!(signed, arrows);
+        let path = string_to_path(String::from_utf8(signed).unwrap()).unwrap();
+        assert_eq!(path.repeat().orientation, Orientation::Backward);
+        assert_eq!(path.to_string().as_bytes(), arrows);
+    }
+
+    #[test]
+    fn test_combine_counts() {
+        let first = [
+            vec![gaf_line(">u1>u2>u3"); 4],
+            vec![gaf_line("<u3<u2<u1"); 2],
+        ]
+        .concat()
+        .concat();
+        let second = [
+            vec![gaf_line(">u1>u2>u3"); 1],
+            vec![gaf_line(">u4>u2>u5"); 3],
+        ]
+        .concat()
+        .concat();
+        let nodes = vec!["u2".to_string()];
+        let count = |combine| {
+            let files = [&first, &second]
+                .map(|gaf| count_paths(gaf.as_bytes(), &nodes, &CountOptions::default()).unwrap());
+            let counts = combine_counts(files.into(), combine);
+            assert_eq!(counts.alignments, 10);
+            let mut paths: Vec<(String, u64)> = counts
+                .support
+                .into_iter()
+                .map(|((_, path), s)| (path, s.count))
+                .collect();
+            paths.sort();
+            paths
+        };
+        let path = |path: &str, count: u64| (path.to_string(), count);
+
+        assert_eq!(
+            count(Combine::Sum),
+            [
+                path("<u3<u2<u1", 2),
+                path(">u1>u2>u3", 5),
+                path(">u4>u2>u5", 3)
+            ]
+        );
+        assert_eq!(
+            count(Combine::Max),
+            [
+                path("<u3<u2<u1", 2),
+                path(">u1>u2>u3", 4),
+                path(">u4>u2>u5", 3)
+            ]
+        );
+        // (2 + 0) / 2, (4 + 1) / 2 rounded and (0 + 3) / 2 rounded
+        assert_eq!(
+            count(Combine::Mean),
+            [
+                path("<u3<u2<u1", 1),
+                path(">u1>u2>u3", 3),
+                path(">u4>u2>u5", 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_entropy_by_focal_position() {
+        // u2 is centred in the first pair, and starts or ends the second
+        let counts: HashMap<PathKey, u64> = [
+            (">u1>u2>u3", 10),
+            ("<u3<u2<u1", 10),
+            (">u2>u5>u6", 5),
+            ("<u6<u5<u2", 5),
+        ]
+        .into_iter()
+        .map(|(path, count)| (("u2".to_string(), path.to_string()), count))
+        .collect();
+        let paths = Paths::from_vec(counts.iter().collect()).split_into_repeats();
+
+        let (_, _, mixed) =
+            output_entropy_lines(&mut io::sink(), &paths, &EntropyOptions::default(), 3).unwrap();
+        assert_eq!(mixed.len(), 1);
+        assert_eq!(mixed[0].focal_pos, None);
+        // four paths with 1/3, 1/3, 1/6, 1/6 of the reads
+        let expected = (3f64).log2() * 2.0 / 3.0 + (6f64).log2() / 3.0;
+        assert!((mixed[0].entropy.unwrap() - expected).abs() < 1e-9);
+
+        let mut out = Vec::new();
+        let (mean, _, rows) = output_entropy_lines(
+            &mut out,
+            &paths,
+            &EntropyOptions {
+                multi_focal: true,
+                ..Default::default()
+            },
+            3,
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 2);
+        for (row, pos) in rows.iter().zip([0, 1]) {
+            assert_eq!(row.repeat_node, "u2");
+            assert_eq!(row.focal_pos, Some(pos));
+            assert_eq!(row.path_count, 2);
+            assert!((row.entropy.unwrap() - 1.0).abs() < 1e-9);
+        }
+        assert!((mean - 1.0).abs() < 1e-9);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("rev_cov\ttop_path\ttop_path_frac\tfocal_pos\n"));
+        assert!(out.contains("u2\t2\t1.000\t5\t5\t<u6<u5<u2\t0.500\t0\n"));
+        assert!(out.contains("u2\t2\t1.000\t10\t10\t<u3<u2<u1\t0.500\t1\n"));
+    }
+
+    #[test]
+    fn test_check_probabilities() {
+        let weights = [100.0, 100.0, 50.0];
+        assert_eq!(check_probabilities("u66", &weights, 250.0), None);
+        // a third of 3 isn't exactly representable, but is within epsilon
+        assert_eq!(check_probabilities("u66", &[1.0; 3], 3.0), None);
+
+        let drift = check_probabilities("u66", &weights, 200.0).unwrap();
+        assert!((drift - 1.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_oriented_focal_nodes() {
+        let specs = ["u2+", "u5", "u7-", "u7+", "a-b"].map(String::from);
+        let (ids, orientations) = parse_focal_specs(&specs);
+        assert_eq!(ids, ["u2", "u5", "u7", "a-b"]);
+        assert_eq!(
+            orientations,
+            FocalOrientations::from([("u2".to_string(), Orientation::Forward)])
+        );
+
+        let gaf = [
+            vec![gaf_line(">u1>u2>u3"); 3],
+            vec![gaf_line("<u3<u2<u1"); 2],
+        ]
+        .concat()
+        .concat();
+        let nodes = vec!["u2".to_string()];
+        let count = |specs: &[&str]| {
+            let specs: Vec<String> = specs.iter().map(|s| s.to_string()).collect();
+            let (_, orientations) = parse_focal_specs(&specs);
+            let options = CountOptions {
+                orientations: Some(&orientations),
+                ..Default::default()
+            };
+            let counts = count_paths(gaf.as_bytes(), &nodes, &options).unwrap();
+            let mut paths: Vec<(String, u64)> = counts
+                .support
+                .into_iter()
+                .map(|((_, path), s)| (path, s.count))
+                .collect();
+            paths.sort();
+            paths
+        };
+
+        // unoriented matches both strands, oriented only its own
+        assert_eq!(
+            count(&["u2"]),
+            [("<u3<u2<u1".to_string(), 2), (">u1>u2>u3".to_string(), 3)]
+        );
+        assert_eq!(count(&["u2+"]), [(">u1>u2>u3".to_string(), 3)]);
+        assert_eq!(count(&["u2-"]), [("<u3<u2<u1".to_string(), 2)]);
+    }
+
+    #[test]
+    fn test_min_repeat_cov() {
+        let gaf = [
+            vec![gaf_line(">u1>u2>u3"); 6],
+            vec![gaf_line("<u3<u2<u1"); 4],
+            // a weakly supported repeat
+            vec![gaf_line(">u4>u5>u6"); 2],
+            vec![gaf_line("<u6<u5<u4"); 1],
+        ]
+        .concat()
+        .concat();
+        let nodes = vec!["u2".to_string(), "u5".to_string()];
+        let counts = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default()).unwrap();
+        let paths = path_counts(&counts.support);
+        let mut groups = Paths::from_vec(paths.iter().collect()).split_into_repeats();
+
+        assert_eq!(drop_weak_repeats(&mut groups.clone(), 3), 0);
+        assert_eq!(drop_weak_repeats(&mut groups, 4), 1);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].paths[0].0 .0, "u2");
+
+        let report = output_repeat_lines(
+            &mut io::sink(),
+            &groups,
+            &PRINT_PAIRS,
+            &RciOptions::default(),
+            3,
+        );
+        assert_eq!(report.pairs.len(), 1);
+        assert!((report.recombination_potential - 0.8).abs() < 1e-9);
+        let (_, _, entropies) =
+            output_entropy_lines(&mut io::sink(), &groups, &EntropyOptions::default(), 3).unwrap();
+        assert_eq!(entropies.len(), 1);
+    }
+
+    #[test]
+    fn test_require_both_orientations() {
+        let gaf = [
+            vec![gaf_line(">u1>u2>u3"); 6],
+            vec![gaf_line("<u3<u2<u1"); 4],
+            // u5 is only ever read forward, whatever its flanks
+            vec![gaf_line(">u4>u5>u6"); 8],
+            vec![gaf_line("<u7>u5>u6"); 3],
+        ]
+        .concat()
+        .concat();
+        let nodes = vec!["u2".to_string(), "u5".to_string()];
+        let counts = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default()).unwrap();
+        let paths = path_counts(&counts.support);
+        let mut groups = Paths::from_vec(paths.iter().collect()).split_into_repeats();
+
+        assert_eq!(drop_single_orientation_repeats(&mut groups), 1);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].paths[0].0 .0, "u2");
+        assert_eq!(drop_single_orientation_repeats(&mut groups), 0);
+    }
+
+    #[test]
+    fn test_potential_weight() {
+        let gaf = [
+            // deep and skewed: 0.2 over 100 reads
+            vec![gaf_line(">u1>u2>u3"); 90],
+            vec![gaf_line("<u3<u2<u1"); 10],
+            // shallow and balanced: 1.0 over 2 reads
+            vec![gaf_line(">u4>u5>u6"); 1],
+            vec![gaf_line("<u6<u5<u4"); 1],
+        ]
+        .concat()
+        .concat();
+        let nodes = vec!["u2".to_string(), "u5".to_string()];
+        let counts = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default()).unwrap();
+        let paths = path_counts(&counts.support);
+        let groups = Paths::from_vec(paths.iter().collect()).split_into_repeats();
+
+        let equal = output_repeat_lines(
+            &mut io::sink(),
+            &groups,
+            &PRINT_PAIRS,
+            &RciOptions::default(),
+            3,
+        );
+        assert!((equal.recombination_potential - 0.6).abs() < 1e-9);
+        let options = PairOptions {
+            coverage_weighted: true,
+            ..PRINT_PAIRS
+        };
+        let mut out = Vec::new();
+        let weighted = output_repeat_lines(&mut out, &groups, &options, &RciOptions::default(), 3);
+        assert!((weighted.recombination_potential - 22.0 / 102.0).abs() < 1e-9);
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains("Recombination potential: 0.216\n"));
+        // the pair scores themselves don't change
+        assert_eq!(weighted.recomb_scores, equal.recomb_scores);
+    }
+
+    #[test]
+    fn test_n_pairs() {
+        let gaf = [
+            // u2: two reverse-complement pairs
+            vec![gaf_line(">u1>u2>u3"); 6],
+            vec![gaf_line("<u3<u2<u1"); 4],
+            vec![gaf_line(">u1>u2>u4"); 2],
+            vec![gaf_line("<u4<u2<u1"); 2],
+            // u5: two paths, neither with its reverse
+            vec![gaf_line(">u4>u5>u6"); 3],
+            vec![gaf_line(">u7>u5>u6"); 1],
+        ]
+        .concat()
+        .concat();
+        let nodes = vec!["u2".to_string(), "u5".to_string()];
+        let counts = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default()).unwrap();
+        let paths = path_counts(&counts.support);
+        let groups = Paths::from_vec(paths.iter().collect()).split_into_repeats();
+        let pairs = output_repeat_lines(
+            &mut io::sink(),
+            &groups,
+            &PRINT_PAIRS,
+            &RciOptions::default(),
+            3,
+        );
+        assert_eq!(pairs.pair_counts["u2"], 2);
+        assert!(!pairs.pair_counts.contains_key("u5"));
+
+        let mut out = Vec::new();
+        let (_, _, rows) = output_entropy_lines(
+            &mut out,
+            &groups,
+            &EntropyOptions {
+                pair_counts: Some(&pairs.pair_counts),
+                ..Default::default()
+            },
+            3,
+        )
+        .unwrap();
+        let n_pairs: Vec<_> = rows.iter().map(|r| (r.path_count, r.n_pairs)).collect();
+        assert_eq!(n_pairs, [(4, Some(2)), (2, Some(0))]);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("top_path_frac\tn_pairs\n"));
+        assert!(out.contains("\nu5\t2\t"));
+        assert!(out.contains("\t0.750\t0\n"));
+    }
+
+    #[test]
+    fn test_min_path_frac() {
+        let gaf = [
+            // deep: 3 reads are below 5% of 100
+            vec![gaf_line(">u1>u2>u3"); 100],
+            vec![gaf_line("<u3<u2<u1"); 90],
+            vec![gaf_line(">u1>u2>u4"); 3],
+            // shallow: 1 read is not below 5% of 4
+            vec![gaf_line(">u4>u5>u6"); 4],
+            vec![gaf_line("<u6<u5<u4"); 1],
+        ]
+        .concat()
+        .concat();
+        let nodes = vec!["u2".to_string(), "u5".to_string()];
+        let counts = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default()).unwrap();
+        let paths = path_counts(&counts.support);
+        let mut groups = Paths::from_vec(paths.iter().collect()).split_into_repeats();
+
+        assert_eq!(drop_noise_paths(&mut groups, 0.05), 1);
+        let path_counts: Vec<(&str, usize)> = groups
+            .iter()
+            .map(|g| (g.paths[0].0 .0.as_str(), g.paths.len()))
+            .collect();
+        assert_eq!(path_counts, [("u2", 2), ("u5", 2)]);
+        assert!(groups[0]
+            .paths
+            .iter()
+            .all(|((_, p), _)| p.to_string() != ">u1>u2>u4"));
+        assert_eq!(drop_noise_paths(&mut groups, 0.05), 0);
+    }
+
+    #[test]
+    fn test_query_pair() {
+        let gaf = [
+            vec![gaf_line(">u1>u2>u3"); 6],
+            vec![gaf_line("<u3<u2<u1"); 2],
+            // other flanks through the same repeat are ignored
+            vec![gaf_line(">u1>u2>u4"); 5],
+            vec![gaf_line("<u4<u2<u1"); 5],
+        ]
+        .concat()
+        .concat();
+        let nodes = vec!["u2".to_string()];
+        let counts = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default()).unwrap();
+        let paths = path_counts(&counts.support);
+        let groups = Paths::from_vec(paths.iter().collect()).split_into_repeats();
+
+        // the flanks can be given in either order
+        for (a, b) in [("u1", "u3"), ("u3", "u1")] {
+            let group = query_paths(&groups, a, "u2", b);
+            assert_eq!(group.paths.len(), 2);
+            let report = output_repeat_lines(
+                &mut io::sink(),
+                &[group],
+                &PRINT_PAIRS,
+                &RciOptions::default(),
+                3,
+            );
+            assert_eq!(report.pairs.len(), 1);
+            assert_eq!((report.pairs[0].cov_1, report.pairs[0].cov_2), (6, 2));
+            assert!((report.pairs[0].recomb_score - 0.5).abs() < 1e-9);
+        }
+        assert!(query_paths(&groups, "u3", "u2", "u4").paths.is_empty());
+    }
+
+    #[test]
+    fn test_anomalies() {
+        let entropy = |repeat: &str, path_count, entropy| EntropyRecord {
+            repeat_node: repeat.into(),
+            path_count,
+            entropy: Some(entropy),
+            fwd_cov: 0,
+            rev_cov: 0,
+            top_path: None,
+            top_path_frac: None,
+            focal_pos: None,
+            n_pairs: None,
+        };
+        let entropies = [
+            // four even paths where the graph allows only two: over-diverse
+            entropy("u2", 4, 2.0),
+            entropy("u5", 4, 2.0),
+            entropy("u7", 2, 1.0),
+            entropy("u9", 1, 0.0),
+        ];
+        let possible = HashMap::from([
+            ("u2".to_string(), 2),
+            ("u5".to_string(), 4),
+            ("u7".to_string(), 4),
+            ("u9".to_string(), 1),
+        ]);
+
+        let flagged = anomalies(&entropies, &possible, AnomalyCutoff::Deviation(0.6));
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].repeat_node, "u2");
+        assert!((flagged[0].deviation - 1.0).abs() < 1e-9);
+
+        let flagged = anomalies(&entropies, &possible, AnomalyCutoff::Deviation(0.4));
+        let ids: Vec<&str> = flagged.iter().map(|r| r.repeat_node.as_str()).collect();
+        assert_eq!(ids, ["u2", "u7"]);
+
+        // deviations 1, 0 and -0.5: only u2 is more than 1.2 sd from the mean
+        let flagged = anomalies(&entropies, &possible, AnomalyCutoff::ZScore(1.2));
+        assert_eq!(flagged.len(), 1);
+        assert!((flagged[0].z_score.unwrap() - 1.336).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_count_paths_empty_gaf() {
+        let nodes = vec!["u2".to_string()];
+        for gaf in ["", "# produced by an aligner\n\n# nothing aligned\n"] {
+            let counts = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default()).unwrap();
+            assert_eq!(counts.alignments, 0);
+            assert!(counts.support.is_empty());
+        }
+
+        // an alignment that matched no focal node still counts as usable
+        let gaf = ["# header\n".to_string(), gaf_line(">u4>u5>u6")].concat();
+        let counts = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default()).unwrap();
+        assert_eq!(counts.alignments, 1);
+        assert!(counts.support.is_empty());
+    }
+
+    #[test]
+    fn test_count_paths_multi_focal() {
+        // centre (u2) and flank (u3) are both focal repeats
+        let gaf = [gaf_line(">u1>u2>u3"), gaf_line(">u1>u2>u3")].concat();
+        let nodes = vec!["u2".to_string(), "u3".to_string()];
+
+        let counts = count_paths(
+            gaf.as_bytes(),
+            &nodes,
+            &CountOptions {
+                multi_focal: true,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .support;
+
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[&("u2".into(), ">u1>u2>u3".into())].count, 2);
+        assert_eq!(counts[&("u3".into(), ">u1>u2>u3".into())].count, 2);
+
+        let path = string_to_path(">u1>u2>u3".into()).unwrap();
+        assert_eq!(path.position_of("u2"), Some(1));
+        assert_eq!(path.position_of("u3"), Some(2));
+    }
+
+    #[test]
+    fn test_count_paths_min_flank() {
+        let gaf = [
+            gaf_line(">u1>u2>u3"),
+            gaf_line(">u1>u2>short"),
+            gaf_line("<short<u2<u1"),
+        ]
+        .concat();
+        let sizes: HashMap<Vec<u8>, usize> = [
+            (b"u1".to_vec(), 20000),
+            (b"u2".to_vec(), 500),
+            (b"u3".to_vec(), 15000),
+            (b"short".to_vec(), 200),
+        ]
+        .into_iter()
+        .collect();
+        let filter = FlankFilter {
+            sizes: &sizes,
+            min_size: 10000,
+        };
+        let nodes = vec!["u2".to_string()];
+
+        let all = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default())
+            .unwrap()
+            .support;
+        assert_eq!(all.len(), 3);
+
+        // the small repeat itself is fine, only its flanks are checked
+        let counts = count_paths(
+            gaf.as_bytes(),
+            &nodes,
+            &CountOptions {
+                min_flank: Some(&filter),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .support;
+        assert_eq!(
+            counts.keys().collect::<Vec<_>>(),
+            vec![&("u2".to_string(), ">u1>u2>u3".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_write_counts() {
+        let gaf = [
+            gaf_line(">u1>u2>u3"),
+            gaf_line("<u3<u2<u1"),
+            gaf_line(">u1>u2>u3"),
+            gaf_line(">u4>u5>u6"),
+        ]
+        .concat();
+        let nodes = vec!["u2".to_string(), "u5".to_string()];
+        let counts = path_counts(
+            &count_paths(gaf.as_bytes(), &nodes, &CountOptions::default())
+                .unwrap()
+                .support,
+        );
+
+        let mut out = Vec::new();
+        write_counts(&counts, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "repeat_node\tpath\tcount\n\
+             u2\t<u3<u2<u1\t1\n\
+             u2\t>u1>u2>u3\t2\n\
+             u5\t>u4>u5>u6\t1\n"
+        );
+    }
+
+    #[test]
+    fn test_counts_round_trip() {
+        let gaf = [
+            gaf_line(">u1>u2>u3"),
+            gaf_line("<u3<u2<u1"),
+            gaf_line(">u1>u2>u3"),
+            gaf_line(">u4>u2>u6"),
+            gaf_line("<u6<u2<u4"),
+            gaf_line(">u4>u5>u6"),
+        ]
+        .concat();
+        let nodes = vec!["u2".to_string(), "u5".to_string()];
+        let counts = path_counts(
+            &count_paths(gaf.as_bytes(), &nodes, &CountOptions::default())
+                .unwrap()
+                .support,
+        );
+
+        let mut dump = Vec::new();
+        write_counts(&counts, &mut dump).unwrap();
+        let reloaded = read_counts(dump.as_slice(), PathNotation::Arrow).unwrap();
+        assert_eq!(reloaded, counts);
+
+        let report = |counts: &HashMap<PathKey, u64>| {
+            let paths = Paths::from_vec(counts.iter().collect()).split_into_repeats();
+            let mut out = Vec::new();
+            output_repeat_lines(&mut out, &paths, &PRINT_PAIRS, &RciOptions::default(), 3);
+            output_entropy_lines(&mut out, &paths, &EntropyOptions::default(), 3);
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(report(&reloaded), report(&counts));
+    }
+
+    #[test]
+    fn test_flank_sequences() {
+        let flanks = FlankSequences {
+            sequences: HashMap::from([
+                ("u1".to_string(), b"AAAACCG".to_vec()),
+                ("u2".to_string(), b"TTTT".to_vec()),
+                ("u3".to_string(), b"GGATTT".to_vec()),
+                ("u4".to_string(), b"*".to_vec()),
+            ]),
+            length: 3,
+        };
+        let flanks_of = |p: &str| flanks.flanks(&string_to_path(p.into()).unwrap(), 1);
+
+        // the bases next to the repeat: the end of the left flank, the start of the right
+        assert_eq!(flanks_of(">u1>u2>u3"), ("CCG".into(), "GGA".into()));
+        // traversed in reverse, each flank is reverse complemented
+        assert_eq!(flanks_of("<u3<u2<u1"), ("TCC".into(), "CGG".into()));
+        assert_eq!(flanks_of(">u1>u2<u3"), ("CCG".into(), "AAA".into()));
+        assert_eq!(flanks_of(">u4>u2>u5"), ("NA".into(), "NA".into()));
+        // with --multi-focal, the flanks are around the focal position instead
+        let path = string_to_path(">u1>u2>u3".into()).unwrap();
+        assert_eq!(flanks.flanks(&path, 0), ("NA".into(), "TTT".into()));
+        assert_eq!(flanks.flanks(&path, 2), ("TTT".into(), "NA".into()));
+
+        let entry = |p: &str| (("u2".to_string(), string_to_path(p.into()).unwrap()), 5);
+        let group = [Paths::new(vec![entry(">u1>u2>u3"), entry("<u3<u2<u1")])];
+        let options = PairOptions {
+            flanks: Some(&flanks),
+            ..PRINT_PAIRS
+        };
+        let mut out = Vec::new();
+        let report = output_repeat_lines(&mut out, &group, &options, &RciOptions::default(), 3);
+        let pair = &report.pairs[0];
+        assert_eq!(pair.path_1, "<u3<u2<u1");
+        assert_eq!(pair.left_flank.as_deref(), Some("TCC"));
+        assert_eq!(pair.right_flank.as_deref(), Some("CGG"));
+        assert!(String::from_utf8(out).unwrap().starts_with(
+            "path_1\tcov_1\tpath_2\tcov_2\trecomb_score\tleft_flank\tright_flank\n\
+             <u3<u2<u1\t5\t>u1>u2>u3\t5\t1.000\tTCC\tCGG\n"
+        ));
+    }
+
+    #[test]
+    fn test_recombination_rate() {
+        let entry = |p: &str, c| (("u2".to_string(), string_to_path(p.into()).unwrap()), c);
+        let groups = [Paths::new(vec![
+            // balanced: score 0.9
+            entry(">u1>u2>u3", 55),
+            entry("<u3<u2<u1", 45),
+            // unbalanced: score 0.2
+            entry(">u4>u2>u5", 90),
+            entry("<u5<u2<u4", 10),
+            // unpaired, but still through the repeat
+            entry(">u6>u2>u7", 50),
+        ])];
+        let report = output_repeat_lines(
+            &mut io::sink(),
+            &groups,
+            &PRINT_PAIRS,
+            &RciOptions::default(),
+            3,
+        );
+        assert_eq!(report.pairs.len(), 2);
+
+        // 100 of the 250 reads are in the balanced pair
+        assert!((recombination_rate(&report.pairs, &groups, None, 0.5) - 0.4).abs() < 1e-9);
+        assert!((recombination_rate(&report.pairs, &groups, None, 0.1) - 0.8).abs() < 1e-9);
+        assert_eq!(recombination_rate(&report.pairs, &groups, None, 0.95), 0.0);
+        assert_eq!(recombination_rate(&[], &[], None, 0.5), 0.0);
+
+        // with --only-inverted, the reads of the other repeats aren't in the total
+        let other = string_to_path(">u8>u9>u10".into()).unwrap();
+        let groups = [
+            groups[0].clone(),
+            Paths::new(vec![(("u9".to_string(), other), 750)]),
+        ];
+        let inverted = HashSet::from(["u2".to_string()]);
+        assert!((recombination_rate(&report.pairs, &groups, None, 0.5) - 0.1).abs() < 1e-9);
+        let rate = recombination_rate(&report.pairs, &groups, Some(&inverted), 0.5);
+        assert!((rate - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_only_inverted_pairs() {
+        let group = |repeat: &str| {
+            let path = |p: String| ((repeat.to_string(), string_to_path(p).unwrap()), 5);
+            Paths::new(vec![
+                path(format!(">u1>{repeat}>u3")),
+                path(format!("<u3<{repeat}<u1")),
+            ])
+        };
+        let groups = [group("u2"), group("u7")];
+        let report = output_repeat_lines(
+            &mut io::sink(),
+            &groups,
+            &PRINT_PAIRS,
+            &RciOptions::default(),
+            3,
+        );
+        assert_eq!(report.pairs.len(), 2);
+
+        // u7 isn't a structurally inverted repeat, so its pair is left out
+        let inverted = HashSet::from(["u2".to_string()]);
+        let options = PairOptions {
+            inverted: Some(&inverted),
+            ..PRINT_PAIRS
+        };
+        let mut out = Vec::new();
+        let report = output_repeat_lines(&mut out, &groups, &options, &RciOptions::default(), 3);
+        assert_eq!(report.pairs.len(), 1);
+        assert!(report.pairs[0].path_1.contains("u2"));
+        assert!(!String::from_utf8(out).unwrap().contains("u7"));
+    }
+
+    #[test]
+    fn test_top_path() {
+        let entry = |p: &str, c| (("u2".to_string(), string_to_path(p.into()).unwrap()), c);
+        let group = Paths::new(vec![
+            entry(">u1>u2>u3", 2),
+            entry("<u3<u2<u1", 6),
+            entry(">u4>u2>u5", 6),
+            entry("<u5<u2<u4", 1),
+        ]);
+        // a tie goes to the first path in string order
+        assert_eq!(group.top_path(), ("<u3<u2<u1".to_string(), 0.4));
+
+        let (_, _, rows) =
+            output_entropy_lines(&mut io::sink(), &[group], &EntropyOptions::default(), 3).unwrap();
+        assert_eq!(rows[0].top_path.as_deref(), Some("<u3<u2<u1"));
+        assert_eq!(rows[0].top_path_frac, Some(0.4));
+
+        let dominant = Paths::new(vec![entry(">u1>u2>u3", 99), entry("<u3<u2<u1", 1)]);
+        assert_eq!(dominant.top_path(), (">u1>u2>u3".to_string(), 0.99));
+    }
 
-    // split the string on < or >
-    let split: Vec<&str> = s.split(['<', '>']).collect();
-    // remove empty strings in vec
-    let split: Vec<&str> = split.into_iter().filter(|&x| !x.is_empty()).collect();
-    // also collect a vec of the delimiters
-    let delimiters: Vec<&str> = s.split(|c| c != '<' && c != '>').collect();
-    // remove empty strings in vec
-    let delimiters: Vec<&str> = delimiters.into_iter().filter(|&x| !x.is_empty()).collect();
+    #[test]
+    fn test_relative_coverage() {
+        let entry = |p: &str, c| (("u2".to_string(), string_to_path(p.into()).unwrap()), c);
+        let group = [Paths::new(vec![
+            entry(">u1>u2>u3", 7),
+            entry("<u3<u2<u1", 3),
+            entry(">u4>u2>u5", 11),
+            entry("<u5<u2<u4", 13),
+        ])];
+        let options = PairOptions {
+            relative: true,
+            ..PRINT_PAIRS
+        };
+        let mut out = Vec::new();
+        let report = output_repeat_lines(&mut out, &group, &options, &RciOptions::default(), 3);
+        assert_eq!(report.pairs.len(), 2);
+        for pair in &report.pairs {
+            let (rel_1, rel_2) = (pair.rel_1.unwrap(), pair.rel_2.unwrap());
+            assert!((rel_1 + rel_2 - 1.0).abs() < 1e-9);
+            assert!((rel_1 - pair.cov_1 as f64 / (pair.cov_1 + pair.cov_2) as f64).abs() < 1e-9);
+            assert!((pair.recomb_score - 2.0 * rel_1.min(rel_2)).abs() < 1e-9);
+        }
+        assert!(String::from_utf8(out).unwrap().starts_with(
+            "path_1\tcov_1\tpath_2\tcov_2\trecomb_score\trel_1\trel_2\n\
+             <u5<u2<u4\t13\t>u4>u2>u5\t11\t0.917\t0.542\t0.458\n\
+             >u1>u2>u3\t7\t<u3<u2<u1\t3\t0.600\t0.700\t0.300\n"
+        ));
 
-    let mut inner_segment = Segment::default();
-    for (i, (path, orientation)) in split.iter().zip(delimiters).enumerate() {
-        inner_segment.orientation = match orientation {
-            "<" => Orientation::Backward,
-            ">" => Orientation::Forward,
-            _ => return Err(anyhow::anyhow!("no orientation")), // default
+        let report = output_repeat_lines(
+            &mut io::sink(),
+            &group,
+            &PRINT_PAIRS,
+            &RciOptions::default(),
+            3,
+        );
+        assert!(report.pairs.iter().all(|p| p.rel_1.is_none()));
+    }
+
+    #[test]
+    fn test_pair_pvalues() {
+        let entry = |p: &str, c| (("u2".to_string(), string_to_path(p.into()).unwrap()), c);
+        // a balanced and a strongly skewed pair, both deep
+        let group = [Paths::new(vec![
+            entry(">u1>u2>u3", 2510),
+            entry("<u3<u2<u1", 2490),
+            entry(">u4>u2>u5", 1500),
+            entry("<u5<u2<u4", 3500),
+        ])];
+        let options = PairOptions {
+            pvalue: true,
+            fdr: true,
+            ..PRINT_PAIRS
         };
-        inner_segment.segid = path.to_string();
-        out[i] = inner_segment;
+        let mut out = Vec::new();
+        let report = output_repeat_lines(&mut out, &group, &options, &RciOptions::default(), 3);
+        let pair = |cov| report.pairs.iter().find(|p| p.cov_1 == cov).unwrap();
+        let (balanced, skewed) = (pair(2510), pair(3500));
+        assert!(balanced.pvalue.unwrap() > 0.5);
+        assert!(skewed.pvalue.unwrap() < 1e-12);
+        // with two tests, the smaller p-value is doubled and the larger kept
+        assert_eq!(balanced.qvalue, balanced.pvalue);
+        assert_eq!(skewed.qvalue, Some(skewed.pvalue.unwrap() * 2.0));
 
-        // reset the inner segment
-        inner_segment = Segment::default();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("path_1\tcov_1\tpath_2\tcov_2\trecomb_score\tpvalue\tqvalue\n"));
+        let row = out.lines().find(|l| l.starts_with(">u1>u2>u3")).unwrap();
+        assert!(row.ends_with("\t7.882e-1\t7.882e-1"), "{}", row);
+
+        let report = output_repeat_lines(
+            &mut io::sink(),
+            &group,
+            &PRINT_PAIRS,
+            &RciOptions::default(),
+            3,
+        );
+        assert!(report
+            .pairs
+            .iter()
+            .all(|p| p.pvalue.is_none() && p.qvalue.is_none()));
     }
 
-    Ok(Path(out))
-}
+    #[test]
+    fn test_path_direction() {
+        // each path and its reverse complement
+        for (path, revcomp) in [
+            (">u1>u2>u3", "<u3<u2<u1"),
+            ("<u28<u25>u27", "<u27>u25>u28"),
+            (">u28<u25>u26", "<u26>u25<u28"),
+            (">a>r<b", ">b<r<a"),
+        ] {
+            assert_ne!(path_direction(path), path_direction(revcomp), "{}", path);
+            assert_eq!(path_direction(&canonical_path(path).0), "forward");
+        }
+        assert_eq!(path_direction(">u1>u2>u3"), "forward");
+        assert_eq!(path_direction("<u3<u2<u1"), "reverse");
 
-// tests
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let entry = |p: &str, c| (("u2".to_string(), string_to_path(p.into()).unwrap()), c);
+        let group = [Paths::new(vec![
+            entry(">u1>u2>u3", 7),
+            entry("<u3<u2<u1", 3),
+        ])];
+        let options = PairOptions {
+            direction: true,
+            ..PRINT_PAIRS
+        };
+        let mut out = Vec::new();
+        let report = output_repeat_lines(&mut out, &group, &options, &RciOptions::default(), 3);
+        assert_eq!(report.pairs[0].direction, Some("forward"));
+        assert!(String::from_utf8(out).unwrap().starts_with(
+            "path_1\tcov_1\tpath_2\tcov_2\trecomb_score\tdirection\n\
+             >u1>u2>u3\t7\t<u3<u2<u1\t3\t0.600\tforward\n"
+        ));
+    }
 
     #[test]
-    fn test_path_parse() {
-        let s = "<u28<u25>u27";
-        let path = string_to_path(s.to_string()).unwrap();
-        assert_eq!(path.from().segid, "u28");
-        assert_eq!(path.from().orientation, Orientation::Backward);
-        assert_eq!(path.repeat().segid, "u25");
-        assert_eq!(path.repeat().orientation, Orientation::Backward);
-        assert_eq!(path.to().segid, "u27");
-        assert_eq!(path.to().orientation, Orientation::Forward);
+    fn test_difference_positions() {
+        assert_eq!(difference_positions(":15*ag:24", 0), Some(vec![15]));
+        // insertions don't consume path bases, deletions do
+        assert_eq!(
+            difference_positions(":3+ac:2-gg:4*ct", 10),
+            Some(vec![13, 15, 21])
+        );
+        assert_eq!(difference_positions("=ACGT*ag=AC", 0), Some(vec![4]));
+        // ds brackets around uncertain indels
+        assert_eq!(difference_positions(":5+[ac]:2", 0), Some(vec![5]));
+        assert_eq!(difference_positions(":40", 0), Some(vec![]));
+        assert_eq!(difference_positions(":x", 0), None);
+        // a splice, and truncated ones
+        assert_eq!(difference_positions(":2~gt10ag*ac", 0), Some(vec![12]));
+        assert_eq!(difference_positions(":2~a", 0), None);
+        assert_eq!(difference_positions(":2~", 0), None);
+        assert_eq!(difference_positions(":2~gt10a", 0), None);
     }
 
     #[test]
-    fn test_is_reverse() {
-        let s = "<u28<u25>u27";
-        let path = string_to_path(s.to_string()).unwrap();
-        let s2 = "<u27>u25>u28";
-        let path2 = string_to_path(s2.to_string()).unwrap();
-        assert!(path.is_reverse(&path2));
+    fn test_breakpoints() {
+        let sizes: HashMap<Vec<u8>, usize> = [("a", 10), ("r", 20), ("b", 10)]
+            .into_iter()
+            .map(|(id, size)| (id.as_bytes().to_vec(), size))
+            .collect();
+        let tagged = |path: &str, cs: &str| {
+            gaf_line_with_length(path, 40).replace('\n', &format!("\tcs:Z:{}\n", cs))
+        };
+        let gaf = [
+            // 5 bases into r
+            tagged(">a>r>b", ":15*ag:24"),
+            // 2 bases into r read backwards, 17 on its forward strand
+            tagged("<b<r<a", ":12-ac:26"),
+            // a difference in a flank only, and no tag at all
+            tagged("<b<r<a", ":4*ct:35"),
+            gaf_line_with_length("<b<r<a", 40),
+        ]
+        .concat();
+        let nodes = vec!["r".to_string()];
+        let count_options = CountOptions {
+            breakpoints: Some(&sizes),
+            ..Default::default()
+        };
+        let counts = count_paths(gaf.as_bytes(), &nodes, &count_options).unwrap();
+        let support = |path: &str| counts.support[&("r".to_string(), path.to_string())];
+        assert_eq!(support(">a>r>b").breakpoint_offsets, 5);
+        assert_eq!(support(">a>r>b").breakpoint_reads, 1);
+        assert_eq!(support("<b<r<a").breakpoint_offsets, 17);
+        assert_eq!(support("<b<r<a").breakpoint_reads, 1);
+        assert_eq!(support("<b<r<a").count, 3);
+
+        let groups =
+            Paths::from_vec(path_counts(&counts.support).iter().collect()).split_into_repeats();
+        let options = PairOptions {
+            breakpoints: Some(&counts.support),
+            ..PRINT_PAIRS
+        };
+        let mut out = Vec::new();
+        let report = output_repeat_lines(&mut out, &groups, &options, &RciOptions::default(), 3);
+        assert_eq!(report.pairs[0].breakpoint, Some(Some(11)));
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("path_1\tcov_1\tpath_2\tcov_2\trecomb_score\tbreakpoint\n"));
+        assert!(out.lines().nth(1).unwrap().ends_with("\t11"));
+
+        // without tags
+        let counts = count_paths(gaf_line(">a>r>b").as_bytes(), &nodes, &count_options).unwrap();
+        assert_eq!(mean_breakpoint(counts.support.values()), None);
     }
 
     #[test]
-    fn test_is_reverse_2() {
-        let s = ">u27>u29<u30";
-        let s2 = "<u30<u29<u27";
+    fn test_merge_shared_paths() {
+        let group = |repeat: &str, counts: &[(&str, u64)]| {
+            Paths::new(
+                counts
+                    .iter()
+                    .map(|(p, c)| {
+                        (
+                            (repeat.to_string(), string_to_path(p.to_string()).unwrap()),
+                            *c,
+                        )
+                    })
+                    .collect(),
+            )
+        };
+        // with --multi-focal, reads over the adjacent repeats u2 and u3 are seen by both
+        let groups = vec![
+            group("u2", &[(">u1>u2>u3", 10), ("<u3<u2<u1", 8)]),
+            group(
+                "u3",
+                &[(">u1>u2>u3", 10), ("<u3<u2<u1", 8), (">u2>u3>u4", 1)],
+            ),
+            group("u5", &[(">u4>u5>u6", 4)]),
+        ];
+        assert!((path_set_jaccard(&groups[0], &groups[1]) - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(path_set_jaccard(&groups[0], &groups[2]), 0.0);
 
-        let path = string_to_path(s.to_string()).unwrap();
-        let path2 = string_to_path(s2.to_string()).unwrap();
+        let (records, rci) = output_family_lines(&mut io::sink(), &groups, 0.5, None, 3);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].family, "u2+u3");
+        assert_eq!(records[0].repeat_count, 2);
+        assert_eq!(records[0].path_count, 3);
+        let h: f64 = [10.0, 8.0, 1.0]
+            .iter()
+            .map(|c: &f64| -(c / 19.0) * (c / 19.0).log2())
+            .sum();
+        assert!((records[0].entropy - h).abs() < 1e-9);
+        // the pair seen by both members counts once
+        let expected = 2.0 * 8.0 / 18.0 * 3f64.log2();
+        assert!((records[0].rci - expected).abs() < 1e-9);
+        assert!((rci - expected).abs() < 1e-9);
+        assert_eq!(records[1].family, "u5");
+        assert_eq!(records[1].rci, 0.0);
 
-        assert!(!path.is_reverse(&path2));
+        // above the overlap, every repeat is its own family
+        let (records, _) = output_family_lines(&mut io::sink(), &groups, 0.7, None, 3);
+        assert_eq!(records.len(), 3);
     }
 
-    // test split into repeats
-    // u25	132	<u28<u25>u27 (reverse)
-    // u25	126	<u27>u25<u28 (forward)
-    // u25	126	<u27>u25>u28 (forward)
-    // u25	120	>u28<u25>u26 (reverse)
-    // u25	119	>u28<u25>u27 (reverse)
-    // u25	115	<u26>u25<u28 (forward)
-    // u25	107	<u28<u25>u26 (reverse)
-    // u25	101	<u26>u25>u28 (forward)
     #[test]
-    fn test_split_repeats() {
-        let paths = Paths::from_vec(vec![
-            (&("u25".into(), "<u28<u25>u27".into()), &132),
-            (&("u25".into(), "<u27>u25<u28".into()), &126),
-            (&("u25".into(), "<u27>u25>u28".into()), &126),
-            (&("u25".into(), ">u28<u25>u26".into()), &120),
-            (&("u25".into(), ">u28<u25>u27".into()), &119),
-            (&("u25".into(), "<u26>u25<u28".into()), &115),
-            (&("u25".into(), "<u28<u25>u26".into()), &107),
-            (&("u25".into(), "<u26>u25>u28".into()), &101),
-        ]);
+    fn test_multiway_balance() {
+        let group = |counts: &[(&str, u64)]| {
+            Paths::new(
+                counts
+                    .iter()
+                    .map(|(p, c)| {
+                        (
+                            ("u2".to_string(), string_to_path(p.to_string()).unwrap()),
+                            *c,
+                        )
+                    })
+                    .collect(),
+            )
+        };
 
-        let split = paths.split_into_repeats();
+        // three balanced paths, none of them reverse complements of each other
+        let balanced = group(&[(">u1>u2>u3", 10), (">u1>u2>u4", 10), (">u5>u2>u3", 10)]);
+        assert!((multiway_balance(&balanced).unwrap() - 1.0).abs() < 1e-9);
+        let report = output_repeat_lines(
+            &mut io::sink(),
+            std::slice::from_ref(&balanced),
+            &PRINT_PAIRS,
+            &RciOptions::default(),
+            3,
+        );
+        assert!(report.pairs.is_empty());
 
-        assert!(split.len() == 1);
+        let skewed = group(&[(">u1>u2>u3", 98), (">u1>u2>u4", 1), (">u5>u2>u3", 1)]);
+        assert!(multiway_balance(&skewed).unwrap() < 0.2);
+        assert_eq!(multiway_balance(&group(&[(">u1>u2>u3", 5)])), Some(0.0));
+
+        let mut out = Vec::new();
+        let records = output_multiway_lines(&mut out, &[balanced], 3);
+        assert_eq!(records[0].path_count, 3);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\nrepeat_node\tpath_count\tmultiway_balance\nu2\t3\t1.000\n\nMean multiway balance: 1.000\n"
+        );
     }
 
     #[test]
-    fn test_split_repeats_multiple() {
-        // +u30-u29-u26 -
-        // -u30-u29-u26 -
-        // +u26+u29-u30 -
-        // -u30-u29-u27 -
-        // +u27+u29+u30 -
-        // +u30-u29-u27 -
-        // +u26+u29+u30 -
-        let paths = Paths::from_vec(vec![
-            (&("u25".into(), "<u28<u25>u27".into()), &132),
-            (&("u25".into(), "<u27>u25<u28".into()), &126),
-            (&("u25".into(), "<u27>u25>u28".into()), &126),
-            (&("u25".into(), ">u28<u25>u26".into()), &120),
-            (&("u25".into(), ">u28<u25>u27".into()), &119),
-            (&("u25".into(), "<u26>u25<u28".into()), &115),
-            (&("u25".into(), "<u28<u25>u26".into()), &107),
-            (&("u25".into(), "<u26>u25>u28".into()), &101),
-            (&("u29".into(), ">u27>u29<u30".into()), &87),
-            (&("u29".into(), ">u30<u29<u26".into()), &67),
-            (&("u29".into(), "<u30<u29<u26".into()), &66),
-            (&("u29".into(), ">u26>u29<u30".into()), &65),
-            (&("u29".into(), "<u30<u29<u27".into()), &63),
-            (&("u29".into(), ">u27>u29>u30".into()), &61),
-            (&("u29".into(), ">u30<u29<u27".into()), &57),
-            (&("u29".into(), ">u26>u29>u30".into()), &55),
-        ]);
+    fn test_show_unsupported() {
+        let mut counts = HashMap::new();
+        counts.insert(("u2".to_string(), ">u1>u2>u3".to_string()), 3);
+        counts.insert(("u2".to_string(), "<u3<u2<u1".to_string()), 3);
+        let paths = Paths::from_vec(counts.iter().collect()).split_into_repeats();
+        let nodes = ["u9".to_string(), "u2".to_string(), "u1".to_string()];
 
-        let split = paths.split_into_repeats();
+        // by default the detected repeats without paths are left out
+        let (_, _, rows) =
+            output_entropy_lines(&mut io::sink(), &paths, &EntropyOptions::default(), 3).unwrap();
+        assert_eq!(rows.len(), 1);
 
-        assert!(split.len() == 2);
-        assert_eq!(split[0].paths.len(), 8);
-        assert_eq!(split[1].paths.len(), 8);
+        let mut out = Vec::new();
+        let (mean, total, rows) = output_entropy_lines(
+            &mut out,
+            &paths,
+            &EntropyOptions {
+                unsupported: Some(&nodes),
+                ..Default::default()
+            },
+            3,
+        )
+        .unwrap();
+        let names: Vec<_> = rows.iter().map(|r| r.repeat_node.as_str()).collect();
+        assert_eq!(names, ["u1", "u2", "u9"]);
+        assert_eq!((rows[2].path_count, rows[2].entropy), (0, None));
+        // unsupported repeats don't lower the mean
+        assert_eq!((mean, total), (1.0, 1.0));
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(
+            "\nu1\t0\tNA\t0\t0\tNA\tNA\nu2\t2\t1.000\t3\t3\t<u3<u2<u1\t0.500\nu9\t0\tNA\t0\t0\tNA\tNA\n"
+        ));
     }
 
     #[test]
-    fn test_extract_middle_segment_valid() {
-        let p = "<u27>u25<u28";
-        assert_eq!(extract_middle_segment(p), Some("u25".to_string()));
+    fn test_path_profiles_are_deterministic() {
+        let profile = |rows: &[(&str, &str, u64)]| {
+            let counts: HashMap<PathKey, u64> = rows
+                .iter()
+                .map(|(node, path, count)| ((node.to_string(), path.to_string()), *count))
+                .collect();
+            let paths = Paths::from_vec(counts.iter().collect()).split_into_repeats();
+            let mut out = Vec::new();
+            write_path_profiles(&paths, &mut out).unwrap();
+            out
+        };
+
+        // the same usage, listed in a different order
+        let first = profile(&[
+            ("u2", ">u1>u2>u3", 5),
+            ("u2", "<u3<u2<u1", 4),
+            ("u5", ">u4>u5>u6", 2),
+        ]);
+        let second = profile(&[
+            ("u5", ">u4>u5>u6", 2),
+            ("u2", "<u3<u2<u1", 4),
+            ("u2", ">u1>u2>u3", 5),
+        ]);
+        assert_eq!(first, second);
+        assert_eq!(
+            String::from_utf8(first).unwrap(),
+            "repeat_node\tprofile\nu2\t<u3<u2<u1=4,>u1>u2>u3=5\nu5\t>u4>u5>u6=2\n"
+        );
+
+        // any change in a count changes the profile
+        let changed = profile(&[("u2", ">u1>u2>u3", 5), ("u2", "<u3<u2<u1", 3)]);
+        assert_eq!(
+            String::from_utf8(changed).unwrap(),
+            "repeat_node\tprofile\nu2\t<u3<u2<u1=3,>u1>u2>u3=5\n"
+        );
     }
 
     #[test]
-    fn test_extract_middle_segment_invalid() {
-        let p = ">u27<u25"; // Only two segments
-        assert_eq!(extract_middle_segment(p), None);
+    fn test_counts_beyond_i32() {
+        let big = 3_000_000_000u64; // > i32::MAX
+        let mut counts = HashMap::new();
+        counts.insert(("u2".to_string(), ">u1>u2>u3".to_string()), big);
+        counts.insert(("u2".to_string(), "<u3<u2<u1".to_string()), big);
+
+        let mut dump = Vec::new();
+        write_counts(&counts, &mut dump).unwrap();
+        assert_eq!(
+            read_counts(dump.as_slice(), PathNotation::Arrow).unwrap(),
+            counts
+        );
+
+        let paths = Paths::from_vec(counts.iter().collect()).split_into_repeats();
+        let report = output_repeat_lines(
+            &mut io::sink(),
+            &paths,
+            &PRINT_PAIRS,
+            &RciOptions::default(),
+            3,
+        );
+        assert_eq!(report.pairs[0].cov_1, big);
+        assert_eq!(report.pairs[0].recomb_score, 1.0);
+        let (mean_entropy, _, _) =
+            output_entropy_lines(&mut io::sink(), &paths, &EntropyOptions::default(), 3).unwrap();
+        assert!((mean_entropy - 1.0).abs() < 1e-9);
     }
 
     #[test]
-    fn test_extract_repeat_info_valid_pair() {
-        let (id, count) = extract_repeat_info("<u27>u25<u28", ">u26<u25>u27");
-        assert_eq!(id, Some("u25".to_string()));
-        assert_eq!(count, 2);
+    fn test_read_counts_rejects_malformed() {
+        let bad_header = "node\tpath\tcount\n";
+        assert!(read_counts(bad_header.as_bytes(), PathNotation::Arrow).is_err());
+
+        let err = read_counts(
+            "repeat_node\tpath\tcount\nu2\t>u1>u2>u3\n".as_bytes(),
+            PathNotation::Arrow,
+        )
+        .unwrap_err()
+        .to_string();
+        assert_eq!(
+            err,
+            "line 2: expected 3 columns (repeat_node, path, count), found 2"
+        );
+
+        for count in ["-1", "1.5", "NaN", "abc"] {
+            let table = format!("repeat_node\tpath\tcount\nu2\t>u1>u2>u3\t{}\n", count);
+            let err = read_counts(table.as_bytes(), PathNotation::Arrow)
+                .unwrap_err()
+                .to_string();
+            assert_eq!(err, format!("line 2: invalid count \"{}\"", count));
+        }
+
+        let bad_path = "repeat_node\tpath\tcount\nu2\tu1,u2,u3\t1\n";
+        assert!(read_counts(bad_path.as_bytes(), PathNotation::Arrow).is_err());
     }
 
     #[test]
-    fn test_extract_repeat_info_mismatch() {
-        let (id, count) = extract_repeat_info("<u27>u25<u28", ">u26<u24>u27");
-        assert_eq!(id, None);
-        assert_eq!(count, 0);
+    fn test_write_split_files() {
+        let gaf = [
+            gaf_line(">u1>u2>u3"),
+            gaf_line("<u3<u2<u1"),
+            gaf_line(">u4>u5/x>u6"),
+        ]
+        .concat();
+        let nodes = vec!["u2".to_string(), "u5/x".to_string()];
+        let counts = path_counts(
+            &count_paths(gaf.as_bytes(), &nodes, &CountOptions::default())
+                .unwrap()
+                .support,
+        );
+        let paths = Paths::from_vec(counts.iter().collect()).split_into_repeats();
+
+        let dir = std::env::temp_dir().join(format!("gfa_recomb_split_{}", std::process::id()));
+        write_split_files(
+            &dir,
+            &paths,
+            &PairOptions::default(),
+            false,
+            &RciOptions::default(),
+            None,
+            3,
+        )
+        .unwrap();
+
+        let mut files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["u2.tsv", "u5_x.tsv"]);
+
+        let u2 = std::fs::read_to_string(dir.join("u2.tsv")).unwrap();
+        assert!(u2.starts_with("path\tcount\n<u3<u2<u1\t1\n>u1>u2>u3\t1\n"));
+        assert!(
+            u2.contains("<u3<u2<u1\t1\t>u1>u2>u3\t1\t1.000")
+                || u2.contains(">u1>u2>u3\t1\t<u3<u2<u1\t1\t1.000")
+        );
+        assert!(u2.contains("\nu2\t2\t1.000\t1\t1\t<u3<u2<u1\t0.500\n"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
-    fn test_compute_rci_balanced() {
-        let revcomps = vec![
-            // repeat node: u66
-            (
-                "<u67<u66>u65".to_string(),
-                100,
-                "<u65>u66>u67".to_string(),
-                100,
-            ),
-            // repeat node: u69
-            (
-                ">u65<u69<u68".to_string(),
-                80,
-                ">u68>u69<u65".to_string(),
-                80,
-            ),
-        ];
+    fn test_reference_lines() {
+        let gaf = [
+            gaf_line(">u1>u2>u3"),
+            gaf_line(">u1>u2>u3"),
+            gaf_line(">u4>u2>u3"),
+        ]
+        .concat();
+        let counts = path_counts(
+            &count_paths(
+                gaf.as_bytes(),
+                &["u2".to_string()],
+                &CountOptions::default(),
+            )
+            .unwrap()
+            .support,
+        );
+        let paths = Paths::from_vec(counts.iter().collect()).split_into_repeats();
+        // the P line traverses u1+,u2+,u3+ (seen here from the other strand)
+        let reference = HashSet::from(["<u3<u2<u1".to_string(), ">u1>u2>u3".to_string()]);
 
-        let rci = compute_rci(&revcomps);
-        eprintln!("RCI: {rci}");
-        assert!(rci > 0.0);
-        assert!((rci - 1.0).abs() < 0.1);
+        let mut out = Vec::new();
+        let summary = output_reference_lines(&mut out, &paths, &reference);
 
-        let revcomps = vec![
-            // Repeat node u66 (2 pairs → 4 paths total)
-            (
-                "<u67<u66>u65".to_string(),
-                100,
-                "<u65>u66>u67".to_string(),
-                100,
-            ),
-            (
-                "<u68<u66>u64".to_string(),
-                90,
-                "<u64>u66>u68".to_string(),
-                90,
-            ),
-            // Repeat node u69 (2 pairs → 4 paths total)
-            (
-                ">u65<u69<u68".to_string(),
-                80,
-                ">u68>u69<u65".to_string(),
-                80,
-            ),
-            (
-                ">u64<u69<u67".to_string(),
-                70,
-                ">u67>u69<u64".to_string(),
-                70,
-            ),
-        ];
+        assert_eq!(
+            summary,
+            vec![(
+                "u2".to_string(),
+                ReferenceSupport {
+                    ref_paths: 1,
+                    ref_reads: 2,
+                    recomb_paths: 1,
+                    recomb_reads: 1,
+                }
+            )]
+        );
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("u2\t>u1>u2>u3\t2\treference\n"));
+        assert!(out.contains("u2\t>u4>u2>u3\t1\trecombinant\n"));
+    }
 
-        let rci = compute_rci(&revcomps);
-        eprintln!("RCI: {rci}");
-        assert!(rci > 0.0);
-        assert!((rci - 2.0).abs() < 0.1); // 2 repeat nodes × log2(2) × score ~ 1.0
+    #[test]
+    fn test_length_weighted_entropy() {
+        // two paths with one read each, but one read is three times longer
+        let gaf = [
+            gaf_line_with_length(">u1>u2>u3", 300),
+            gaf_line_with_length(">u4>u2>u3", 100),
+        ]
+        .concat();
+        let support = count_paths(
+            gaf.as_bytes(),
+            &["u2".to_string()],
+            &CountOptions::default(),
+        )
+        .unwrap()
+        .support;
+        let counts = path_counts(&support);
+        let lengths: HashMap<PathKey, u64> =
+            support.iter().map(|(k, s)| (k.clone(), s.length)).collect();
+        let paths = Paths::from_vec(counts.iter().collect()).split_into_repeats();
+
+        let (by_count, _, _) =
+            output_entropy_lines(&mut io::sink(), &paths, &EntropyOptions::default(), 3).unwrap();
+        let (by_length, _, _) = output_entropy_lines(
+            &mut io::sink(),
+            &paths,
+            &EntropyOptions {
+                length_weights: Some(&lengths),
+                ..Default::default()
+            },
+            3,
+        )
+        .unwrap();
+
+        assert!((by_count - 1.0).abs() < 1e-9);
+        // -(0.75 * log2(0.75) + 0.25 * log2(0.25))
+        assert!((by_length - 0.811).abs() < 1e-3);
     }
 
     #[test]
-    fn test_compute_rci_unbalanced() {
-        let revcomps = vec![
-            (
-                "<u27>u25<u28".to_string(),
-                190,
-                ">u26<u25>u27".to_string(),
-                10,
-            ), // very unbalanced
-        ];
+    fn test_zero_weight_paths() {
+        // the second path's read has no length, so it carries no weight
+        let gaf = [
+            gaf_line_with_length(">u1>u2>u3", 300),
+            gaf_line_with_length(">u4>u2>u3", 0),
+        ]
+        .concat();
+        let support = count_paths(
+            gaf.as_bytes(),
+            &["u2".to_string()],
+            &CountOptions::default(),
+        )
+        .unwrap()
+        .support;
+        let counts = path_counts(&support);
+        let lengths: HashMap<PathKey, u64> =
+            support.iter().map(|(k, s)| (k.clone(), s.length)).collect();
+        let paths = Paths::from_vec(counts.iter().collect()).split_into_repeats();
 
-        let rci = compute_rci(&revcomps);
-        assert!(rci < 0.5);
+        let options = EntropyOptions {
+            length_weights: Some(&lengths),
+            ..Default::default()
+        };
+        let (_, _, rows) = output_entropy_lines(&mut io::sink(), &paths, &options, 3).unwrap();
+        assert_eq!(rows[0].path_count, 1);
+        assert_eq!(rows[0].entropy, Some(0.0));
+
+        // by read count, both paths are supported
+        let (_, _, rows) =
+            output_entropy_lines(&mut io::sink(), &paths, &EntropyOptions::default(), 3).unwrap();
+        assert_eq!(rows[0].path_count, 2);
     }
 
     #[test]
-    fn test_compute_rci_empty() {
-        let revcomps = vec![];
-        let rci = compute_rci(&revcomps);
-        assert_eq!(rci, 0.0);
+    fn test_check_namespace() {
+        let sizes: HashMap<Vec<u8>, usize> = [(b"u2".to_vec(), 10)].into_iter().collect();
+        // only one of four path centres is in the GFA
+        let gaf = [
+            gaf_line(">u1>u2>u3"),
+            gaf_line(">a1>a2>a3"),
+            gaf_line(">a4>a5>a6"),
+            gaf_line(">a7>a8>a9"),
+        ]
+        .concat();
+        let err = check_namespace(gaf.as_bytes(), GafDialect::Standard, &sizes, 0.5).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("3 of 4 sampled GAF path centre(s) (75.0%)"));
+        check_namespace(gaf.as_bytes(), GafDialect::Standard, &sizes, 0.75).unwrap();
     }
 
     #[test]
-    fn test_compute_path_entropy_single_node() {
-        let paths = Paths::new(vec![
-            (
-                ("u66".into(), string_to_path("<u67<u66>u65".into()).unwrap()),
-                100,
-            ),
-            (
-                ("u66".into(), string_to_path("<u65>u66>u67".into()).unwrap()),
-                100,
-            ),
-            (
-                ("u66".into(), string_to_path("<u68<u66>u64".into()).unwrap()),
-                50,
-            ),
-        ]);
+    fn test_flank_pair_entropy() {
+        // three orientations of {u1, u3} and one path to u4
+        let counts: HashMap<PathKey, u64> = [
+            (">u1>u2>u3", 3),
+            ("<u3<u2<u1", 2),
+            (">u1<u2>u3", 1),
+            (">u1>u2>u4", 2),
+        ]
+        .into_iter()
+        .map(|(path, count)| (("u2".to_string(), path.to_string()), count))
+        .collect();
+        let paths = Paths::from_vec(counts.iter().collect()).split_into_repeats();
 
-        let (mean_entropy, _total_entropy, details) = output_entropy_lines(&[paths], true).unwrap();
+        let (_, _, by_path) =
+            output_entropy_lines(&mut io::sink(), &paths, &EntropyOptions::default(), 3).unwrap();
+        assert_eq!(by_path[0].path_count, 4);
 
-        assert!((mean_entropy > 1.0) && (mean_entropy < 2.0));
-        assert_eq!(details.len(), 1);
-        assert_eq!(details[0].0, "u66");
-        assert_eq!(details[0].1, 3);
-        assert!(details[0].2 > 1.0); // entropy should be positive
+        let options = EntropyOptions {
+            flank_pairs: true,
+            ..Default::default()
+        };
+        let (_, _, rows) = output_entropy_lines(&mut io::sink(), &paths, &options, 3).unwrap();
+        assert_eq!(rows[0].path_count, 2);
+        // -(0.75 * log2(0.75) + 0.25 * log2(0.25))
+        assert!((rows[0].entropy.unwrap() - 0.811).abs() < 1e-3);
     }
 
     #[test]
-    fn test_split_into_repeats_merges_by_repeat_id() {
-        let paths = Paths::new(vec![
-            (
-                ("u66".into(), string_to_path("<u67<u66>u65".into()).unwrap()),
-                100,
-            ),
-            (
-                ("u69".into(), string_to_path(">u65<u69<u67".into()).unwrap()),
-                175,
-            ),
-            (
-                ("u66".into(), string_to_path("<u65>u66>u67".into()).unwrap()),
-                180,
-            ),
-            (
-                ("u69".into(), string_to_path(">u67>u69<u65".into()).unwrap()),
-                171,
-            ),
-        ]);
+    fn test_focal_mismatch_warning() {
+        let gaf = [gaf_line(">a1>a2>a3"), gaf_line(">a4>a2>a3")].concat();
+        let nodes = vec!["u2".to_string(), "u5".to_string()];
+        let support = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default())
+            .unwrap()
+            .support;
 
-        let merged = paths.split_into_repeats();
+        let warning = focal_mismatch_warning(&nodes, &support).unwrap();
+        assert!(warning.contains("2 focal nodes searched, 0 matched"));
 
-        assert_eq!(merged.len(), 2);
-        let mut ids: Vec<String> = merged.iter().map(|p| p.paths[0].0 .0.clone()).collect();
-        ids.sort();
-        assert_eq!(ids, vec!["u66", "u69"]);
+        let nodes = vec!["a2".to_string(), "u5".to_string()];
+        let support = count_paths(gaf.as_bytes(), &nodes, &CountOptions::default())
+            .unwrap()
+            .support;
+        assert_eq!(focal_mismatch_warning(&nodes, &support), None);
     }
 }
@@ -1,19 +1,77 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+    path::PathBuf,
+    time::Instant,
+};
 
 use anyhow::{Context, Result};
+use bstr::{io::BufReadExt, ByteSlice};
 use clap::ArgMatches;
 use gfa::{
-    gfa::{Orientation, GFA},
-    parser::GFAParser,
+    gfa::{Line, Link, Orientation, SegmentId, GFA},
+    optfields::{OptField, OptFieldVal, OptFields},
+    parser::{error::ParserTolerance, GFAParser, GFAParserBuilder, GFAResult},
 };
+use regex::bytes::Regex;
+use serde::Serialize;
+
+use crate::cache;
+use crate::manifest::Manifest;
+use crate::seq::Composition;
+use crate::timings::Timings;
+
+/// A parsed GFA, keeping optional fields (tags) such as `SR:i:` on links.
+pub type Gfa = GFA<Vec<u8>, Vec<OptField>>;
+
+/// A link of a [`Gfa`].
+pub type GfaLink = Link<Vec<u8>, Vec<OptField>>;
+
+/// Adjacency of each segment, keyed by segment name and then by the orientation
+/// of the link end on that segment.
+pub type EdgeMap = HashMap<Vec<u8>, HashMap<Orientation, Vec<(Vec<u8>, Orientation)>>>;
+
+/// A UTF-8 byte order mark, which some Windows editors write at the start of a file.
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Strip what files written on Windows may add to a line of a GFA or GAF: a UTF-8
+/// byte order mark at the start of the `first` line, and a trailing `\r`, which
+/// `byte_lines` only removes before a `\n`, not on a last line without one.
+pub fn clean_line(line: &[u8], first: bool) -> &[u8] {
+    let line = match line.strip_prefix(BOM) {
+        Some(rest) if first => rest,
+        _ => line,
+    };
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Parse the GFA at `path` as `GFAParser::parse_file` does, but with every line
+/// passed through [`clean_line`], so a byte order mark doesn't make the first line
+/// unparseable (and silently skipped).
+fn parse_gfa_file<N: SegmentId, T: OptFields>(
+    parser: &GFAParser<N, T>,
+    path: &std::path::Path,
+) -> GFAResult<GFA<N, T>> {
+    let file = std::fs::File::open(path)?;
+    let mut gfa = GFA::new();
+    for (i, line) in std::io::BufReader::new(file).byte_lines().enumerate() {
+        let line = line?;
+        match parser.parse_gfa_line(clean_line(&line, i == 0)) {
+            Ok(parsed) => gfa.insert_line(parsed),
+            Err(err) if err.can_safely_continue(&ParserTolerance::default()) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(gfa)
+}
 
 /// Load a GFA file from the provided path.
-pub fn load_gfa<P>(path: P) -> Result<GFA<Vec<u8>, ()>>
+pub fn load_gfa<P>(path: P) -> Result<Gfa>
 where
     P: AsRef<std::path::Path>,
 {
     let parser = GFAParser::new();
-    let gfa = parser.parse_file(path.as_ref()).with_context(|| {
+    let gfa = parse_gfa_file(&parser, path.as_ref()).with_context(|| {
         format!(
             "Failed to parse GFA from path: {:?}",
             path.as_ref().as_os_str()
@@ -22,108 +80,2138 @@ where
     Ok(gfa)
 }
 
-pub fn nodes(args: ArgMatches, store: bool) -> Result<Option<Vec<String>>> {
-    // args
-    let gfa_file = args.get_one::<PathBuf>("GFA").expect("GFA required");
-    let gfa = load_gfa(gfa_file).context("Failed to load GFA file")?;
+/// Load only the `S` lines of a GFA and return each segment's sequence by name.
+pub fn load_sequences<P>(path: P) -> Result<HashMap<String, Vec<u8>>>
+where
+    P: AsRef<std::path::Path>,
+{
+    let mut builder = GFAParserBuilder::none();
+    builder.segments(true);
+    let parser: GFAParser<Vec<u8>, ()> = builder.build_bstr_id();
+    let gfa = parse_gfa_file(&parser, path.as_ref()).with_context(|| {
+        format!(
+            "Failed to parse GFA segments from path: {:?}",
+            path.as_ref().as_os_str()
+        )
+    })?;
+    Ok(gfa
+        .segments
+        .into_iter()
+        .map(|s| (s.name.to_str_lossy().into_owned(), s.sequence))
+        .collect())
+}
 
-    let repeat_node_size_limit = *args.get_one::<usize>("repeat").unwrap();
-    let neighboring_node_minimum = *args.get_one::<usize>("neighbor").unwrap();
-    let in_out_threshold = *args.get_one::<usize>("inout").unwrap();
+/// Load only the `P` lines of a GFA and return their 3-segment windows
+/// (see [`reference_windows`]).
+pub fn load_reference_windows<P>(path: P) -> Result<HashSet<String>>
+where
+    P: AsRef<std::path::Path>,
+{
+    let mut builder = GFAParserBuilder::none();
+    builder.paths(true);
+    let parser: GFAParser<Vec<u8>, ()> = builder.build_bstr_id();
+    let gfa = parse_gfa_file(&parser, path.as_ref()).with_context(|| {
+        format!(
+            "Failed to parse GFA paths from path: {:?}",
+            path.as_ref().as_os_str()
+        )
+    })?;
+    Ok(reference_windows(&gfa))
+}
 
-    // main
-    let segment_sizes: HashMap<Vec<u8>, usize> = gfa
+/// Every 3-segment window along the GFA's `P` lines, on both strands, in GAF path
+/// notation. For a path `a+,b+,c-` this is `>a>b<c` and its reverse `>c<b<a`.
+pub fn reference_windows<T: OptFields>(gfa: &GFA<Vec<u8>, T>) -> HashSet<String> {
+    let step = |seg: &[u8], reverse: bool| {
+        let seg = seg.to_str_lossy();
+        if reverse {
+            format!("<{}", seg)
+        } else {
+            format!(">{}", seg)
+        }
+    };
+
+    let mut windows = HashSet::new();
+    for path in &gfa.paths {
+        let steps: Vec<_> = path.iter().collect();
+        for window in steps.windows(3) {
+            windows.insert(
+                window
+                    .iter()
+                    .map(|(seg, o)| step(seg, o.is_reverse()))
+                    .collect(),
+            );
+            windows.insert(
+                window
+                    .iter()
+                    .rev()
+                    .map(|(seg, o)| step(seg, !o.is_reverse()))
+                    .collect(),
+            );
+        }
+    }
+    windows
+}
+
+/// Number of distinct oriented 3-segment paths the graph allows through each of
+/// `ids`, on both strands: `2 * |left| * |right|`, where `left` and `right` are the
+/// oriented segments linked to either end of the forward segment.
+pub fn possible_paths(gfa: &Gfa, ids: &[String]) -> HashMap<String, usize> {
+    // oriented segment (name, is_reverse) -> oriented segments that may follow it
+    type Oriented<'a> = (&'a [u8], bool);
+    let mut next: HashMap<Oriented, HashSet<Oriented>> = HashMap::new();
+    for link in &gfa.links {
+        let from = (link.from_segment.as_slice(), link.from_orient.is_reverse());
+        let to = (link.to_segment.as_slice(), link.to_orient.is_reverse());
+        next.entry(from).or_default().insert(to);
+        // the same link read on the other strand
+        next.entry((to.0, !to.1))
+            .or_default()
+            .insert((from.0, !from.1));
+    }
+
+    ids.iter()
+        .map(|id| {
+            let id_bytes = id.as_bytes();
+            let right = next.get(&(id_bytes, false)).map_or(0, |n| n.len());
+            // segments preceding id+ are those following id-
+            let left = next.get(&(id_bytes, true)).map_or(0, |n| n.len());
+            (id.clone(), 2 * left * right)
+        })
+        .collect()
+}
+
+/// Parse `path` and count the possible paths through each of `ids`, see [`possible_paths`].
+pub fn load_possible_paths<P>(path: P, ids: &[String]) -> Result<HashMap<String, usize>>
+where
+    P: AsRef<std::path::Path>,
+{
+    Ok(possible_paths(&load_gfa(path)?, ids))
+}
+
+/// Read segment IDs to exclude from detection, one per line. Blank lines and lines
+/// starting with `#` are ignored.
+pub fn load_exclude_list(path: &std::path::Path) -> Result<HashSet<Vec<u8>>> {
+    let list = std::fs::read(path)
+        .with_context(|| format!("Failed to read exclude list: {:?}", path.as_os_str()))?;
+    Ok(parse_exclude_list(&list))
+}
+
+fn parse_exclude_list(list: &[u8]) -> HashSet<Vec<u8>> {
+    list.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with(b"#"))
+        .map(|line| line.to_vec())
+        .collect()
+}
+
+/// Read segment sizes from a TSV of segment ID and size, one per line (`--sizes`).
+/// Blank lines and lines starting with `#` are ignored.
+pub fn load_sizes(path: &std::path::Path) -> Result<Vec<(Vec<u8>, usize)>> {
+    let table = std::fs::read(path)
+        .with_context(|| format!("Failed to read sizes file: {:?}", path.as_os_str()))?;
+    parse_sizes(&table).with_context(|| format!("Malformed sizes file: {:?}", path.as_os_str()))
+}
+
+fn parse_sizes(table: &[u8]) -> Result<Vec<(Vec<u8>, usize)>> {
+    let mut sizes = Vec::new();
+    for (i, line) in table.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+        let fields: Vec<&[u8]> = line.split_str("\t").collect();
+        let [id, size] = fields[..] else {
+            anyhow::bail!("line {}: expected a segment ID and a size", i + 1);
+        };
+        let size = size
+            .to_str()
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .with_context(|| format!("line {}: invalid size {}", i + 1, size.to_str_lossy()))?;
+        sizes.push((id.to_vec(), size));
+    }
+    Ok(sizes)
+}
+
+/// A minimum for an integer link tag, e.g. `SR:5` to require split-read support
+/// `SR:i:` of at least 5 (`--min-link-tag`). Links without the tag fail.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkTagFilter {
+    pub tag: [u8; 2],
+    pub min: i64,
+}
+
+impl LinkTagFilter {
+    /// Whether the link with optional fields `optional` has the tag, at or above the minimum.
+    pub fn passes(&self, optional: &[OptField]) -> bool {
+        match optional
+            .iter()
+            .find(|f| f.tag == self.tag)
+            .map(|f| &f.value)
+        {
+            Some(OptFieldVal::Int(value)) => *value >= self.min,
+            _ => false,
+        }
+    }
+}
+
+impl std::str::FromStr for LinkTagFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || format!("expected TAG:MIN, e.g. SR:5, found \"{}\"", s);
+        let (tag, min) = s.split_once(':').ok_or_else(invalid)?;
+        let tag: [u8; 2] = tag.as_bytes().try_into().map_err(|_| invalid())?;
+        let min = min.parse().map_err(|_| invalid())?;
+        Ok(Self { tag, min })
+    }
+}
+
+/// The links touching one of `repeats`, in GFA order, and the segments on either
+/// end of them, including the repeats themselves.
+pub fn neighborhood<'a>(gfa: &'a Gfa, repeats: &[String]) -> (HashSet<&'a [u8]>, Vec<&'a GfaLink>) {
+    let repeats: HashSet<&[u8]> = repeats.iter().map(|r| r.as_bytes()).collect();
+    let links: Vec<_> = gfa
+        .links
+        .iter()
+        .filter(|l| {
+            repeats.contains(l.from_segment.as_slice()) || repeats.contains(l.to_segment.as_slice())
+        })
+        .collect();
+    let segments = gfa
         .segments
         .iter()
-        .map(|segment| (segment.name.clone(), segment.sequence.len()))
+        .map(|s| s.name.as_slice())
+        .filter(|name| repeats.contains(name))
+        .chain(
+            links
+                .iter()
+                .flat_map(|l| [l.from_segment.as_slice(), l.to_segment.as_slice()]),
+        )
         .collect();
+    (segments, links)
+}
 
-    let mut edge_map: HashMap<Vec<u8>, HashMap<Orientation, Vec<(Vec<u8>, Orientation)>>> =
-        HashMap::new();
+/// Write the `repeats`, their neighbouring segments and the links touching a repeat
+/// as GFA 1.0, keeping sequences, orientations, overlaps and optional fields.
+pub fn write_sub_gfa<W: Write>(gfa: &Gfa, repeats: &[String], mut out: W) -> Result<()> {
+    let (segments, links) = neighborhood(gfa, repeats);
+    let tags =
+        |optional: &[OptField]| -> String { optional.iter().map(|f| format!("\t{}", f)).collect() };
+    let or_star = |field: &[u8]| {
+        if field.is_empty() {
+            "*".to_string()
+        } else {
+            field.to_str_lossy().into_owned()
+        }
+    };
 
-    for link in &gfa.links {
-        let from = link.from_segment.clone();
-        let to = link.to_segment.clone();
-        let from_orient = link.from_orient;
-        let to_orient = link.to_orient;
+    writeln!(out, "H\tVN:Z:1.0")?;
+    for segment in gfa
+        .segments
+        .iter()
+        .filter(|s| segments.contains(s.name.as_slice()))
+    {
+        writeln!(
+            out,
+            "S\t{}\t{}{}",
+            segment.name.as_bstr(),
+            or_star(&segment.sequence),
+            tags(&segment.optional)
+        )?;
+    }
+    for link in links {
+        writeln!(
+            out,
+            "L\t{}\t{}\t{}\t{}\t{}{}",
+            link.from_segment.as_bstr(),
+            link.from_orient,
+            link.to_segment.as_bstr(),
+            link.to_orient,
+            or_star(&link.overlap),
+            tags(&link.optional)
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
 
-        edge_map
-            .entry(from.clone())
-            .or_default()
-            .entry(from_orient)
-            .or_default()
-            .push((to.clone(), to_orient));
+/// A link as a bidirected edge: the same for the link written from either end,
+/// e.g. `A+ B-` and `B+ A-`.
+fn edge_key(link: &GfaLink) -> (Vec<u8>, Orientation, Vec<u8>, Orientation) {
+    let (from, to) = (link.from_segment.clone(), link.to_segment.clone());
+    // the same edge read from its other end
+    let reverse = (
+        to.clone(),
+        flip(link.to_orient),
+        from.clone(),
+        flip(link.from_orient),
+    );
+    let edge = (from, link.from_orient, to, link.to_orient);
+    edge.min(reverse)
+}
 
-        edge_map
-            .entry(to.clone())
-            .or_default()
-            .entry(to_orient)
-            .or_default()
-            .push((from.clone(), from_orient));
+/// Write the links of `gfa` as a TSV edge list of `from_id`, `from_orient`, `to_id`
+/// and `to_orient`, in GFA order, for graph libraries such as networkx or igraph. A
+/// link listed twice, verbatim or as its reciprocal, is written once, as it is
+/// first listed. With `repeats`, only the links touching one of them are written.
+pub fn write_edge_list<W: Write>(gfa: &Gfa, repeats: Option<&[String]>, mut out: W) -> Result<()> {
+    let links: Vec<&GfaLink> = match repeats {
+        Some(repeats) => neighborhood(gfa, repeats).1,
+        None => gfa.links.iter().collect(),
+    };
+    let mut seen = HashSet::new();
+    writeln!(out, "from_id\tfrom_orient\tto_id\tto_orient")?;
+    for link in links {
+        if !seen.insert(edge_key(link)) {
+            continue;
+        }
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            link.from_segment.as_bstr(),
+            link.from_orient,
+            link.to_segment.as_bstr(),
+            link.to_orient
+        )?;
     }
+    out.flush()?;
+    Ok(())
+}
+
+/// Size and degree thresholds for repeat detection.
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    /// Maximum size of a repeat node.
+    pub repeat: usize,
+    /// Minimum size of each neighbouring node.
+    pub neighbor: usize,
+    /// Minimum in/out degree of a repeat node.
+    pub inout: usize,
+    /// How many hops out segments must pass the neighbour size check.
+    pub depth: usize,
+}
 
+impl Thresholds {
+    pub fn from_args(args: &ArgMatches) -> Self {
+        Self {
+            repeat: *args.get_one::<usize>("repeat").unwrap(),
+            neighbor: *args.get_one::<usize>("neighbor").unwrap(),
+            inout: *args.get_one::<usize>("inout").unwrap(),
+            depth: *args.get_one::<u64>("neighbor-depth").unwrap() as usize,
+        }
+    }
+
+    /// A warning if the thresholds admit segments with fewer than 2 links, which
+    /// are not branch points and so cannot be repeats.
+    pub fn trivial_warning(&self) -> Option<String> {
+        (self.inout == 0).then(|| {
+            "Warning: --inout 0 admits segments with fewer than 2 links (every segment \
+             passing the size checks becomes a candidate). Use --allow-trivial if this is intended."
+                .to_string()
+        })
+    }
+}
+
+/// Segment sizes and adjacency of a GFA, as used for repeat detection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphIndex {
+    /// Segment names and sizes, in GFA order.
+    pub segments: Vec<(Vec<u8>, usize)>,
+    pub segment_sizes: HashMap<Vec<u8>, usize>,
+    pub edge_map: EdgeMap,
+    /// Whether `edge_map` is keyed canonically (see [`GraphIndex::new`]).
+    pub canonical_links: bool,
+}
+
+impl GraphIndex {
+    /// Index `gfa`, failing if two segments share a name. With `warn_duplicates`,
+    /// duplicates are reported on stderr instead and the last segment of each name wins.
+    /// Links failing `link_filter` are left out of the edge map.
+    ///
+    /// A link listed twice, either verbatim or as its reciprocal (`A+ B-` and
+    /// `B+ A-` are the same bidirected edge), is only added to the edge map once, so
+    /// redundant GFAs don't inflate degrees.
+    ///
+    /// By default a link `A+ B+` is stored under `A+` and `B+`, so the same edge
+    /// written from its other end (`B- A-`) is stored under `B-` and `A-` instead.
+    /// With `canonical_links`, every link is stored under the end of each segment
+    /// it leaves from: `A+ B+` under `A+` (to `B+`) and `B-` (to `A-`), exactly
+    /// as `B- A-` is, so both spellings of an edge give the same edge map.
+    pub fn new(
+        gfa: &Gfa,
+        warn_duplicates: bool,
+        link_filter: Option<&LinkTagFilter>,
+        canonical_links: bool,
+    ) -> Result<Self> {
+        let segments: Vec<(Vec<u8>, usize)> = gfa
+            .segments
+            .iter()
+            .map(|segment| (segment.name.clone(), segment.sequence.len()))
+            .collect();
+        Self::from_parts(
+            segments,
+            &gfa.links,
+            warn_duplicates,
+            link_filter,
+            canonical_links,
+        )
+    }
+
+    /// Like [`GraphIndex::new`], but reads the GFA at `path` line by line, keeping
+    /// only segment names and lengths and the links. Sequences are dropped as soon
+    /// as they are measured, so detection on a huge graph doesn't hold them all in
+    /// memory. Only `S` and `L` lines are parsed.
+    pub fn load<P>(
+        path: P,
+        warn_duplicates: bool,
+        link_filter: Option<&LinkTagFilter>,
+        canonical_links: bool,
+    ) -> Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let path = path.as_ref();
+        let mut builder = GFAParserBuilder::none();
+        builder.segments(true).links(true);
+        let parser: GFAParser<Vec<u8>, Vec<OptField>> = builder.build_bstr_id();
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to parse GFA from path: {:?}", path.as_os_str()))?;
+
+        let mut segments = Vec::new();
+        let mut links = Vec::new();
+        for (i, line) in std::io::BufReader::new(file).byte_lines().enumerate() {
+            let line = line?;
+            let line = clean_line(&line, i == 0);
+            if parser.ignore_line(line) {
+                continue;
+            }
+            let parsed = parser.parse_gfa_line(line).with_context(|| {
+                format!("Failed to parse GFA from path: {:?}", path.as_os_str())
+            })?;
+            match parsed {
+                Line::Segment(segment) => segments.push((segment.name, segment.sequence.len())),
+                Line::Link(link) => links.push(link),
+                _ => {}
+            }
+        }
+        Self::from_parts(
+            segments,
+            &links,
+            warn_duplicates,
+            link_filter,
+            canonical_links,
+        )
+    }
+
+    fn from_parts(
+        segments: Vec<(Vec<u8>, usize)>,
+        links: &[GfaLink],
+        warn_duplicates: bool,
+        link_filter: Option<&LinkTagFilter>,
+        canonical_links: bool,
+    ) -> Result<Self> {
+        let mut segment_sizes: HashMap<Vec<u8>, usize> = HashMap::new();
+
+        for (name, size) in &segments {
+            let previous = segment_sizes.insert(name.clone(), *size);
+            if previous.is_some() {
+                let name = name.to_str_lossy();
+                if !warn_duplicates {
+                    anyhow::bail!("Duplicate segment name in GFA: {}", name);
+                }
+                eprintln!("Warning: duplicate segment name in GFA: {}", name);
+            }
+        }
+
+        let mut edge_map: EdgeMap = HashMap::new();
+        let mut seen_edges = HashSet::new();
+        let mut reciprocal = 0;
+
+        for link in links {
+            if link_filter.is_some_and(|filter| !filter.passes(&link.optional)) {
+                continue;
+            }
+            let from = link.from_segment.clone();
+            let to = link.to_segment.clone();
+            let from_orient = link.from_orient;
+            let to_orient = link.to_orient;
+
+            if !seen_edges.insert(edge_key(link)) {
+                reciprocal += 1;
+                continue;
+            }
+
+            edge_map
+                .entry(from.clone())
+                .or_default()
+                .entry(from_orient)
+                .or_default()
+                .push((to.clone(), to_orient));
+
+            // canonically, the edge leaves `to` from its other end, towards `from`
+            // read the other way
+            let (to_key, from_orient) = if canonical_links {
+                (flip(to_orient), flip(from_orient))
+            } else {
+                (to_orient, from_orient)
+            };
+            edge_map
+                .entry(to.clone())
+                .or_default()
+                .entry(to_key)
+                .or_default()
+                .push((from.clone(), from_orient));
+        }
+
+        if reciprocal > 0 {
+            eprintln!(
+                "Note: {} duplicate or reciprocal link(s) in GFA counted once",
+                reciprocal
+            );
+        }
+
+        Ok(Self {
+            segments,
+            segment_sizes,
+            edge_map,
+            canonical_links,
+        })
+    }
+
+    /// Replace the sizes of the segments in `sizes`, returning the IDs that are not
+    /// in the graph, which are skipped.
+    pub fn override_sizes(&mut self, sizes: &[(Vec<u8>, usize)]) -> Vec<Vec<u8>> {
+        let mut unknown = Vec::new();
+        let mut overrides = HashMap::new();
+        for (id, size) in sizes {
+            match self.segment_sizes.get_mut(id) {
+                Some(old) => {
+                    *old = *size;
+                    overrides.insert(id.as_slice(), *size);
+                }
+                None => unknown.push(id.clone()),
+            }
+        }
+        for (id, size) in &mut self.segments {
+            if let Some(&new) = overrides.get(id.as_slice()) {
+                *size = new;
+            }
+        }
+        unknown
+    }
+}
+
+/// The opposite orientation.
+fn flip(orient: Orientation) -> Orientation {
+    match orient {
+        Orientation::Forward => Orientation::Backward,
+        Orientation::Backward => Orientation::Forward,
+    }
+}
+
+/// Find the segments that pass the repeat thresholds, in GFA order.
+///
+/// With `verbose`, the neighbour that disqualified each rejected segment is reported
+/// on stderr.
+pub fn find_candidates(
+    index: &GraphIndex,
+    thresholds: &Thresholds,
+    excluded: &HashSet<Vec<u8>>,
+    verbose: bool,
+) -> Vec<Vec<u8>> {
     let mut repeat_candidates = Vec::new();
 
-    for segment in &gfa.segments {
-        let id = segment.name.clone();
-        let size = segment.sequence.len();
+    for (id, size) in &index.segments {
+        match decide(id, *size, index, thresholds, excluded) {
+            Decision::Passed => repeat_candidates.push(id.clone()),
+            Decision::Excluded if verbose => {
+                eprintln!("[debug] {} excluded", id.to_str_lossy());
+            }
+            Decision::Neighbor(failure) if verbose => {
+                eprintln!("[debug] {} rejected: {}", id.to_str_lossy(), failure);
+            }
+            _ => {}
+        }
+    }
 
-        // if the segment is too large, skip it
-        if size > repeat_node_size_limit {
-            continue;
+    repeat_candidates
+}
+
+/// Why detection kept or rejected a segment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    Passed,
+    /// Listed in `--exclude-nodes`.
+    Excluded,
+    /// Larger than the maximum repeat size.
+    TooLarge,
+    /// Fewer links than twice the minimum in/out degree.
+    TooFewNeighbors,
+    Neighbor(NeighborFailure),
+}
+
+impl Decision {
+    /// The `--decision-log` label of the decision.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Decision::Passed => "passed",
+            Decision::Excluded => "excluded",
+            Decision::TooLarge => "too-large",
+            Decision::TooFewNeighbors => "too-few-neighbors",
+            Decision::Neighbor(NeighborFailure::TooSmall { .. }) => "small-neighbor",
+            Decision::Neighbor(NeighborFailure::Dangling { .. }) => "dangling",
+        }
+    }
+}
+
+/// The links of `id` at either end, as compared with `--inout`.
+fn neighbor_count(id: &[u8], index: &GraphIndex) -> usize {
+    index
+        .edge_map
+        .get(id)
+        .map(|orient_map| orient_map.values().map(|v| v.len()).sum())
+        .unwrap_or(0)
+}
+
+/// Apply the repeat thresholds to segment `id` of `size`, in order, stopping at
+/// the first that fails. Known artifacts are skipped before any threshold.
+fn decide(
+    id: &[u8],
+    size: usize,
+    index: &GraphIndex,
+    thresholds: &Thresholds,
+    excluded: &HashSet<Vec<u8>>,
+) -> Decision {
+    if excluded.contains(id) {
+        return Decision::Excluded;
+    }
+    if size > thresholds.repeat {
+        return Decision::TooLarge;
+    }
+    if neighbor_count(id, index) < thresholds.inout * 2 {
+        return Decision::TooFewNeighbors;
+    }
+    match check_neighbors(id, index, thresholds.neighbor, thresholds.depth) {
+        None => Decision::Passed,
+        Some(failure) => Decision::Neighbor(failure),
+    }
+}
+
+/// Write the decision of detection on every segment, in GFA order, as a TSV of its
+/// ID, size, neighbour count (links at either end), decision label and, for a
+/// neighbour failure, the neighbour at fault (`NA` otherwise), for `--decision-log`.
+pub fn write_decision_log<W: Write>(
+    index: &GraphIndex,
+    thresholds: &Thresholds,
+    excluded: &HashSet<Vec<u8>>,
+    mut out: W,
+) -> Result<()> {
+    writeln!(out, "ID\tSize\tneighbors\tdecision\tdetail")?;
+    for (id, size) in &index.segments {
+        let decision = decide(id, *size, index, thresholds, excluded);
+        let detail = match &decision {
+            Decision::Neighbor(failure) => failure.to_string(),
+            _ => "NA".to_string(),
+        };
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}",
+            id.to_str_lossy(),
+            size,
+            neighbor_count(id, index),
+            decision.label(),
+            detail
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Keep only the candidates whose name matches `pattern` (`--segment-regex`),
+/// anywhere in the name unless the pattern is anchored.
+pub fn retain_matching(candidates: &mut Vec<Vec<u8>>, pattern: &Regex, verbose: bool) {
+    candidates.retain(|id| {
+        let keep = pattern.is_match(id);
+        if !keep && verbose {
+            eprintln!(
+                "[debug] {} rejected: name does not match --segment-regex",
+                id.to_str_lossy()
+            );
+        }
+        keep
+    });
+}
+
+/// Order candidates for `--sort`: by decreasing `size`, or by `id`. Ties keep
+/// their GFA order.
+pub fn sort_candidates(candidates: &mut [Vec<u8>], index: &GraphIndex, key: &str) {
+    match key {
+        "size" => candidates.sort_by_key(|id| std::cmp::Reverse(index.segment_sizes[id])),
+        "id" => candidates.sort(),
+        _ => unreachable!("--sort only accepts size and id"),
+    }
+}
+
+/// Candidates whose size falls in one `--size-buckets` range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizeBucket {
+    /// The range, e.g. `<1000`, `1000-4999` or `>=5000`.
+    pub label: String,
+    /// Candidate IDs, in candidate order.
+    pub members: Vec<String>,
+}
+
+/// Split candidates into size classes at `bounds`: below the first bound, between
+/// each pair of consecutive bounds, and at or above the last one. Bounds are used
+/// in increasing order, and every bucket is returned, even if empty.
+pub fn size_buckets(candidates: &[Candidate], bounds: &[usize]) -> Vec<SizeBucket> {
+    let mut bounds = bounds.to_vec();
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    let mut buckets = Vec::with_capacity(bounds.len() + 1);
+    for i in 0..=bounds.len() {
+        let label = match (i.checked_sub(1).map(|j| bounds[j]), bounds.get(i)) {
+            (None, Some(hi)) => format!("<{}", hi),
+            (Some(lo), Some(hi)) => format!("{}-{}", lo, hi - 1),
+            (Some(lo), None) => format!(">={}", lo),
+            (None, None) => "all".to_string(),
+        };
+        buckets.push(SizeBucket {
+            label,
+            members: Vec::new(),
+        });
+    }
+    for candidate in candidates {
+        let i = bounds.partition_point(|&b| b <= candidate.size);
+        buckets[i].members.push(candidate.id.clone());
+    }
+    buckets
+}
+
+/// The fewest links between segments `from` and `to` on a walk that never enters
+/// `avoid`, or `None` if there is none within `max_hops`. Links are followed
+/// regardless of orientation, so this is a distance in the undirected graph.
+pub fn hops_avoiding(
+    index: &GraphIndex,
+    from: &[u8],
+    to: &[u8],
+    avoid: &[u8],
+    max_hops: usize,
+) -> Option<usize> {
+    if from == to {
+        return Some(0);
+    }
+    let mut seen: HashSet<&[u8]> = HashSet::from([from, avoid]);
+    let mut frontier = vec![from];
+    for hops in 1..=max_hops {
+        let mut next = Vec::new();
+        for node in frontier {
+            let neighbors = index
+                .edge_map
+                .get(node)
+                .into_iter()
+                .flat_map(|m| m.values());
+            for (neighbor, _) in neighbors.flatten() {
+                if neighbor.as_slice() == to {
+                    return Some(hops);
+                }
+                if seen.insert(neighbor) {
+                    next.push(neighbor.as_slice());
+                }
+            }
         }
+        if next.is_empty() {
+            break;
+        }
+        frontier = next;
+    }
+    None
+}
 
-        let neighbor_count: usize = edge_map
-            .get(&id)
-            .map(|orient_map| orient_map.values().map(|v| v.len()).sum())
-            .unwrap_or(0);
+/// Label each segment with its connected component in the (undirected) graph.
+///
+/// Components are numbered from 1 by decreasing number of segments, so component
+/// 1 is the largest, with ties broken by the order of their first segment in the GFA.
+pub fn connected_components(index: &GraphIndex) -> HashMap<Vec<u8>, usize> {
+    let mut label: HashMap<&[u8], usize> = HashMap::new();
+    let mut members: Vec<Vec<&[u8]>> = Vec::new();
 
-        if neighbor_count < in_out_threshold * 2 {
+    for (id, _) in &index.segments {
+        if label.contains_key(id.as_slice()) {
             continue;
         }
+        let component = members.len();
+        let mut queue = std::collections::VecDeque::from([id.as_slice()]);
+        let mut seen = vec![];
+        label.insert(id, component);
+        while let Some(node) = queue.pop_front() {
+            seen.push(node);
+            let neighbors = index
+                .edge_map
+                .get(node)
+                .into_iter()
+                .flat_map(|m| m.values());
+            for (neighbor, _) in neighbors.flatten() {
+                if !label.contains_key(neighbor.as_slice()) {
+                    label.insert(neighbor, component);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        members.push(seen);
+    }
 
-        let mut valid_neighbors = true;
-        for orient_neighbors in edge_map.get(&id).unwrap_or(&HashMap::new()).values() {
-            for (neighbor_id, _) in orient_neighbors {
-                if let Some(&neighbor_size) = segment_sizes.get(neighbor_id) {
-                    if neighbor_size < neighboring_node_minimum {
-                        valid_neighbors = false;
-                        break;
+    // renumber by decreasing size; the sort is stable so ties keep GFA order
+    let mut order: Vec<usize> = (0..members.len()).collect();
+    order.sort_by_key(|&c| std::cmp::Reverse(members[c].len()));
+    let mut renumbered = vec![0; members.len()];
+    for (rank, c) in order.into_iter().enumerate() {
+        renumbered[c] = rank + 1;
+    }
+
+    label
+        .into_iter()
+        .map(|(id, c)| (id.to_vec(), renumbered[c]))
+        .collect()
+}
+
+/// Whether `id` looks like an inverted repeat: it is linked to the same neighbours,
+/// in the same orientations, when forward as when reverse complemented.
+///
+/// In a circular genome `a R b R'`, where `R'` is the reverse complement of `R`,
+/// the links are `a+ R+`, `R+ b+`, `b+ R-` and `R- a+`, so both `R+` and `R-` are
+/// linked to `{a+, b+}`. A direct repeat only ever appears as `R+` (or `R-`).
+///
+/// With canonical links the same graph stores `{b+, b-}` under `R+` and
+/// `{a+, a-}` under `R-`: each neighbour is reached in both orientations from the
+/// same end of `R`, which is what is checked instead.
+pub fn is_inverted_repeat(id: &[u8], index: &GraphIndex) -> bool {
+    let Some(orient_map) = index.edge_map.get(id) else {
+        return false;
+    };
+    let neighbors = |orient| -> HashSet<(&[u8], Orientation)> {
+        orient_map
+            .get(&orient)
+            .into_iter()
+            .flatten()
+            .map(|(neighbor, o)| (neighbor.as_slice(), *o))
+            .collect()
+    };
+    let forward = neighbors(Orientation::Forward);
+    let backward = neighbors(Orientation::Backward);
+    if !index.canonical_links {
+        return !forward.is_empty() && forward == backward;
+    }
+
+    let both_ways = |ends: &HashSet<(&[u8], Orientation)>| {
+        !ends.is_empty() && ends.iter().all(|&(n, o)| ends.contains(&(n, flip(o))))
+    };
+    both_ways(&forward) && both_ways(&backward)
+}
+
+/// The local topology of a repeat candidate, for `--structure`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Structure {
+    /// Two flanks, each linked to the repeat in both orientations: the flip-flop of
+    /// an inverted repeat.
+    SimpleBubble,
+    /// At most two flanks at each end, whose far ends meet again in a single
+    /// segment one link further, on each side.
+    Superbubble,
+    /// Anything more complex: more flanks, or flanks that don't meet again.
+    Tangle,
+    /// A flank leads nowhere past the repeat: the region is incomplete.
+    TipAttached,
+}
+
+impl Structure {
+    /// The `structure` column label.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Structure::SimpleBubble => "simple-bubble",
+            Structure::Superbubble => "superbubble",
+            Structure::Tangle => "tangle",
+            Structure::TipAttached => "tip-attached",
+        }
+    }
+}
+
+/// Classify the topology around `id`, checking in order: a flank that is a tip, an
+/// inverted-repeat bubble, a superbubble, and otherwise a tangle (see
+/// [`Structure`]). `index` must be keyed canonically (see [`GraphIndex::new`]):
+/// the default keying doesn't say which end of a segment a link leaves from.
+pub fn classify_structure(id: &[u8], index: &GraphIndex) -> Structure {
+    debug_assert!(index.canonical_links);
+    let ends = |segment: &[u8], orient| -> &[(Vec<u8>, Orientation)] {
+        index
+            .edge_map
+            .get(segment)
+            .and_then(|m| m.get(&orient))
+            .map_or(&[], Vec::as_slice)
+    };
+    // the flanks off either end of the repeat, as entered from it
+    let sides = [
+        ends(id, Orientation::Backward),
+        ends(id, Orientation::Forward),
+    ];
+
+    // carrying on through a flank, away from the repeat, must lead somewhere
+    if sides
+        .iter()
+        .flat_map(|side| side.iter())
+        .any(|(flank, orient)| ends(flank, *orient).is_empty())
+    {
+        return Structure::TipAttached;
+    }
+
+    let mut orientations: HashMap<&[u8], HashSet<Orientation>> = HashMap::new();
+    for (flank, orient) in sides.iter().flat_map(|side| side.iter()) {
+        if flank.as_slice() != id {
+            orientations.entry(flank).or_default().insert(*orient);
+        }
+    }
+    if orientations.len() == 2 && orientations.values().all(|o| o.len() == 2) {
+        return Structure::SimpleBubble;
+    }
+
+    let closes = |side: &[(Vec<u8>, Orientation)]| {
+        let flanks: HashSet<&[u8]> = side.iter().map(|(f, _)| f.as_slice()).collect();
+        let beyond: HashSet<&[u8]> = side
+            .iter()
+            .flat_map(|(flank, orient)| ends(flank, *orient))
+            .map(|(next, _)| next.as_slice())
+            .collect();
+        flanks.len() <= 2 && beyond.len() == 1 && !beyond.contains(id)
+    };
+    let [left, right] = sides;
+    let shared = left
+        .iter()
+        .any(|(flank, _)| right.iter().any(|(other, _)| other == flank));
+    if !shared && closes(left) && closes(right) {
+        return Structure::Superbubble;
+    }
+    Structure::Tangle
+}
+
+/// The first neighbour that disqualifies a segment from being a repeat candidate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NeighborFailure {
+    /// The neighbour, `hops` links away, is shorter than the minimum neighbour size.
+    TooSmall {
+        neighbor: Vec<u8>,
+        size: usize,
+        hops: usize,
+    },
+    /// The neighbour, `hops` links away, is linked to but has no `S` line.
+    Dangling { neighbor: Vec<u8>, hops: usize },
+}
+
+impl std::fmt::Display for NeighborFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hops = match self {
+            NeighborFailure::TooSmall {
+                neighbor,
+                size,
+                hops,
+            } => {
+                write!(
+                    f,
+                    "neighbor {} is too small ({})",
+                    neighbor.to_str_lossy(),
+                    size
+                )?;
+                hops
+            }
+            NeighborFailure::Dangling { neighbor, hops } => {
+                write!(f, "neighbor {} has no segment", neighbor.to_str_lossy())?;
+                hops
+            }
+        };
+        if *hops > 1 {
+            write!(f, " at {} hops", hops)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check that every segment up to `depth` links from `id` exists and is at least
+/// `min_size` long, returning the first one that is not, nearest first.
+/// Forward-end neighbours are checked first at each distance. Each segment is
+/// checked once, at its shortest distance, so cycles (including back through `id`)
+/// end the search.
+fn check_neighbors(
+    id: &[u8],
+    index: &GraphIndex,
+    min_size: usize,
+    depth: usize,
+) -> Option<NeighborFailure> {
+    let mut seen: HashSet<&[u8]> = HashSet::from([id]);
+    let mut frontier = vec![id];
+
+    for hops in 1..=depth {
+        let mut next = Vec::new();
+        for node in frontier {
+            let Some(orient_map) = index.edge_map.get(node) else {
+                continue;
+            };
+            for orient in [Orientation::Forward, Orientation::Backward] {
+                for (neighbor, _) in orient_map.get(&orient).into_iter().flatten() {
+                    if !seen.insert(neighbor) {
+                        continue;
+                    }
+                    match index.segment_sizes.get(neighbor) {
+                        Some(&size) if size < min_size => {
+                            return Some(NeighborFailure::TooSmall {
+                                neighbor: neighbor.clone(),
+                                size,
+                                hops,
+                            })
+                        }
+                        Some(_) => next.push(neighbor.as_slice()),
+                        None => {
+                            return Some(NeighborFailure::Dangling {
+                                neighbor: neighbor.clone(),
+                                hops,
+                            })
+                        }
                     }
-                } else {
-                    valid_neighbors = false;
-                    break;
                 }
             }
-            if !valid_neighbors {
-                break;
+        }
+        if next.is_empty() {
+            break;
+        }
+        frontier = next;
+    }
+
+    None
+}
+
+/// A detected repeat candidate.
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidate {
+    pub id: String,
+    pub size: usize,
+    /// Whether the repeat is structurally inverted, only set with `--inverted`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inverted: Option<bool>,
+    /// Connected component of the repeat, only set with `--components`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub component: Option<usize>,
+    /// Oriented IDs of the repeat's immediate neighbours, e.g. `u12+`, only set with
+    /// `--with-neighbors`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub neighbors: Option<Vec<String>>,
+    /// The local topology of the repeat (see [`Structure`]), only set with
+    /// `--structure`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structure: Option<&'static str>,
+}
+
+/// The neighbours of `id` listed in the edge map under `orient`, as oriented IDs
+/// in GFA order, e.g. `u12+`.
+pub fn neighbor_ids(index: &GraphIndex, id: &[u8], orient: Orientation) -> Vec<String> {
+    index
+        .edge_map
+        .get(id)
+        .and_then(|m| m.get(&orient))
+        .map_or_else(Vec::new, |ends| {
+            ends.iter()
+                .map(|(id, o)| format!("{}{}", id.to_str_lossy(), o))
+                .collect()
+        })
+}
+
+/// Comma-join oriented IDs, or `NA` if there are none.
+fn join_or_na(ids: &[String]) -> String {
+    if ids.is_empty() {
+        "NA".to_string()
+    } else {
+        ids.join(",")
+    }
+}
+
+/// Write the topology of each candidate as a TSV: its size, its degree (links at
+/// either end, as compared with `--inout`) and its neighbours as listed in the edge
+/// map under its forward and reverse orientation (see [`GraphIndex::new`]), as
+/// comma-separated oriented IDs in GFA order (`NA` if none).
+pub fn write_topology<W: Write>(
+    index: &GraphIndex,
+    candidates: &[Candidate],
+    mut out: W,
+) -> Result<()> {
+    writeln!(out, "ID\tSize\tdegree\tfwd_links\trev_links")?;
+    for candidate in candidates {
+        let orient_map = index.edge_map.get(candidate.id.as_bytes());
+        let neighbors = |orient| join_or_na(&neighbor_ids(index, candidate.id.as_bytes(), orient));
+        let degree: usize = orient_map.map_or(0, |m| m.values().map(Vec::len).sum());
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}",
+            candidate.id,
+            candidate.size,
+            degree,
+            neighbors(Orientation::Forward),
+            neighbors(Orientation::Backward)
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Detect candidate repeat nodes and return them, with the graph index they were found in.
+///
+/// Candidates are printed as a table unless `store` is set, in which case they
+/// are only returned for the GAF stage or a JSON report.
+pub fn nodes(
+    args: &ArgMatches,
+    store: bool,
+    manifest: &mut Manifest,
+    timings: &mut Timings,
+) -> Result<(Vec<Candidate>, GraphIndex)> {
+    // args
+    let gfa_file = args.get_one::<PathBuf>("GFA").expect("GFA required");
+    let thresholds = Thresholds::from_args(args);
+    if !args.get_flag("allow-trivial") {
+        if let Some(warning) = thresholds.trivial_warning() {
+            eprintln!("{}", warning);
+        }
+    }
+    let verbose = args.get_flag("verbose");
+    let seq_stats = args.get_flag("seq-stats");
+    let inverted = args.get_flag("inverted");
+    let precision = *args.get_one::<usize>("precision").unwrap();
+    let cache_file = args.get_one::<PathBuf>("graph-cache");
+
+    // sequences are not cached, so --seq-stats always needs the GFA itself; without
+    // it only names, lengths and links are read (see GraphIndex::load)
+    let start = Instant::now();
+    let cached = match cache_file {
+        Some(cache_file) => cache::load(cache_file, gfa_file, verbose)?,
+        None => None,
+    };
+    let gfa = if seq_stats {
+        Some(load_gfa(gfa_file).context("Failed to load GFA file")?)
+    } else {
+        None
+    };
+    timings.record("graph load", start);
+
+    // main
+    let start = Instant::now();
+    let mut index = match cached {
+        Some(index) => index,
+        None => {
+            let warn_duplicates = args.get_flag("warn-duplicates");
+            let link_filter = args.get_one::<LinkTagFilter>("min-link-tag");
+            // --structure needs to know which end of a segment each link leaves from
+            let canonical = args.get_flag("canonical-links") || args.get_flag("structure");
+            let index = match &gfa {
+                Some(gfa) => GraphIndex::new(gfa, warn_duplicates, link_filter, canonical)?,
+                None => GraphIndex::load(gfa_file, warn_duplicates, link_filter, canonical)
+                    .context("Failed to load GFA file")?,
+            };
+            if let Some(cache_file) = cache_file {
+                cache::save(cache_file, gfa_file, &index)?;
+                manifest.add("graph_cache", cache_file);
             }
+            index
+        }
+    };
+    if let Some(path) = args.get_one::<PathBuf>("sizes") {
+        let unknown = index.override_sizes(&load_sizes(path)?);
+        if let Some(first) = unknown.first() {
+            eprintln!(
+                "Warning: {} segment(s) in --sizes are not in the GFA and were ignored, e.g. {}",
+                unknown.len(),
+                first.to_str_lossy()
+            );
         }
+    }
+    timings.record("indexing", start);
+
+    let start = Instant::now();
+    let excluded = match args.get_one::<PathBuf>("exclude-nodes") {
+        Some(path) => load_exclude_list(path)?,
+        None => HashSet::new(),
+    };
+    let mut repeat_candidates = find_candidates(&index, &thresholds, &excluded, verbose);
+    if let Some(path) = args.get_one::<PathBuf>("decision-log") {
+        let out = std::fs::File::create(path)
+            .with_context(|| format!("Failed to create decision log: {:?}", path.as_os_str()))?;
+        write_decision_log(&index, &thresholds, &excluded, std::io::BufWriter::new(out))?;
+        manifest.add("decision_log", path);
+    }
+    if let Some(pattern) = args.get_one::<Regex>("segment-regex") {
+        retain_matching(&mut repeat_candidates, pattern, verbose);
+    }
 
-        if valid_neighbors {
-            repeat_candidates.push(id);
+    let show_components = args.get_flag("components");
+    let with_neighbors = args.get_flag("with-neighbors");
+    let structure = args.get_flag("structure");
+    let only_component = args.get_one::<usize>("component").copied();
+    let components =
+        (show_components || only_component.is_some()).then(|| connected_components(&index));
+    if let (Some(components), Some(only)) = (&components, only_component) {
+        repeat_candidates.retain(|id| {
+            let keep = components[id] == only;
+            if !keep && verbose {
+                eprintln!(
+                    "[debug] {} rejected: in component {}, not {}",
+                    id.to_str_lossy(),
+                    components[id],
+                    only
+                );
+            }
+            keep
+        });
+    }
+
+    if let Some(key) = args.get_one::<String>("sort") {
+        sort_candidates(&mut repeat_candidates, &index, key);
+    }
+    if let Some(&max) = args.get_one::<usize>("max-candidates") {
+        let suppressed = repeat_candidates.len().saturating_sub(max);
+        repeat_candidates.truncate(max);
+        if suppressed > 0 {
+            eprintln!(
+                "Note: {} candidate(s) suppressed by --max-candidates {}",
+                suppressed, max
+            );
         }
     }
 
-    if !repeat_candidates.is_empty() && !store {
-        println!("ID\tSize");
+    // the candidate table is printed unless only the GAF stage needs it,
+    // but always with extra columns requested (except in JSON mode)
+    let buckets: Option<Vec<usize>> = args
+        .get_many::<usize>("size-buckets")
+        .map(|bounds| bounds.copied().collect());
+    let print = !store
+        || ((seq_stats
+            || inverted
+            || show_components
+            || with_neighbors
+            || structure
+            || buckets.is_some())
+            && !args.get_flag("json"));
+    let sequences: HashMap<&[u8], &[u8]> = match &gfa {
+        Some(gfa) if seq_stats => gfa
+            .segments
+            .iter()
+            .map(|s| (s.name.as_slice(), s.sequence.as_slice()))
+            .collect(),
+        _ => HashMap::new(),
+    };
+
+    if !repeat_candidates.is_empty() && print {
+        let mut header = vec!["ID", "Size"];
+        if seq_stats {
+            header.extend(["A", "C", "G", "T", "N", "ambiguous", "GC", "seq_entropy"]);
+        }
+        if inverted {
+            header.push("inverted");
+        }
+        if show_components {
+            header.push("component");
+        }
+        if with_neighbors {
+            header.push("neighbors");
+        }
+        if structure {
+            header.push("structure");
+        }
+        println!("{}", header.join("\t"));
     }
 
     let mut nodes = Vec::new();
 
-    for node in repeat_candidates {
-        let node_name = std::str::from_utf8(&node)?;
-        nodes.push(node_name.to_string());
-        let segment_size = segment_sizes.get(&node).unwrap();
+    for node in &repeat_candidates {
+        let node_name = std::str::from_utf8(node)?;
+        let segment_size = index.segment_sizes.get(node).unwrap();
+        let is_inverted = inverted.then(|| is_inverted_repeat(node, &index));
+        let component = components
+            .as_ref()
+            .filter(|_| show_components)
+            .map(|c| c[node]);
+        let neighbors = with_neighbors.then(|| {
+            let mut ids = neighbor_ids(&index, node, Orientation::Forward);
+            ids.extend(neighbor_ids(&index, node, Orientation::Backward));
+            ids
+        });
+        let neighbor_column = neighbors.as_deref().map(join_or_na);
+        let node_structure = structure.then(|| classify_structure(node, &index).label());
+        nodes.push(Candidate {
+            id: node_name.to_string(),
+            size: *segment_size,
+            inverted: is_inverted,
+            component,
+            neighbors,
+            structure: node_structure,
+        });
 
-        if !store {
-            println!("{}\t{}", node_name, segment_size);
+        if !print {
+            continue;
         }
+        let mut row = vec![node_name.to_string(), segment_size.to_string()];
+        if seq_stats {
+            let comp = Composition::new(sequences[node.as_slice()]);
+            let gc = comp
+                .gc()
+                .map_or("NA".to_string(), |gc| format!("{:.*}", precision, gc));
+            row.extend([
+                comp.a.to_string(),
+                comp.c.to_string(),
+                comp.g.to_string(),
+                comp.t.to_string(),
+                comp.n.to_string(),
+                comp.ambiguity_codes().to_string(),
+                gc,
+                format!("{:.*}", precision, comp.entropy()),
+            ]);
+        }
+        if let Some(is_inverted) = is_inverted {
+            row.push(if is_inverted { "yes" } else { "no" }.to_string());
+        }
+        if let Some(component) = component {
+            row.push(component.to_string());
+        }
+        if let Some(neighbors) = neighbor_column {
+            row.push(neighbors);
+        }
+        if let Some(structure) = node_structure {
+            row.push(structure.to_string());
+        }
+        println!("{}", row.join("\t"));
     }
 
-    if store {
-        Ok(Some(nodes))
-    } else {
-        Ok(None)
+    if let Some(bounds) = buckets.filter(|_| print && !nodes.is_empty()) {
+        let buckets = size_buckets(&nodes, &bounds);
+        println!("\nsize_bucket\tcandidates");
+        for bucket in &buckets {
+            println!("{}\t{}", bucket.label, bucket.members.len());
+        }
+        if args.get_flag("bucket-members") {
+            for bucket in buckets.iter().filter(|b| !b.members.is_empty()) {
+                println!("\n# {}", bucket.label);
+                for member in &bucket.members {
+                    println!("{}", member);
+                }
+            }
+        }
+    }
+
+    // separate the candidate table from the GAF stage output
+    if store && print && !repeat_candidates.is_empty() {
+        println!();
+    }
+    timings.record("detection", start);
+
+    Ok((nodes, index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(lines: &[&str]) -> Gfa {
+        GFAParser::new()
+            .parse_lines(lines.iter().map(|l| l.as_bytes()))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_size_buckets() {
+        let candidate = |id: &str, size| Candidate {
+            id: id.into(),
+            size,
+            inverted: None,
+            component: None,
+            neighbors: None,
+            structure: None,
+        };
+        let candidates = [
+            candidate("a", 500),
+            candidate("b", 1000),
+            candidate("c", 4999),
+            candidate("d", 12000),
+            candidate("e", 999),
+        ];
+
+        // bounds are sorted, whatever order they're given in
+        let buckets = size_buckets(&candidates, &[5000, 1000]);
+        let summary: Vec<(&str, usize)> = buckets
+            .iter()
+            .map(|b| (b.label.as_str(), b.members.len()))
+            .collect();
+        assert_eq!(summary, [("<1000", 2), ("1000-4999", 2), (">=5000", 1)]);
+        assert_eq!(buckets[0].members, ["a", "e"]);
+
+        let buckets = size_buckets(&candidates, &[100000]);
+        assert_eq!(buckets[0].members.len(), 5);
+        assert!(buckets[1].members.is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_segment_names() {
+        let gfa = parse(&[
+            "S\ta\tACGT",
+            "S\tb\tACGT",
+            "S\ta\tACGTACGT",
+            "L\ta\t+\tb\t+\t0M",
+        ]);
+
+        let err = GraphIndex::new(&gfa, false, None, false).err().unwrap();
+        assert!(err.to_string().contains("Duplicate segment name in GFA: a"));
+
+        // with the flag, the last segment of that name is kept
+        let index = GraphIndex::new(&gfa, true, None, false).unwrap();
+        assert_eq!(index.segment_sizes[b"a".as_slice()], 8);
+    }
+
+    #[test]
+    fn test_reference_windows() {
+        let gfa = parse(&[
+            "S\ta\tACGT",
+            "S\tb\tACGT",
+            "S\tc\tACGT",
+            "S\td\tACGT",
+            "P\tref\ta+,b+,c-,d+\t*",
+        ]);
+
+        let windows = reference_windows(&gfa);
+
+        assert_eq!(windows.len(), 4);
+        assert!(windows.contains(">a>b<c"));
+        assert!(windows.contains(">c<b<a"));
+        assert!(windows.contains(">b<c>d"));
+        assert!(windows.contains("<d>c<b"));
+    }
+
+    #[test]
+    fn test_check_neighbors_reports_small_neighbor() {
+        let gfa = parse(&[
+            "S\tr\tACGT",
+            "S\tbig\tACGTACGTACGT",
+            "S\tsmall\tACG",
+            "L\tbig\t+\tr\t+\t0M",
+            "L\tr\t+\tsmall\t+\t0M",
+            "L\tr\t-\tghost\t+\t0M",
+        ]);
+        let index = GraphIndex::new(&gfa, false, None, false).unwrap();
+
+        assert_eq!(
+            check_neighbors(b"r", &index, 5, 1),
+            Some(NeighborFailure::TooSmall {
+                neighbor: b"small".to_vec(),
+                size: 3,
+                hops: 1
+            })
+        );
+        assert_eq!(
+            check_neighbors(b"r", &index, 3, 1),
+            Some(NeighborFailure::Dangling {
+                neighbor: b"ghost".to_vec(),
+                hops: 1
+            })
+        );
+        assert_eq!(check_neighbors(b"big", &index, 3, 1), None);
+    }
+
+    #[test]
+    fn test_neighbor_depth() {
+        // x a R b y, and a cycle back from y to x through R's flanks
+        let gfa = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tACGTACGTACGT",
+            "S\tb\tACGTACGTACGT",
+            "S\tc\tACGTACGTACGT",
+            "S\td\tACGTACGTACGT",
+            "S\tx\tACGTACGTACGT",
+            "S\ty\tACG",
+            "L\tx\t+\ta\t+\t0M",
+            "L\ta\t+\tR\t+\t0M",
+            "L\tR\t+\tb\t+\t0M",
+            "L\tc\t+\tR\t+\t0M",
+            "L\tR\t+\td\t+\t0M",
+            "L\tb\t+\ty\t+\t0M",
+            "L\ty\t+\tx\t+\t0M",
+        ]);
+        let index = GraphIndex::new(&gfa, false, None, false).unwrap();
+        let thresholds = |depth| Thresholds {
+            repeat: 10,
+            neighbor: 10,
+            inout: 2,
+            depth,
+        };
+
+        // the immediate neighbours are long enough, but y, two hops out, is not
+        let found = find_candidates(&index, &thresholds(1), &HashSet::new(), false);
+        assert_eq!(found, vec![b"R".to_vec()]);
+        let found = find_candidates(&index, &thresholds(2), &HashSet::new(), false);
+        assert!(found.is_empty());
+        let failure = check_neighbors(b"R", &index, 10, 2).unwrap();
+        assert_eq!(
+            failure,
+            NeighborFailure::TooSmall {
+                neighbor: b"y".to_vec(),
+                size: 3,
+                hops: 2
+            }
+        );
+        assert_eq!(failure.to_string(), "neighbor y is too small (3) at 2 hops");
+
+        // the cycle is only followed once
+        assert_eq!(check_neighbors(b"R", &index, 3, 100), None);
+    }
+
+    #[test]
+    fn test_inverted_repeat() {
+        // circular a R b R', R' being R reverse complemented
+        let inverted = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tACGTACGT",
+            "S\tb\tACGTACGT",
+            "L\ta\t+\tR\t+\t0M",
+            "L\tR\t+\tb\t+\t0M",
+            "L\tb\t+\tR\t-\t0M",
+            "L\tR\t-\ta\t+\t0M",
+        ]);
+        let index = GraphIndex::new(&inverted, false, None, false).unwrap();
+        assert!(is_inverted_repeat(b"R", &index));
+        assert!(!is_inverted_repeat(b"a", &index));
+
+        // a direct repeat a R b, c R d
+        let direct = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tACGT",
+            "S\tb\tACGT",
+            "S\tc\tACGT",
+            "S\td\tACGT",
+            "L\ta\t+\tR\t+\t0M",
+            "L\tR\t+\tb\t+\t0M",
+            "L\tc\t+\tR\t+\t0M",
+            "L\tR\t+\td\t+\t0M",
+        ]);
+        let index = GraphIndex::new(&direct, false, None, false).unwrap();
+        assert!(!is_inverted_repeat(b"R", &index));
+    }
+
+    #[test]
+    fn test_classify_structure() {
+        let classify = |links: &[&str]| {
+            let mut lines: Vec<String> = ["R", "a", "b", "c", "d", "s", "t", "u"]
+                .iter()
+                .map(|id| format!("S\t{}\tACGT", id))
+                .collect();
+            lines.extend(links.iter().map(|link| {
+                let f: Vec<&str> = link.split(' ').collect();
+                format!("L\t{}\t{}\t{}\t{}\t0M", f[0], f[1], f[2], f[3])
+            }));
+            let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+            let index = GraphIndex::new(&parse(&lines), false, None, true).unwrap();
+            classify_structure(b"R", &index)
+        };
+
+        // a circular a R b R': R flip-flops between a and b
+        let inverted = ["a + R +", "R + b +", "b + R -", "R - a +"];
+        assert_eq!(classify(&inverted), Structure::SimpleBubble);
+
+        // a and c lead in from s, b and d out to t
+        let superbubble = [
+            "s + a +", "s + c +", "a + R +", "c + R +", "R + b +", "R + d +", "b + t +", "d + t +",
+        ];
+        assert_eq!(classify(&superbubble), Structure::Superbubble);
+
+        // d leads on to u rather than t
+        let mut tangle = superbubble;
+        tangle[7] = "d + u +";
+        assert_eq!(classify(&tangle), Structure::Tangle);
+        // three flanks at one end
+        let mut tangle = superbubble.to_vec();
+        tangle.extend(["u + R +", "s + u +"]);
+        assert_eq!(classify(&tangle), Structure::Tangle);
+
+        // d is a dead end past R
+        let tip = &superbubble[..7];
+        assert_eq!(classify(tip), Structure::TipAttached);
+        assert_eq!(Structure::TipAttached.label(), "tip-attached");
+    }
+
+    #[test]
+    fn test_neighbor_ids() {
+        let gfa = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tACGTACGT",
+            "S\tb\tACGTACGT",
+            "S\tc\tACGTACGT",
+            "L\ta\t+\tR\t+\t0M",
+            "L\tR\t+\tb\t+\t0M",
+            "L\tR\t-\tc\t+\t0M",
+        ]);
+        let index = GraphIndex::new(&gfa, false, None, false).unwrap();
+        assert_eq!(
+            neighbor_ids(&index, b"R", Orientation::Forward),
+            ["a+", "b+"]
+        );
+        assert_eq!(neighbor_ids(&index, b"R", Orientation::Backward), ["c+"]);
+        assert!(neighbor_ids(&index, b"x", Orientation::Forward).is_empty());
+        assert_eq!(join_or_na(&[]), "NA");
+    }
+
+    #[test]
+    fn test_decision_log() {
+        let gfa = parse(&[
+            "S\tR\tACGT",
+            "S\tS\tACGT",
+            "S\tQ\tACGT",
+            "S\ta\tACGTACGT",
+            "S\tb\tACGTACGT",
+            "S\tc\tACGTACGT",
+            "S\td\tACGTACGT",
+            "S\tt\tAC",
+            "S\tx\tACG",
+            "L\ta\t+\tR\t+\t0M",
+            "L\tR\t+\tb\t+\t0M",
+            "L\tc\t+\tR\t+\t0M",
+            "L\tR\t+\td\t-\t0M",
+            // S leads into the short t
+            "L\ta\t+\tS\t+\t0M",
+            "L\tS\t+\tb\t+\t0M",
+            "L\tc\t+\tS\t+\t0M",
+            "L\tS\t+\tt\t+\t0M",
+            // Q leads into z, which has no S line
+            "L\ta\t+\tQ\t+\t0M",
+            "L\tQ\t+\tb\t+\t0M",
+            "L\tc\t+\tQ\t+\t0M",
+            "L\tQ\t+\tz\t+\t0M",
+        ]);
+        let index = GraphIndex::new(&gfa, false, None, false).unwrap();
+        let thresholds = Thresholds {
+            repeat: 5,
+            neighbor: 5,
+            inout: 2,
+            depth: 1,
+        };
+        let excluded = HashSet::from([b"x".to_vec()]);
+        let mut out = Vec::new();
+        write_decision_log(&index, &thresholds, &excluded, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "ID\tSize\tneighbors\tdecision\tdetail");
+        assert_eq!(lines[1], "R\t4\t4\tpassed\tNA");
+        assert_eq!(
+            lines[2],
+            "S\t4\t4\tsmall-neighbor\tneighbor t is too small (2)"
+        );
+        assert_eq!(lines[3], "Q\t4\t4\tdangling\tneighbor z has no segment");
+        assert_eq!(lines[4], "a\t8\t3\ttoo-large\tNA");
+        assert_eq!(lines[8], "t\t2\t1\ttoo-few-neighbors\tNA");
+        assert_eq!(lines[9], "x\t3\t0\texcluded\tNA");
+
+        // the log agrees with detection
+        let found = find_candidates(&index, &thresholds, &excluded, false);
+        assert_eq!(found, vec![b"R".to_vec()]);
+    }
+
+    #[test]
+    fn test_write_topology() {
+        // a R b, c R d, with an unlinked segment
+        let gfa = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tACGTACGT",
+            "S\tb\tACGTACGT",
+            "S\tc\tACGTACGT",
+            "S\td\tACGTACGT",
+            "S\te\tACGTACGT",
+            "L\ta\t+\tR\t+\t0M",
+            "L\tR\t+\tb\t+\t0M",
+            "L\tc\t+\tR\t+\t0M",
+            "L\tR\t+\td\t-\t0M",
+        ]);
+        let index = GraphIndex::new(&gfa, false, None, false).unwrap();
+        let candidate = |id: &str| Candidate {
+            id: id.into(),
+            size: index.segment_sizes[id.as_bytes()],
+            inverted: None,
+            component: None,
+            neighbors: None,
+            structure: None,
+        };
+        let mut out = Vec::new();
+        write_topology(&index, &[candidate("R"), candidate("e")], &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "ID\tSize\tdegree\tfwd_links\trev_links\n\
+             R\t4\t4\ta+,b+,c+,d-\tNA\n\
+             e\t8\t0\tNA\tNA\n"
+        );
+
+        // keyed by the end each link leaves R from
+        let index = GraphIndex::new(&gfa, false, None, true).unwrap();
+        let mut out = Vec::new();
+        write_topology(&index, &[candidate("R")], &mut out).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with("R\t4\t4\tb+,d-\ta-,c-\n"));
+    }
+
+    #[test]
+    fn test_parse_sizes() {
+        let sizes = parse_sizes(b"# trimmed\nR\t40\n\nu1\t7\n").unwrap();
+        assert_eq!(sizes, vec![(b"R".to_vec(), 40), (b"u1".to_vec(), 7)]);
+        let err = parse_sizes(b"R\t40\nu1\tseven\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: invalid size seven");
+        assert!(parse_sizes(b"R 40\n").is_err());
+    }
+
+    #[test]
+    fn test_override_sizes() {
+        let gfa = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tACGTACGTACGT",
+            "S\tb\tACGTACGTACGT",
+            "S\tc\tACGTACGTACGT",
+            "S\td\tACGTACGTACGT",
+            "L\ta\t+\tR\t+\t0M",
+            "L\tR\t+\tb\t+\t0M",
+            "L\tc\t+\tR\t+\t0M",
+            "L\tR\t+\td\t+\t0M",
+        ]);
+        let mut index = GraphIndex::new(&gfa, false, None, false).unwrap();
+        let thresholds = Thresholds {
+            repeat: 10,
+            neighbor: 10,
+            inout: 2,
+            depth: 1,
+        };
+        let found = find_candidates(&index, &thresholds, &HashSet::new(), false);
+        assert_eq!(found, vec![b"R".to_vec()]);
+
+        // a neighbour trimmed below -n disqualifies R
+        let unknown = index.override_sizes(&[(b"b".to_vec(), 5), (b"ghost".to_vec(), 1)]);
+        assert_eq!(unknown, vec![b"ghost".to_vec()]);
+        assert_eq!(index.segment_sizes[b"b".as_slice()], 5);
+        assert!(index.segments.contains(&(b"b".to_vec(), 5)));
+        assert!(find_candidates(&index, &thresholds, &HashSet::new(), false).is_empty());
+    }
+
+    #[test]
+    fn test_canonical_links() {
+        // the same edge, written from either end
+        let segments = ["S\tR\tACGT", "S\ta\tACGTACGT"];
+        let from_a = parse(&[segments[0], segments[1], "L\ta\t+\tR\t-\t0M"]);
+        let from_r = parse(&[segments[0], segments[1], "L\tR\t+\ta\t-\t0M"]);
+
+        let canonical = GraphIndex::new(&from_a, false, None, true).unwrap();
+        assert_eq!(
+            canonical,
+            GraphIndex::new(&from_r, false, None, true).unwrap()
+        );
+        let ends = &canonical.edge_map[b"R".as_slice()];
+        assert_eq!(ends.len(), 1);
+        assert_eq!(
+            ends[&Orientation::Forward],
+            vec![(b"a".to_vec(), Orientation::Backward)]
+        );
+
+        // keyed by the orientation each segment is written in, the two disagree
+        let from_a = GraphIndex::new(&from_a, false, None, false).unwrap();
+        let from_r = GraphIndex::new(&from_r, false, None, false).unwrap();
+        assert_ne!(from_a.edge_map, from_r.edge_map);
+    }
+
+    #[test]
+    fn test_canonical_inverted_repeat() {
+        let inverted = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tACGTACGT",
+            "S\tb\tACGTACGT",
+            "L\ta\t+\tR\t+\t0M",
+            "L\tR\t+\tb\t+\t0M",
+            "L\tb\t+\tR\t-\t0M",
+            "L\tR\t-\ta\t+\t0M",
+        ]);
+        let index = GraphIndex::new(&inverted, false, None, true).unwrap();
+        assert!(is_inverted_repeat(b"R", &index));
+        assert!(!is_inverted_repeat(b"a", &index));
+
+        let direct = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tACGT",
+            "S\tb\tACGT",
+            "L\ta\t+\tR\t+\t0M",
+            "L\tR\t+\tb\t+\t0M",
+            "L\tb\t+\tR\t+\t0M",
+            "L\tR\t+\ta\t+\t0M",
+        ]);
+        let index = GraphIndex::new(&direct, false, None, true).unwrap();
+        assert!(!is_inverted_repeat(b"R", &index));
+    }
+
+    #[test]
+    fn test_possible_paths() {
+        // three segments into R, two out of it, one of them reversed
+        let gfa = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tACGT",
+            "S\tb\tACGT",
+            "S\tc\tACGT",
+            "S\td\tACGT",
+            "S\te\tACGT",
+            "L\ta\t+\tR\t+\t0M",
+            "L\tb\t+\tR\t+\t0M",
+            "L\tR\t-\tc\t+\t0M",
+            "L\tR\t+\td\t+\t0M",
+            "L\te\t-\tR\t-\t0M",
+        ]);
+        let possible = possible_paths(&gfa, &["R".to_string(), "d".to_string()]);
+        assert_eq!(possible["R"], 2 * 3 * 2);
+        assert_eq!(possible["d"], 0);
+    }
+
+    #[test]
+    fn test_segment_regex() {
+        let mut candidates: Vec<Vec<u8>> = ["chr1_u27", "chr2_u27", "chr10_u3", "chr1_u5"]
+            .iter()
+            .map(|id| id.as_bytes().to_vec())
+            .collect();
+
+        retain_matching(&mut candidates, &Regex::new("^chr1_").unwrap(), false);
+        assert_eq!(candidates, vec![b"chr1_u27".to_vec(), b"chr1_u5".to_vec()]);
+
+        // unanchored patterns match anywhere in the name
+        retain_matching(&mut candidates, &Regex::new("u2").unwrap(), false);
+        assert_eq!(candidates, vec![b"chr1_u27".to_vec()]);
+    }
+
+    #[test]
+    fn test_excluded_nodes_are_never_candidates() {
+        let gfa = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tACGT",
+            "S\tb\tACGT",
+            "S\tc\tACGT",
+            "S\td\tACGT",
+            "L\ta\t+\tR\t+\t0M",
+            "L\tb\t+\tR\t+\t0M",
+            "L\tR\t+\tc\t+\t0M",
+            "L\tR\t+\td\t+\t0M",
+        ]);
+        let index = GraphIndex::new(&gfa, false, None, false).unwrap();
+        let thresholds = Thresholds {
+            repeat: 10,
+            neighbor: 1,
+            inout: 2,
+            depth: 1,
+        };
+
+        let found = find_candidates(&index, &thresholds, &HashSet::new(), false);
+        assert_eq!(found, vec![b"R".to_vec()]);
+
+        let excluded = parse_exclude_list(b"# assembler artifacts\n\nR\n");
+        assert!(find_candidates(&index, &thresholds, &excluded, false).is_empty());
+    }
+
+    #[test]
+    fn test_connected_components() {
+        let gfa = parse(&[
+            "S\ta\tACGT",
+            "S\tb\tACGT",
+            "S\tc\tACGT",
+            "S\td\tACGT",
+            "S\te\tACGT",
+            "S\tf\tACGT",
+            "L\ta\t+\tb\t+\t0M",
+            "L\td\t+\te\t-\t0M",
+            "L\tf\t-\te\t+\t0M",
+            "L\tc\t+\tc\t+\t0M",
+        ]);
+        let index = GraphIndex::new(&gfa, false, None, false).unwrap();
+        let components = connected_components(&index);
+
+        // d-e-f is the largest component, then a-b, then the self-looped c
+        for (id, component) in [("a", 2), ("b", 2), ("c", 3), ("d", 1), ("e", 1), ("f", 1)] {
+            assert_eq!(components[id.as_bytes()], component, "{}", id);
+        }
+    }
+
+    #[test]
+    fn test_sort_candidates() {
+        let gfa = parse(&["S\tc\tACGT", "S\ta\tAC", "S\tb\tACGTACGT", "S\td\tACGT"]);
+        let index = GraphIndex::new(&gfa, false, None, false).unwrap();
+        let ids = |candidates: &[Vec<u8>]| {
+            candidates
+                .iter()
+                .map(|id| id.to_str_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        let mut candidates: Vec<Vec<u8>> =
+            index.segments.iter().map(|(id, _)| id.clone()).collect();
+
+        sort_candidates(&mut candidates, &index, "size");
+        // c and d tie on size and keep their GFA order
+        assert_eq!(ids(&candidates), ["b", "c", "d", "a"]);
+
+        sort_candidates(&mut candidates, &index, "id");
+        assert_eq!(ids(&candidates), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_windows_line_endings() {
+        assert_eq!(clean_line(b"\xEF\xBB\xBFS\ta\tACGT\r", true), b"S\ta\tACGT");
+        // a byte order mark is only stripped from the first line
+        assert_eq!(clean_line(b"\xEF\xBB\xBFS\ta", false), b"\xEF\xBB\xBFS\ta");
+
+        // a segment on the first line, after a byte order mark
+        let path = std::env::temp_dir().join(format!("gfa_recomb_crlf_{}.gfa", std::process::id()));
+        let gfa = [
+            "\u{FEFF}S\tR\tACGT",
+            "S\ta\tACGTACGT",
+            "S\tb\tACGTACGT",
+            "L\ta\t+\tR\t+\t0M",
+            "L\tR\t+\tb\t+\t0M",
+            "P\tp1\ta+,R+,b+\t*",
+        ]
+        .join("\r\n")
+            + "\r";
+        std::fs::write(&path, gfa).unwrap();
+        let full = load_gfa(&path).unwrap();
+        let light = GraphIndex::load(&path, false, None, false).unwrap();
+        let windows = load_reference_windows(&path).unwrap();
+        let validation =
+            crate::validate::validate(std::io::BufReader::new(std::fs::File::open(&path).unwrap()))
+                .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let names: Vec<&[u8]> = full.segments.iter().map(|s| s.name.as_slice()).collect();
+        assert_eq!(names, [b"R".as_slice(), b"a", b"b"]);
+        assert_eq!(full.links.len(), 2);
+        assert_eq!(GraphIndex::new(&full, false, None, false).unwrap(), light);
+        assert_eq!(light.segment_sizes[b"R".as_slice()], 4);
+        assert!(windows.contains(">a>R>b"));
+        assert!(validation.is_empty(), "{:?}", validation);
+    }
+
+    #[test]
+    fn test_lightweight_index() {
+        let path =
+            std::env::temp_dir().join(format!("gfa_recomb_light_{}.gfa", std::process::id()));
+        std::fs::write(
+            &path,
+            [
+                "H\tVN:Z:1.0",
+                "S\tR\tACGTACGT",
+                "S\ta\tACGTACGTACGTACGT",
+                "S\tb\tACGTACGTACGTACGT",
+                "S\tc\t*",
+                "L\ta\t+\tR\t+\t0M\tSR:i:3",
+                "L\tR\t+\tb\t+\t0M",
+                "L\tb\t+\tR\t+\t0M",
+                "L\tR\t+\ta\t+\t0M",
+                "P\tp1\ta+,R+,b+\t*",
+                "",
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+
+        let full = GraphIndex::new(&load_gfa(&path).unwrap(), false, None, false).unwrap();
+        let light = GraphIndex::load(&path, false, None, false).unwrap();
+        assert_eq!(full, light);
+
+        let thresholds = Thresholds {
+            repeat: 10,
+            neighbor: 10,
+            inout: 2,
+            depth: 1,
+        };
+        let excluded = HashSet::new();
+        assert_eq!(
+            find_candidates(&full, &thresholds, &excluded, false),
+            find_candidates(&light, &thresholds, &excluded, false)
+        );
+        assert_eq!(
+            find_candidates(&light, &thresholds, &excluded, false),
+            [b"R".to_vec()]
+        );
+
+        // tags are kept for --min-link-tag
+        let filter: LinkTagFilter = "SR:1".parse().unwrap();
+        let full = GraphIndex::new(&load_gfa(&path).unwrap(), false, Some(&filter), false).unwrap();
+        assert_eq!(
+            full,
+            GraphIndex::load(&path, false, Some(&filter), false).unwrap()
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_hops_avoiding() {
+        // a -> R -> b directly, and a -> x -> y -> b around the repeat
+        let gfa = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tACGT",
+            "S\tb\tACGT",
+            "S\tx\tACGT",
+            "S\ty\tACGT",
+            "S\tz\tACGT",
+            "L\ta\t+\tR\t+\t0M",
+            "L\tR\t+\tb\t+\t0M",
+            "L\ta\t+\tx\t-\t0M",
+            "L\ty\t+\tx\t+\t0M",
+            "L\tb\t-\ty\t-\t0M",
+            "L\tz\t+\tR\t-\t0M",
+        ]);
+        let index = GraphIndex::new(&gfa, false, None, false).unwrap();
+
+        assert_eq!(hops_avoiding(&index, b"a", b"b", b"R", 10), Some(3));
+        assert_eq!(hops_avoiding(&index, b"b", b"a", b"R", 10), Some(3));
+        assert_eq!(hops_avoiding(&index, b"a", b"b", b"R", 2), None);
+        // through the repeat it would be 2
+        assert_eq!(hops_avoiding(&index, b"a", b"b", b"x", 10), Some(2));
+        // z only connects through the repeat
+        assert_eq!(hops_avoiding(&index, b"a", b"z", b"R", 10), None);
+        assert_eq!(hops_avoiding(&index, b"a", b"a", b"R", 10), Some(0));
+    }
+
+    #[test]
+    fn test_reciprocal_links() {
+        let single = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tACGT",
+            "S\tb\tACGT",
+            "L\ta\t+\tR\t-\t0M",
+            "L\tR\t+\tb\t+\t0M",
+        ]);
+        // the same two edges, each also listed from its other end, and once verbatim
+        let both = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tACGT",
+            "S\tb\tACGT",
+            "L\ta\t+\tR\t-\t0M",
+            "L\tR\t+\ta\t-\t0M",
+            "L\tR\t+\tb\t+\t0M",
+            "L\tb\t-\tR\t-\t0M",
+            "L\tR\t+\tb\t+\t0M",
+        ]);
+        let single = GraphIndex::new(&single, false, None, false).unwrap();
+        let both = GraphIndex::new(&both, false, None, false).unwrap();
+        assert_eq!(single.edge_map, both.edge_map);
+        let degree: usize = both.edge_map[b"R".as_slice()].values().map(Vec::len).sum();
+        assert_eq!(degree, 2);
+
+        // a link in the opposite orientation is a different edge
+        let other = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tACGT",
+            "L\ta\t+\tR\t-\t0M",
+            "L\tR\t-\ta\t-\t0M",
+        ]);
+        let other = GraphIndex::new(&other, false, None, false).unwrap();
+        let degree: usize = other.edge_map[b"R".as_slice()].values().map(Vec::len).sum();
+        assert_eq!(degree, 2);
+    }
+
+    #[test]
+    fn test_link_tag_filter() {
+        let gfa = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tACGT",
+            "S\tb\tACGT",
+            "S\tc\tACGT",
+            "L\ta\t+\tR\t+\t0M\tSR:i:12",
+            "L\tR\t+\tb\t+\t0M\tSR:i:3",
+            "L\tR\t+\tc\t+\t0M",
+        ]);
+        let filter: LinkTagFilter = "SR:5".parse().unwrap();
+        assert!("SR5".parse::<LinkTagFilter>().is_err());
+        assert!("SRX:5".parse::<LinkTagFilter>().is_err());
+
+        let all = GraphIndex::new(&gfa, false, None, false).unwrap();
+        assert_eq!(
+            all.edge_map[b"R".as_slice()][&Orientation::Forward].len(),
+            3
+        );
+
+        // only the well supported link a+ R+ is kept
+        let supported = GraphIndex::new(&gfa, false, Some(&filter), false).unwrap();
+        assert_eq!(
+            supported.edge_map[b"R".as_slice()][&Orientation::Forward],
+            vec![(b"a".to_vec(), Orientation::Forward)]
+        );
+        assert!(!supported.edge_map.contains_key(b"b".as_slice()));
+    }
+
+    #[test]
+    fn test_sub_gfa_round_trip() {
+        let gfa = parse(&[
+            "H\tVN:Z:1.0",
+            "S\tR\tACGT\tLN:i:4",
+            "S\ta\tAAAA",
+            "S\tb\tCCCC",
+            "S\tfar\tGGGG",
+            "L\ta\t+\tR\t-\t2M\tSR:i:7",
+            "L\tR\t+\tb\t+\t*",
+            "L\tb\t+\tfar\t+\t0M",
+        ]);
+        let mut out = Vec::new();
+        write_sub_gfa(&gfa, &["R".to_string()], &mut out).unwrap();
+
+        let lines: Vec<&[u8]> = out.lines().collect();
+        let sub = parse(
+            &lines
+                .iter()
+                .map(|l| l.to_str().unwrap())
+                .collect::<Vec<_>>(),
+        );
+        let names: Vec<_> = sub.segments.iter().map(|s| s.name.to_str_lossy()).collect();
+        assert_eq!(names, ["R", "a", "b"]);
+        assert_eq!(sub.segments[0].sequence, b"ACGT");
+        assert_eq!(sub.links.len(), 2);
+        assert_eq!(sub.links[0].overlap, b"2M");
+        assert_eq!(sub.links[0].to_orient, Orientation::Backward);
+        assert_eq!(sub.links[0].optional, gfa.links[0].optional);
+    }
+
+    #[test]
+    fn test_edge_list() {
+        let gfa = parse(&[
+            "S\tR\tACGT",
+            "S\ta\tAAAA",
+            "S\tb\tCCCC",
+            "S\tfar\tGGGG",
+            "L\ta\t+\tR\t-\t0M",
+            "L\tR\t+\tb\t+\t0M",
+            "L\tb\t+\tfar\t+\t0M",
+            // a+ R- read from R's end, then verbatim
+            "L\tR\t+\ta\t-\t0M",
+            "L\tR\t+\tb\t+\t0M",
+        ]);
+        let mut out = Vec::new();
+        write_edge_list(&gfa, None, &mut out).unwrap();
+        let rows: Vec<_> = out.lines().map(|l| l.to_str().unwrap()).collect();
+        assert_eq!(rows[0], "from_id\tfrom_orient\tto_id\tto_orient");
+        // one row per input link, as written
+        let links: Vec<_> = gfa.links[..3]
+            .iter()
+            .map(|l| {
+                format!(
+                    "{}\t{}\t{}\t{}",
+                    l.from_segment.as_bstr(),
+                    l.from_orient,
+                    l.to_segment.as_bstr(),
+                    l.to_orient
+                )
+            })
+            .collect();
+        assert_eq!(rows[1..], links);
+        assert_eq!(rows[1], "a\t+\tR\t-");
+
+        let mut out = Vec::new();
+        write_edge_list(&gfa, Some(&["R".to_string()]), &mut out).unwrap();
+        assert_eq!(out.lines().count(), 3);
     }
 }
@@ -0,0 +1,144 @@
+//! Export candidate repeats and their immediate neighbourhoods as GraphML, for
+//! Cytoscape, networkx and similar tools.
+
+use anyhow::Result;
+use bstr::ByteSlice;
+use std::collections::HashSet;
+use std::io::Write;
+
+use crate::gfa::{neighborhood, Gfa};
+
+/// Write the `repeats` and every segment linked to one of them, with the links
+/// touching a repeat, as a GraphML graph.
+///
+/// Nodes carry `size` and `is_repeat` attributes, edges `from_orient`, `to_orient`
+/// and `overlap`. Segments and links are written in GFA order.
+pub fn write_graphml<W: Write>(gfa: &Gfa, repeats: &[String], mut out: W) -> Result<()> {
+    let (neighborhood, links) = neighborhood(gfa, repeats);
+    let repeats: HashSet<&[u8]> = repeats.iter().map(|r| r.as_bytes()).collect();
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    for (id, target, name, kind) in [
+        ("d0", "node", "size", "long"),
+        ("d1", "node", "is_repeat", "boolean"),
+        ("d2", "edge", "from_orient", "string"),
+        ("d3", "edge", "to_orient", "string"),
+        ("d4", "edge", "overlap", "string"),
+    ] {
+        writeln!(
+            out,
+            r#"  <key id="{id}" for="{target}" attr.name="{name}" attr.type="{kind}"/>"#
+        )?;
+    }
+    writeln!(out, r#"  <graph id="repeats" edgedefault="directed">"#)?;
+
+    for segment in &gfa.segments {
+        let name = segment.name.as_slice();
+        if !neighborhood.contains(name) {
+            continue;
+        }
+        writeln!(out, r#"    <node id="{}">"#, escape(name))?;
+        writeln!(
+            out,
+            r#"      <data key="d0">{}</data>"#,
+            segment.sequence.len()
+        )?;
+        writeln!(
+            out,
+            r#"      <data key="d1">{}</data>"#,
+            repeats.contains(name)
+        )?;
+        writeln!(out, "    </node>")?;
+    }
+
+    for link in links {
+        writeln!(
+            out,
+            r#"    <edge source="{}" target="{}">"#,
+            escape(&link.from_segment),
+            escape(&link.to_segment)
+        )?;
+        writeln!(out, r#"      <data key="d2">{}</data>"#, link.from_orient)?;
+        writeln!(out, r#"      <data key="d3">{}</data>"#, link.to_orient)?;
+        writeln!(
+            out,
+            r#"      <data key="d4">{}</data>"#,
+            escape(&link.overlap)
+        )?;
+        writeln!(out, "    </edge>")?;
+    }
+
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</graphml>")?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Escape XML special characters in a segment ID or attribute value.
+fn escape(text: &[u8]) -> String {
+    let mut escaped = String::new();
+    for c in text.to_str_lossy().chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfa::parser::GFAParser;
+
+    #[test]
+    fn test_graphml_is_well_formed() {
+        let gfa: Gfa = GFAParser::new()
+            .parse_lines(
+                [
+                    "S\tR&1\tACGT",
+                    "S\t<a>\tACGTACGT",
+                    "S\tb\tAC",
+                    "S\tfar\tA",
+                    "L\t<a>\t+\tR&1\t-\t2M",
+                    "L\tR&1\t+\tb\t+\t0M",
+                    "L\tb\t+\tfar\t+\t0M",
+                ]
+                .iter()
+                .map(|l| l.as_bytes()),
+            )
+            .unwrap();
+
+        let mut out = Vec::new();
+        write_graphml(&gfa, &["R&1".to_string()], &mut out).unwrap();
+        let xml = String::from_utf8(out).unwrap();
+
+        assert!(xml.contains(r#"<node id="R&amp;1">"#));
+        assert!(xml.contains(r#"<edge source="&lt;a&gt;" target="R&amp;1">"#));
+        assert!(xml.contains(r#"<data key="d4">2M</data>"#));
+        // `far` is two links away from the repeat
+        assert!(!xml.contains("far"));
+        assert_eq!(xml.matches("<node ").count(), 3);
+        assert_eq!(xml.matches("<edge ").count(), 2);
+
+        // every element that is opened is closed, in order
+        let mut open = Vec::new();
+        for tag in xml.split('<').skip(2) {
+            let tag = tag.split('>').next().unwrap();
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(name.to_string()));
+            } else if !tag.ends_with('/') {
+                open.push(tag.split(' ').next().unwrap().to_string());
+            }
+        }
+        assert!(open.is_empty());
+    }
+}
@@ -1,17 +1,52 @@
 //! This program identifies potential repeat nodes in a GFA graph.
+//!
+//! Exit codes:
+//! - `0`: the run succeeded (and, with `--empty-is-error`, found results)
+//! - `1`: an error occurred (bad input, unreadable file, ...)
+//! - `2`: invalid command line usage
+//! - `3`: the run succeeded but found no candidates/pairs, only with `--empty-is-error`
+//! - `4`: the GAF had no usable alignments, only with `--empty-is-error`
 
-use anyhow::Result;
-use clap::{arg, command, value_parser, ArgMatches};
+use anyhow::{Context, Result};
+use clap::{arg, command, value_parser, ArgAction, ArgMatches, Command};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
 use std::path::PathBuf;
+use std::time::Instant;
 
+mod batch;
+mod cache;
+mod columnar;
+mod compare;
+mod degrees;
 mod gaf;
 mod gfa;
+mod graphml;
+mod manifest;
+mod merge;
+mod parallel;
+mod report;
+mod seq;
+mod simulate;
+mod stats;
+mod summarize;
+mod tidy;
+mod timings;
+mod validate;
+
+/// Exit code for a successful run with an empty result set (see `--empty-is-error`).
+const EXIT_EMPTY: i32 = 3;
+/// Exit code for a GAF without usable alignments (see `--empty-is-error`).
+const EXIT_EMPTY_GAF: i32 = 4;
 
 fn cli() -> ArgMatches {
     command!()
         .arg_required_else_help(true)
         .help_template("{name}: v{version}\n{about}\n\n{all-args}")
         .about("A Bidirected Repeat Path Enumerator")
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
         .arg(arg!(<GFA> "Input file in GFA format.").value_parser(value_parser!(PathBuf)))
         .arg(
             arg!(-r --repeat [REPEAT] "Repeat node size limit")
@@ -29,23 +64,737 @@ fn cli() -> ArgMatches {
                 .default_value("2"),
         )
         .arg(
-            arg!(-g --gaf <GAF> "Input GAF file from GraphAligner.")
+            arg!(--"neighbor-depth" <K> "Check the size of every segment up to K links from a repeat, not only its immediate neighbours.")
+                .value_parser(value_parser!(u64).range(1..))
+                .default_value("1"),
+        )
+        .arg(
+            arg!(--"allow-trivial" "Don't warn when --inout 0 admits segments that are not branch points.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(-v --verbose "Report why segments were rejected on stderr.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(-g --gaf <GAF> "Input GAF file from GraphAligner. Repeat to merge several GAFs, see --combine.")
+                .value_parser(value_parser!(PathBuf))
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(--combine <MODE> "How counts of the same path in several GAFs are merged: summed (pooled data), their max, or their mean (replicates)")
+                .value_parser(["sum", "max", "mean"])
+                .default_value("sum"),
+        )
+        .arg(
+            arg!(--"graph-cache" <PATH> "Reuse the graph index cached at PATH, (re)building it if missing or stale.")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"sizes" <TSV> "Override the GFA's segment sizes with those in TSV, one segment ID and size per line.")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"exclude-nodes" <FILE> "Skip the segment IDs listed in FILE, one per line, in detection and as GAF focal nodes.")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"decision-log" <PATH> "Write every segment examined in detection to PATH as a TSV, with the first threshold it failed or \"passed\".")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"segment-regex" <PATTERN> "Only consider segments whose name matches the regular expression PATTERN, in detection and as GAF focal nodes.")
+                .value_parser(|s: &str| regex::bytes::Regex::new(s)),
+        )
+        .arg(
+            arg!(--"topology-only" "Only print each candidate's degree and neighbour IDs, reading segment lengths and links alone, without sequences or a GAF.")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["gaf", "load-counts", "seq-stats", "inverted", "components", "size-buckets", "json", "graphml", "sub-gfa", "edge-list", "format"]),
+        )
+        .arg(
+            arg!(--"seq-stats" "Add base composition, GC and sequence entropy columns to the candidate table.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--inverted "Add a column flagging structurally inverted repeats.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"only-inverted" "Only use the GAF pairs of structurally inverted repeats (see --inverted) in the pair table, recombination potential and RCI.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"with-neighbors" "Add a column listing each candidate's immediate neighbours as comma-separated oriented IDs.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--structure "Add a column classifying each candidate's local topology as simple-bubble, superbubble, tangle or tip-attached. Implies --canonical-links.")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("graph-cache"),
+        )
+        .arg(
+            arg!(--components "Add a column with the connected component of each candidate.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--component <ID> "Only report candidates in connected component ID (1 is the largest).")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            arg!(--sort <KEY> "Order candidates by decreasing size or by id, instead of GFA order.")
+                .value_parser(["size", "id"]),
+        )
+        .arg(
+            arg!(--"max-candidates" <N> "Keep only the first N candidates in --sort order, reporting how many were suppressed.")
+                .value_parser(value_parser!(usize))
+                .requires("sort"),
+        )
+        .arg(
+            arg!(--"size-buckets" <BP> "Summarize how many candidates fall in each size class, split at a comma-separated list of sizes, e.g. 1000,5000.")
+                .value_parser(value_parser!(usize))
+                .value_delimiter(','),
+        )
+        .arg(
+            arg!(--"bucket-members" "Also list the candidates of each --size-buckets class under its header.")
+                .action(ArgAction::SetTrue)
+                .requires("size-buckets"),
+        )
+        .arg(
+            arg!(--graphml <PATH> "Write the candidates and their neighbouring segments as GraphML to PATH.")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"sub-gfa" <PATH> "Write the candidates, their neighbouring segments and the links between them as GFA to PATH.")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"edge-list" <PATH> "Write the GFA links as a TSV edge list to PATH.")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"edge-list-scope" <SCOPE> "Links written by --edge-list: all of them, or only those touching a candidate.")
+                .value_parser(["all", "candidates"])
+                .default_value("all")
+                .requires("edge-list"),
+        )
+        .arg(
+            arg!(--"min-link-tag" <TAG_MIN> "Ignore links whose integer tag TAG is missing or below MIN, e.g. SR:5.")
+                .value_parser(value_parser!(gfa::LinkTagFilter))
+                .conflicts_with("graph-cache"),
+        )
+        .arg(
+            arg!(--"canonical-links" "Store each link under the segment ends it leaves from, so an edge and its reverse traversal index the same.")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("graph-cache"),
+        )
+        .arg(
+            arg!(--"warn-duplicates" "Warn about duplicate segment names instead of failing.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"multi-focal" "Count a GAF path under every focal node it contains, not just its centre.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"gaf-dialect" <DIALECT> "How the GAF path column is written: standard (>u1>u2<u3), comma (>u1,>u2,<u3), space (>u1 >u2 <u3) or segment-list (u1+,u2+,u3-)")
+                .value_parser(value_parser!(gaf::GafDialect))
+                .default_value("standard"),
+        )
+        .arg(
+            arg!(--"no-revisit" "Drop GAF paths that visit a segment more than once, e.g. >a>a>b, as tandems or mapping artifacts.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"long-reads" "Also count the 3-node windows of GAF paths longer than 3 segments.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--head <N> "Only read the first N lines of each GAF, counted or not, e.g. to check that a new GAF parses.")
+                .alias("first-n-lines")
+                .value_parser(value_parser!(u64).range(1..)),
+        )
+        .arg(
+            arg!(--"max-unmatched-frac" <F> "Fail if more than fraction F of the path centres in the first lines of a GAF are not segments of the GFA.")
+                .value_parser(fraction),
+        )
+        .arg(
+            arg!(--subsample <FRACTION> "Keep each GAF line with probability FRACTION (above 0, at most 1), the same lines for the same --seed.")
+                .value_parser(positive_fraction),
+        )
+        .arg(
+            arg!(--seed <SEED> "Seed for --subsample.")
+                .value_parser(value_parser!(u64))
+                .default_value("1")
+                .requires("subsample"),
+        )
+        .arg(
+            arg!(--focal <NODES> "Use these comma-separated segments as GAF focal nodes instead of the candidates; a + or - suffix (u25+) only counts paths traversing it in that orientation.")
+                .value_delimiter(',')
+                .conflicts_with("query"),
+        )
+        .arg(
+            arg!(--query <SEGMENT> "Only report the pair of paths connecting FLANK_A and FLANK_B through REPEAT.")
+                .num_args(3)
+                .value_names(["FLANK_A", "REPEAT", "FLANK_B"]),
+        )
+        .arg(
+            arg!(--"group-by-tag" <TAG> "Also report the GAF statistics separately for each value of the optional field TAG, e.g. a sample barcode.")
+                .value_parser(gaf_tag),
+        )
+        .arg(
+            arg!(--"gaf-min-flank" <BP> "Skip GAF paths whose flanking segments (besides the focal node) are shorter than BP.")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            arg!(--"dump-counts" <PATH> "Write the raw (repeat, path, count) table from the GAF to PATH.")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"load-counts" <PATH> "Compute the GAF statistics from a previous --dump-counts table instead of a GAF.")
+                .value_parser(value_parser!(PathBuf))
+                .conflicts_with_all(["gaf", "entropy-length-weight"]),
+        )
+        .arg(
+            arg!(--"path-notation" <NOTATION> "How the paths of a --load-counts table are written: arrow (<u28<u25>u27) or pm (u28-u25-u27+).")
+                .value_parser(["arrow", "pm"])
+                .default_value("arrow")
+                .requires("load-counts"),
+        )
+        .arg(
+            arg!(--check "Warn if the path probabilities of a repeat don't sum to 1 in the entropy table, which signals a counting bug. Always on in debug builds.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"entropy-length-weight" "Weight paths by total aligned read length instead of read count in the entropy table.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"entropy-unit" <UNIT> "Compute entropy over oriented paths, or over the unordered flank pairs of each repeat, ignoring orientation.")
+                .value_parser(["path", "flankpair"])
+                .default_value("path"),
+        )
+        .arg(
+            arg!(--"potential-weight" <WEIGHT> "Weight each pair equally in the recombination potential, or by its total coverage.")
+                .value_parser(["equal", "coverage"])
+                .default_value("equal"),
+        )
+        .arg(
+            arg!(--"exclude-single-path" "Leave repeats with a single observed path out of the mean entropy, still listing them in the entropy table.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"n-pairs" "Add an n_pairs column to the entropy table: the reverse-complement pairs each repeat contributes to the pair table.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"min-repeat-cov" <N> "Leave out repeats whose paths total fewer than N reads from every GAF table.")
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            arg!(--"min-path-frac" <F> "Leave out paths read less than F times as often as their repeat's top path (0-1) from every GAF table.")
+                .value_parser(fraction),
+        )
+        .arg(
+            arg!(--"require-both-orientations-observed" "Leave out repeats whose reads only traverse them in one orientation from every GAF table.")
+                .action(ArgAction::SetTrue)
+                .overrides_with("allow-single-orientation"),
+        )
+        .arg(
+            arg!(--"allow-single-orientation" "Keep repeats observed in one orientation (the default), overriding an earlier --require-both-orientations-observed.")
+                .action(ArgAction::SetTrue)
+                .overrides_with("require-both-orientations-observed"),
+        )
+        .arg(
+            arg!(--"no-pairs" "Omit the per-pair table, keeping the recombination potential and RCI.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--multiway "Also report how evenly each repeat's reads are spread over all of its paths, not just reverse-complement pairs.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"merge-shared-paths" <JACCARD> "Also report entropy and RCI for families of repeats whose observed path sets overlap by a Jaccard index above JACCARD (0 to 1).")
+                .value_parser(fraction)
+                .requires("multi-focal"),
+        )
+        .arg(
+            arg!(--"recomb-threshold" <SCORE> "Minimum recomb_score for a pair's reads to count as recombining in the recombination rate")
+                .value_parser(fraction)
+                .default_value("0.5"),
+        )
+        .arg(
+            arg!(--"score-hist" <BINS> "Print a histogram of the pair recombination scores in BINS equal-width bins.")
+                .value_parser(value_parser!(u64).range(1..)),
+        )
+        .arg(
+            arg!(--"emit-flank-seq" <BP> "Append the sequences of path_1's flanks, up to BP bases next to the repeat, to each pair row.")
+                .value_parser(value_parser!(u64).range(1..)),
+        )
+        .arg(
+            arg!(--"flank-distance" "Append to each pair row the fewest links between its flanks that avoid the repeat.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"max-hops" <N> "Give up the --flank-distance search after N links.")
+                .value_parser(value_parser!(usize))
+                .default_value("50")
+                .requires("flank-distance"),
+        )
+        .arg(
+            arg!(--"canonical-pair-key" <FILE> "Pair reverse-complement paths whose flanks are in the same segment family, one family of whitespace-separated IDs per line in FILE.")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"relative-coverage" "Append each pair's coverages as fractions of their sum (rel_1, rel_2), which its recomb_score is based on.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"pvalue" "Append a two-sided binomial test p-value of each pair's coverage split, under equal probability of either path.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"fdr" "Also append the p-values adjusted for multiple testing (Benjamini-Hochberg), as qvalue.")
+                .action(ArgAction::SetTrue)
+                .requires("pvalue"),
+        )
+        .arg(
+            arg!(--"direction" "Label each pair's path_1, and the paths in --split-dir files, as read forward or reverse relative to their reference orientation.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"breakpoints" "Append each pair's mean offset of the reads' first difference within the focal segment, from the GAF cs or ds tags (NA without).")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"reference-orientation" "Display paths in the pair table with their first segment forward, flipping them if needed.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"show-unsupported" "List detected repeats without observed GAF paths in the entropy table, with entropy NA.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"paired-only" "Compute path entropy over only the paths in reverse-complement pairs, as the RCI does.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"rci-denominator" <MODE> "Paths per repeat in the RCI: observed in the GAF, or possible in the graph")
+                .value_parser(["observed", "possible"])
+                .default_value("observed"),
+        )
+        .arg(
+            arg!(--"rci-weight" <WEIGHT> "Average the repeats' RCI terms equally, or weighted by each repeat's share of the total coverage, for comparing samples of different depth.")
+                .value_parser(["equal", "coverage"])
+                .default_value("equal"),
+        )
+        .arg(
+            arg!(--anomaly "Report only the repeats whose observed path entropy deviates from uniform use of the paths the graph allows.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"anomaly-threshold" <DEVIATION> "Flag repeats whose normalized entropy deviates from 1 by more than DEVIATION.")
+                .value_parser(value_parser!(f64))
+                .default_value("0.5")
+                .requires("anomaly"),
+        )
+        .arg(
+            arg!(--"anomaly-z" <Z> "Flag repeats whose deviation is more than Z standard deviations from the mean, instead of --anomaly-threshold.")
+                .value_parser(value_parser!(f64))
+                .requires("anomaly"),
+        )
+        .arg(
+            arg!(--"ref-paths" "Flag GAF paths as following or deviating from the GFA's P line paths.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"path-profile" <PATH> "Write each repeat's observed paths and counts as one sorted, comparable string to PATH.")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--format <FORMAT> "Also write the pair and entropy tables as pairs.parquet and entropy.parquet in the directory PATH (FORMAT: parquet, needs the parquet feature), or the per-repeat statistics as a long repeat/metric/value table to the file PATH (FORMAT: long).")
+                .num_args(2)
+                .value_names(["FORMAT", "PATH"]),
+        )
+        .arg(
+            arg!(--"split-dir" <DIR> "Also write one <repeat_id>.tsv per repeat with its paths, pairs and entropy.")
                 .value_parser(value_parser!(PathBuf)),
         )
+        .arg(
+            arg!(--manifest <PATH> "Write a JSON list of every file this run wrote to PATH.")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--precision <N> "Decimal places for floating-point output")
+                .value_parser(value_parser!(usize))
+                .default_value("3"),
+        )
+        .arg(
+            arg!(--timings "Print the wall-clock time of each phase of the run to stderr.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--json "Write the report as JSON instead of tables.").action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--pretty "Indent JSON output.")
+                .requires("json")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"empty-is-error" "Exit with code 3 if no candidates (or, with --gaf, no path pairs) are found, or 4 if the GAF has no usable alignments.")
+                .action(ArgAction::SetTrue),
+        )
+        .subcommand(simulate_cli())
+        .subcommand(compare_cli())
+        .subcommand(validate_cli())
+        .subcommand(summarize_cli())
+        .subcommand(batch_cli())
+        .subcommand(degrees_cli())
+        .subcommand(merge_cli())
         .get_matches()
 }
 
+/// Parse a fraction between 0 and 1 (inclusive).
+fn fraction(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(f) if (0.0..=1.0).contains(&f) => Ok(f),
+        Ok(_) => Err(format!("{} is not between 0 and 1", s)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Parse a fraction above 0 and at most 1.
+fn positive_fraction(s: &str) -> Result<f64, String> {
+    match fraction(s)? {
+        0.0 => Err(format!("{} is not above 0", s)),
+        f => Ok(f),
+    }
+}
+
+/// Parse a two-character SAM/GAF optional field tag, e.g. `BC`.
+fn gaf_tag(s: &str) -> Result<String, String> {
+    let b = s.as_bytes();
+    if b.len() == 2 && b[0].is_ascii_alphabetic() && b[1].is_ascii_alphanumeric() {
+        Ok(s.to_string())
+    } else {
+        Err(format!("{} is not a two-character tag like BC", s))
+    }
+}
+
+fn simulate_cli() -> Command {
+    Command::new("simulate")
+        .about("Generate a synthetic GFA with planted repeats (and optionally a matching GAF).")
+        .arg(
+            arg!(-o --output <GFA> "Output GFA file [default: stdout]")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(-g --gaf <GAF> "Also write a GAF of planted 3-node paths.")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--repeats <N> "Number of planted repeat nodes")
+                .value_parser(value_parser!(usize))
+                .default_value("5"),
+        )
+        .arg(
+            arg!(--"repeat-size" <BP> "Length of each repeat node")
+                .value_parser(value_parser!(usize))
+                .default_value("1000"),
+        )
+        .arg(
+            arg!(--degree <N> "In/out degree of each repeat node")
+                .value_parser(value_parser!(usize))
+                .default_value("2"),
+        )
+        .arg(
+            arg!(--"flank-size" <BP> "Length of each neighbouring node")
+                .value_parser(value_parser!(usize))
+                .default_value("20000"),
+        )
+        .arg(
+            arg!(--segments <N> "Number of background (non-repeat) segments")
+                .value_parser(value_parser!(usize))
+                .default_value("0"),
+        )
+        .arg(
+            arg!(--"segment-size" <BP> "Length of each background segment")
+                .value_parser(value_parser!(usize))
+                .default_value("20000"),
+        )
+        .arg(
+            arg!(--coverage <N> "Reads per planted path, and per its reverse, in the GAF")
+                .value_parser(value_parser!(usize))
+                .default_value("10"),
+        )
+        .arg(
+            arg!(--seed <SEED> "Seed for the sequence generator")
+                .value_parser(value_parser!(u64))
+                .default_value("1"),
+        )
+}
+
+fn compare_cli() -> Command {
+    Command::new("compare")
+        .about("Compare the per-repeat path usage of two --dump-counts tables.")
+        .arg(
+            arg!(<FIRST> "Counts table of the first (e.g. untreated) run")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(<SECOND> "Counts table of the second (e.g. treated) run")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--precision <N> "Decimal places for floating-point output")
+                .value_parser(value_parser!(usize))
+                .default_value("3"),
+        )
+}
+
+fn summarize_cli() -> Command {
+    Command::new("summarize")
+        .about("Print the mean and total entropy and RCI of a --dump-counts table, without reparsing the GAF.")
+        .arg(
+            arg!(<COUNTS> "Counts table written by --dump-counts")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--precision <N> "Decimal places for floating-point output")
+                .value_parser(value_parser!(usize))
+                .default_value("3"),
+        )
+}
+
+fn merge_cli() -> Command {
+    Command::new("merge")
+        .about("Combine the entropy tables of several reports into a repeat-by-sample matrix.")
+        .arg(
+            arg!(<REPORTS> ... "Reports (stdout of a GAF run) to combine, one per sample")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(arg!(--names <NAMES> "Comma-separated sample names, in report order, instead of the report file names"))
+}
+
+fn batch_cli() -> Command {
+    Command::new("batch")
+        .about("Run the GAF stage on every (GFA, GAF, nodes) row of a TSV manifest and print one summary line per sample.")
+        .arg(
+            arg!(<MANIFEST> "TSV of gfa_path, gaf_path and nodes_path (focal node IDs, one per line) columns")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!([ARGS] ... "Options shared by every sample, after --, e.g. -- --precision 6")
+                .last(true),
+        )
+}
+
+fn degrees_cli() -> Command {
+    Command::new("degrees")
+        .about("List every segment with its in-, out- and total degree and size, without any repeat thresholds.")
+        .arg(arg!(<GFA> "Input file in GFA format.").value_parser(value_parser!(PathBuf)))
+        .arg(
+            arg!(--sort <KEY> "Order segments by decreasing degree or size, by id, or in GFA order.")
+                .value_parser(["degree", "size", "id", "gfa"])
+                .default_value("degree"),
+        )
+        .arg(
+            arg!(--"warn-duplicates" "Warn about duplicate segment names instead of failing.")
+                .action(ArgAction::SetTrue),
+        )
+}
+
+fn validate_cli() -> Command {
+    Command::new("validate")
+        .about("Check a GFA for malformed lines, duplicate segments, dangling references and LN mismatches.")
+        .arg(arg!(<GFA> "Input file in GFA format.").value_parser(value_parser!(PathBuf)))
+}
+
 fn main() -> Result<()> {
     let args = cli();
-    let gaf = args.get_one::<PathBuf>("gaf").cloned();
+
+    match args.subcommand() {
+        Some(("simulate", sub)) => return simulate::run(sub),
+        Some(("compare", sub)) => return compare::run(sub),
+        Some(("validate", sub)) => return validate::run(sub),
+        Some(("summarize", sub)) => return summarize::run(sub),
+        Some(("batch", sub)) => return batch::run(sub),
+        Some(("degrees", sub)) => return degrees::run(sub),
+        Some(("merge", sub)) => return merge::run(sub),
+        _ => {}
+    }
+
+    let (parquet_dir, long_table) = match args.get_many::<String>("format") {
+        Some(format) => {
+            let format: Vec<&String> = format.collect();
+            match format[0].as_str() {
+                "parquet" => {
+                    columnar::check_support()?;
+                    (Some(PathBuf::from(format[1])), None)
+                }
+                "long" => (None, Some(PathBuf::from(format[1]))),
+                _ => anyhow::bail!(
+                    "Unsupported --format {}, expected parquet or long",
+                    format[0]
+                ),
+            }
+        }
+        None => (None, None),
+    };
+    let gafs: Vec<PathBuf> = args
+        .get_many::<PathBuf>("gaf")
+        .map(|gafs| gafs.cloned().collect())
+        .unwrap_or_default();
+    let load_counts = args.get_one::<PathBuf>("load-counts");
+    let empty_is_error = args.get_flag("empty-is-error");
+    let json = args.get_flag("json");
 
     // print nodes
-    let nodes = gfa::nodes(args, gaf.is_some())?;
+    let mut manifest = manifest::Manifest::default();
+    let mut timings = timings::Timings::default();
+    let (candidates, index) = gfa::nodes(
+        &args,
+        !gafs.is_empty() || load_counts.is_some() || json || args.get_flag("topology-only"),
+        &mut manifest,
+        &mut timings,
+    )?;
+    if args.get_flag("topology-only") && !candidates.is_empty() {
+        gfa::write_topology(&index, &candidates, io::stdout().lock())?;
+    }
+    let names: Vec<String> = candidates.iter().map(|c| c.id.clone()).collect();
 
-    // optionally print paths from the GAF
-    match nodes {
-        Some(n) => gaf::count_gaf_paths(gaf.unwrap(), n),
+    // graph exports need the full GFA, which detection may have skipped (--graph-cache)
+    let graphml = args.get_one::<PathBuf>("graphml");
+    let sub_gfa = args.get_one::<PathBuf>("sub-gfa");
+    let edge_list = args.get_one::<PathBuf>("edge-list");
+    if graphml.is_some() || sub_gfa.is_some() || edge_list.is_some() {
+        let start = Instant::now();
+        let gfa_file = args.get_one::<PathBuf>("GFA").expect("GFA required");
+        let graph = gfa::load_gfa(gfa_file)?;
+        if let Some(path) = graphml {
+            let file = File::create(path).with_context(|| {
+                format!("Failed to create GraphML file: {:?}", path.as_os_str())
+            })?;
+            graphml::write_graphml(&graph, &names, BufWriter::new(file))?;
+            manifest.add("graphml", path);
+        }
+        if let Some(path) = sub_gfa {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create GFA file: {:?}", path.as_os_str()))?;
+            gfa::write_sub_gfa(&graph, &names, BufWriter::new(file))?;
+            manifest.add("sub_gfa", path);
+        }
+        if let Some(path) = edge_list {
+            let file = File::create(path).with_context(|| {
+                format!("Failed to create edge list file: {:?}", path.as_os_str())
+            })?;
+            let scope = args
+                .get_one::<String>("edge-list-scope")
+                .map(String::as_str);
+            let repeats = (scope == Some("candidates")).then_some(names.as_slice());
+            gfa::write_edge_list(&graph, repeats, BufWriter::new(file))?;
+            manifest.add("edge_list", path);
+        }
+        timings.record("graph export", start);
+    }
+
+    // optionally print paths from the GAF, or from a previous run's counts
+    let names = match (
+        args.get_many::<String>("query"),
+        args.get_many::<String>("focal"),
+    ) {
+        // the queried repeat is used whether or not it was detected
+        (Some(query), _) => vec![query.collect::<Vec<_>>()[1].clone()],
+        (None, Some(focal)) => gaf::parse_focal_specs(&focal.cloned().collect::<Vec<_>>()).0,
+        (None, None) => names,
+    };
+    // the structural inverted repeats, shared with the GAF stage
+    let inverted: Option<HashSet<String>> = args.get_flag("only-inverted").then(|| {
+        names
+            .iter()
+            .filter(|name| gfa::is_inverted_repeat(name.as_bytes(), &index))
+            .cloned()
+            .collect()
+    });
+    if inverted
+        .as_ref()
+        .is_some_and(|inverted| inverted.is_empty())
+    {
+        eprintln!("Note: none of the focal repeats is structurally inverted (--only-inverted)");
+    }
+    let gaf_report = match (gafs.is_empty(), load_counts) {
+        (false, _) => Some(gaf::count_gaf_paths(
+            &gafs,
+            names,
+            &index,
+            inverted.as_ref(),
+            &args,
+            &mut manifest,
+            &mut timings,
+        )?),
+        (true, Some(counts)) => Some(gaf::load_gaf_counts(
+            counts,
+            names,
+            &index,
+            inverted.as_ref(),
+            &args,
+            &mut manifest,
+            &mut timings,
+        )?),
         // end here
-        None => Ok(()),
+        (true, None) => None,
+    };
+
+    if let (Some(dir), Some(report)) = (&parquet_dir, &gaf_report) {
+        columnar::write_parquet(dir, report, &mut manifest)?;
+    }
+    if let (Some(path), Some(report)) = (&long_table, &gaf_report) {
+        let precision = *args.get_one::<usize>("precision").unwrap();
+        tidy::write_long_table(path, report, precision, &mut manifest)?;
+    }
+
+    let empty_gaf = gaf_report.as_ref().is_some_and(|r| r.alignments == Some(0));
+    let found = match &gaf_report {
+        Some(r) => r.pairs.len(),
+        None => candidates.len(),
+    };
+
+    if json {
+        let mut gaf_report = gaf_report;
+        if args.get_flag("no-pairs") {
+            if let Some(r) = gaf_report.as_mut() {
+                r.pairs.clear();
+            }
+        }
+        let report = report::Report {
+            candidates,
+            gaf: gaf_report,
+        };
+        let precision = *args.get_one::<usize>("precision").unwrap();
+        report::write_json(
+            &report,
+            args.get_flag("pretty"),
+            precision,
+            io::stdout().lock(),
+        )?;
+    }
+
+    if let Some(path) = args.get_one::<PathBuf>("manifest") {
+        manifest.write(path)?;
+    }
+
+    if args.get_flag("timings") {
+        timings.write(io::stderr().lock())?;
     }
+
+    if empty_gaf && empty_is_error {
+        io::stdout().flush()?;
+        std::process::exit(EXIT_EMPTY_GAF);
+    }
+    if found == 0 && empty_is_error {
+        io::stdout().flush()?;
+        std::process::exit(EXIT_EMPTY);
+    }
+
+    Ok(())
 }
@@ -0,0 +1,77 @@
+//! A machine-readable list of the files a run wrote (`--manifest`), so pipelines
+//! can declare their outputs precisely.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// A file written by the run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Artifact {
+    /// What the file holds, e.g. `per_repeat` or `counts`.
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    /// The repeat a `per_repeat` file is about.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat: Option<String>,
+    pub path: PathBuf,
+}
+
+/// The files written so far, in the order they were written.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Manifest {
+    pub files: Vec<Artifact>,
+}
+
+impl Manifest {
+    /// Record that `path` was written.
+    pub fn add(&mut self, kind: &'static str, path: &Path) {
+        self.files.push(Artifact {
+            kind,
+            repeat: None,
+            path: path.to_path_buf(),
+        });
+    }
+
+    /// Record that the per-repeat file `path` for `repeat` was written.
+    pub fn add_repeat(&mut self, repeat: &str, path: &Path) {
+        self.files.push(Artifact {
+            kind: "per_repeat",
+            repeat: Some(repeat.to_string()),
+            path: path.to_path_buf(),
+        });
+    }
+
+    /// Write the manifest as indented JSON to `path`.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create manifest: {:?}", path.as_os_str()))?;
+        let mut out = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut out, self)?;
+        writeln!(out)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_json() {
+        let mut manifest = Manifest::default();
+        manifest.add("counts", Path::new("out/counts.tsv"));
+        manifest.add_repeat("u25", Path::new("out/split/u25.tsv"));
+
+        assert_eq!(
+            serde_json::to_string(&manifest).unwrap(),
+            concat!(
+                r#"{"files":[{"type":"counts","path":"out/counts.tsv"},"#,
+                r#"{"type":"per_repeat","repeat":"u25","path":"out/split/u25.tsv"}]}"#
+            )
+        );
+    }
+}
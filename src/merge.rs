@@ -0,0 +1,185 @@
+//! The `merge` subcommand: combine the entropy tables of several runs' reports
+//! into one repeat-by-sample matrix for cross-sample comparison.
+
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// The entropy of each repeat in one sample, as written in its report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleEntropy {
+    pub sample: String,
+    pub entropies: BTreeMap<String, String>,
+}
+
+/// Run the `merge` subcommand.
+pub fn run(args: &ArgMatches) -> Result<()> {
+    let reports: Vec<&PathBuf> = args
+        .get_many::<PathBuf>("REPORTS")
+        .expect("REPORTS required")
+        .collect();
+    let names = sample_names(&reports, args.get_one::<String>("names"))?;
+    let samples = reports
+        .iter()
+        .zip(names)
+        .map(|(path, sample)| {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read report: {:?}", path.as_os_str()))?;
+            let entropies = read_entropy_table(&text)
+                .with_context(|| format!("Malformed report: {:?}", path.as_os_str()))?;
+            Ok(SampleEntropy { sample, entropies })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    write_matrix(&samples, io::stdout().lock())
+}
+
+/// The sample names: the comma-separated `names` if given, or else the report file
+/// names without their extension. Names must be unique.
+fn sample_names(reports: &[&PathBuf], names: Option<&String>) -> Result<Vec<String>> {
+    let names: Vec<String> = match names {
+        Some(names) => {
+            let names: Vec<String> = names.split(',').map(str::to_string).collect();
+            if names.len() != reports.len() {
+                anyhow::bail!(
+                    "--names lists {} sample(s) for {} report(s)",
+                    names.len(),
+                    reports.len()
+                );
+            }
+            names
+        }
+        None => reports
+            .iter()
+            .map(|path| {
+                path.file_stem()
+                    .unwrap_or(path.as_os_str())
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect(),
+    };
+    let mut seen = HashSet::new();
+    if let Some(duplicate) = names.iter().find(|name| !seen.insert(*name)) {
+        anyhow::bail!(
+            "sample name {} is used twice; name the samples with --names",
+            duplicate
+        );
+    }
+    Ok(names)
+}
+
+/// The `entropy` column of the entropy table in the report `text`, by repeat, with
+/// values kept as written (`NA` included). The table starts at its
+/// `repeat_node\tpath_count\tentropy` header and ends at the next blank line. With
+/// a `focal_pos` column (`--multi-focal`), repeats are named `<repeat>:<focal_pos>`.
+fn read_entropy_table(text: &str) -> Result<BTreeMap<String, String>> {
+    let mut lines = text.lines().map(|line| line.trim_end_matches('\r'));
+    let header = lines
+        .find(|line| line.starts_with("repeat_node\tpath_count\tentropy\t"))
+        .context("no entropy table (repeat_node, path_count, entropy header)")?;
+    let columns: Vec<&str> = header.split('\t').collect();
+    let focal_pos = columns.iter().position(|c| *c == "focal_pos");
+
+    let mut entropies = BTreeMap::new();
+    for line in lines.take_while(|line| !line.is_empty()) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != columns.len() {
+            anyhow::bail!(
+                "entropy row with {} columns, expected {}: {}",
+                fields.len(),
+                columns.len(),
+                line
+            );
+        }
+        let repeat = match focal_pos {
+            Some(i) => format!("{}:{}", fields[0], fields[i]),
+            None => fields[0].to_string(),
+        };
+        entropies.insert(repeat, fields[2].to_string());
+    }
+    Ok(entropies)
+}
+
+/// Write a matrix of the repeats of every sample, sorted, by sample, in the given
+/// order. Repeats missing from a sample are `NA`.
+pub fn write_matrix<W: Write>(samples: &[SampleEntropy], mut out: W) -> Result<()> {
+    let names: Vec<&str> = samples.iter().map(|s| s.sample.as_str()).collect();
+    writeln!(out, "repeat_node\t{}", names.join("\t"))?;
+    let repeats: BTreeSet<&String> = samples.iter().flat_map(|s| s.entropies.keys()).collect();
+    for repeat in repeats {
+        let row: Vec<&str> = samples
+            .iter()
+            .map(|s| s.entropies.get(repeat).map_or("NA", String::as_str))
+            .collect();
+        writeln!(out, "{}\t{}", repeat, row.join("\t"))?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = "path_1\tcov_1\tpath_2\tcov_2\trecomb_score\n\
+        <u67<u66>u65\t192\t<u65>u66>u67\t180\t0.968\n\
+        \n\
+        Recombination potential: 0.968\n\
+        RCI: 0.968\n\
+        \n\
+        repeat_node\tpath_count\tentropy\tfwd_cov\trev_cov\ttop_path\ttop_path_frac\n\
+        u66\t2\t0.999\t180\t192\t<u67<u66>u65\t0.516\n\
+        u69\t0\tNA\t0\t0\tNA\tNA\n\
+        \n\
+        Mean entropy: 0.999\n";
+
+    #[test]
+    fn test_read_entropy_table() {
+        let entropies = read_entropy_table(REPORT).unwrap();
+        assert_eq!(entropies.len(), 2);
+        assert_eq!(entropies["u66"], "0.999");
+        assert_eq!(entropies["u69"], "NA");
+
+        assert!(read_entropy_table("Recombination potential: 0\n").is_err());
+    }
+
+    #[test]
+    fn test_write_matrix() {
+        let sample = |sample: &str, rows: &[(&str, &str)]| SampleEntropy {
+            sample: sample.to_string(),
+            entropies: rows
+                .iter()
+                .map(|(r, h)| (r.to_string(), h.to_string()))
+                .collect(),
+        };
+        let samples = [
+            sample("a", &[("u66", "0.999"), ("u69", "1.500")]),
+            sample("b", &[("u70", "0.500"), ("u66", "0.900")]),
+        ];
+        let mut out = Vec::new();
+        write_matrix(&samples, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "repeat_node\ta\tb\n\
+             u66\t0.999\t0.900\n\
+             u69\t1.500\tNA\n\
+             u70\tNA\t0.500\n"
+        );
+    }
+
+    #[test]
+    fn test_sample_names() {
+        let paths = [PathBuf::from("runs/s1.tsv"), PathBuf::from("other/s1.tsv")];
+        let reports: Vec<&PathBuf> = paths.iter().collect();
+        assert_eq!(sample_names(&reports[..1], None).unwrap(), ["s1"]);
+        // both named s1 after their file
+        assert!(sample_names(&reports, None).is_err());
+        let names = "x,y".to_string();
+        assert_eq!(sample_names(&reports, Some(&names)).unwrap(), ["x", "y"]);
+        let names = "x".to_string();
+        assert!(sample_names(&reports, Some(&names)).is_err());
+    }
+}
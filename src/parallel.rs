@@ -0,0 +1,26 @@
+//! Data parallelism for the GAF stage, on all cores with the `parallel` cargo
+//! feature and serially otherwise.
+//!
+//! Callers always go through [`map`], so both builds run the same algorithm and
+//! only differ in whether the items are processed concurrently.
+
+/// Apply `f` to every item, returning the results in the order of `items`.
+#[cfg(feature = "parallel")]
+pub fn map<T, U, F>(items: &[T], f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> U + Sync + Send,
+{
+    use rayon::prelude::*;
+
+    items.par_iter().map(f).collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn map<T, U, F>(items: &[T], f: F) -> Vec<U>
+where
+    F: Fn(&T) -> U,
+{
+    items.iter().map(f).collect()
+}
@@ -0,0 +1,165 @@
+//! Machine-readable (JSON) reports.
+//!
+//! Reports are plain structs, so keys are always written in field declaration
+//! order, and floating-point values are rounded to the same precision as the
+//! text tables (`--precision`) to keep reports diffable across runs.
+
+use anyhow::Result;
+use serde::{Serialize, Serializer};
+use std::cell::Cell;
+use std::io::Write;
+
+use crate::gaf::GafReport;
+use crate::gfa::Candidate;
+
+thread_local! {
+    /// Decimal places for floats in the report being written, set by [`write_json`].
+    static PRECISION: Cell<usize> = const { Cell::new(3) };
+}
+
+/// The full report of a run.
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub candidates: Vec<Candidate>,
+    /// Only present when a GAF was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gaf: Option<GafReport>,
+}
+
+/// Write `report` as JSON, indented if `pretty`, followed by a newline. Floats
+/// are rounded to `precision` decimal places.
+pub fn write_json<W: Write>(
+    report: &Report,
+    pretty: bool,
+    precision: usize,
+    mut out: W,
+) -> Result<()> {
+    PRECISION.with(|p| p.set(precision));
+    if pretty {
+        serde_json::to_writer_pretty(&mut out, report)?;
+    } else {
+        serde_json::to_writer(&mut out, report)?;
+    }
+    writeln!(out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Serialize a float rounded to the precision given to [`write_json`].
+pub fn round<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    let scale = 10f64.powi(PRECISION.with(|p| p.get()) as i32);
+    serializer.serialize_f64((value * scale).round() / scale)
+}
+
+/// Like [`round`], for an optional float, which is `null` if missing.
+pub fn round_option<S: Serializer>(value: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => round(value, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gaf::{EntropyRecord, PairRecord};
+
+    #[test]
+    fn test_json_key_order_and_precision() {
+        let report = Report {
+            candidates: vec![Candidate {
+                id: "u66".into(),
+                size: 3130,
+                inverted: None,
+                component: None,
+                neighbors: None,
+                structure: None,
+            }],
+            gaf: Some(GafReport {
+                pairs: vec![PairRecord {
+                    path_1: "<u67<u66>u65".into(),
+                    cov_1: 192,
+                    path_2: "<u65>u66>u67".into(),
+                    cov_2: 180,
+                    recomb_score: 0.967741935483871,
+                    rel_1: None,
+                    rel_2: None,
+                    pvalue: None,
+                    qvalue: None,
+                    direction: None,
+                    breakpoint: None,
+                    focal_node: None,
+                    focal_pos: None,
+                    left_flank: None,
+                    right_flank: None,
+                    flank_distance: None,
+                }],
+                recombination_potential: 0.967741935483871,
+                rci: 0.967741935483871,
+                recombination_rate: 1.0,
+                repeats: vec![EntropyRecord {
+                    repeat_node: "u66".into(),
+                    path_count: 2,
+                    entropy: Some(0.9992492479956566),
+                    fwd_cov: 180,
+                    rev_cov: 192,
+                    top_path: Some("<u67<u66>u65".into()),
+                    top_path_frac: Some(192.0 / 372.0),
+                    focal_pos: None,
+                    n_pairs: None,
+                }],
+                mean_entropy: 0.9992492479956566,
+                total_entropy: 0.9992492479956566,
+                reference: None,
+                score_histogram: None,
+                source_lengths: None,
+                multiway: None,
+                anomalies: None,
+                repeat_families: None,
+                family_rci: None,
+                tag_groups: None,
+                alignments: None,
+            }),
+        };
+
+        let mut out = Vec::new();
+        write_json(&report, false, 3, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                r#"{"candidates":[{"id":"u66","size":3130}],"#,
+                r#""gaf":{"pairs":[{"path_1":"<u67<u66>u65","cov_1":192,"path_2":"<u65>u66>u67","cov_2":180,"recomb_score":0.968}],"#,
+                r#""recombination_potential":0.968,"rci":0.968,"recombination_rate":1.0,"#,
+                r#""repeats":[{"repeat_node":"u66","path_count":2,"entropy":0.999,"fwd_cov":180,"rev_cov":192,"#,
+                r#""top_path":"<u67<u66>u65","top_path_frac":0.516}],"#,
+                r#""mean_entropy":0.999,"total_entropy":0.999}}"#,
+                "\n"
+            )
+        );
+
+        let mut pretty = Vec::new();
+        write_json(&report, true, 3, &mut pretty).unwrap();
+        assert!(String::from_utf8(pretty)
+            .unwrap()
+            .starts_with("{\n  \"candidates\": [\n    {\n      \"id\": \"u66\","));
+    }
+
+    #[test]
+    fn test_json_precision() {
+        let report = Report {
+            candidates: vec![],
+            gaf: Some(GafReport {
+                rci: 2.0 / 3.0,
+                ..Default::default()
+            }),
+        };
+
+        let mut out = Vec::new();
+        write_json(&report, false, 6, &mut out).unwrap();
+
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains(r#""rci":0.666667,"#));
+    }
+}
@@ -0,0 +1,185 @@
+//! Base composition of segment sequences.
+//!
+//! IUPAC ambiguity codes are classified by the set of bases they stand for, the
+//! same way for GC content, sequence entropy and reverse complements: `S` is G or
+//! C, `W` is A or T, `R` is A or G, and so on, while `N` is unknown and ignored.
+
+use std::f64::consts::LN_2;
+
+/// The IUPAC nucleotide codes, indexed by the set of bases they stand for as a
+/// bit mask of A (1), C (2), G (4) and T (8).
+const IUPAC_CODES: &[u8; 16] = b"-ACMGRSVTWYHKDBN";
+
+/// The bases the IUPAC code `base` stands for, as a mask indexing [`IUPAC_CODES`].
+/// Case-insensitive, with `U` as `T`. `None` for gaps and anything else.
+fn iupac_mask(base: u8) -> Option<u8> {
+    let base = match base.to_ascii_uppercase() {
+        b'U' => b'T',
+        b'-' => return None,
+        base => base,
+    };
+    IUPAC_CODES
+        .iter()
+        .position(|&code| code == base)
+        .map(|mask| mask as u8)
+}
+
+/// Case-insensitive base counts of a sequence.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Composition {
+    pub a: usize,
+    pub c: usize,
+    pub g: usize,
+    pub t: usize,
+    pub n: usize,
+    /// Strong (`S`, G or C) ambiguity codes.
+    pub strong: usize,
+    /// Weak (`W`, A or T) ambiguity codes.
+    pub weak: usize,
+    /// Other IUPAC ambiguity codes (`R Y K M B D H V`).
+    pub ambiguous: usize,
+    /// Gaps and anything that is not an IUPAC nucleotide code.
+    pub other: usize,
+    /// Expected `ACGT` counts, with each ambiguity code other than `N` split evenly
+    /// between the bases it stands for, e.g. `R` adds half an A and half a G.
+    pub expected: [f64; 4],
+}
+
+impl Composition {
+    /// Count the bases of `seq`. `U` is counted as `T`.
+    pub fn new(seq: &[u8]) -> Self {
+        let mut comp = Self::default();
+        for &base in seq {
+            let Some(mask) = iupac_mask(base) else {
+                comp.other += 1;
+                continue;
+            };
+            match mask {
+                1 => comp.a += 1,
+                2 => comp.c += 1,
+                4 => comp.g += 1,
+                8 => comp.t += 1,
+                15 => {
+                    comp.n += 1;
+                    continue;
+                }
+                6 => comp.strong += 1,
+                9 => comp.weak += 1,
+                _ => comp.ambiguous += 1,
+            }
+            let share = 1.0 / mask.count_ones() as f64;
+            for (i, expected) in comp.expected.iter_mut().enumerate() {
+                if mask & (1 << i) != 0 {
+                    *expected += share;
+                }
+            }
+        }
+        comp
+    }
+
+    /// All IUPAC ambiguity codes other than `N`.
+    pub fn ambiguity_codes(&self) -> usize {
+        self.strong + self.weak + self.ambiguous
+    }
+
+    /// GC fraction of the [`expected`](Self::expected) bases, so ambiguity codes
+    /// count fractionally (`S` as 1, `R` as 1/2, `B` as 2/3, `W` as 0), or `None` if
+    /// there are no bases other than `N`.
+    pub fn gc(&self) -> Option<f64> {
+        let [a, c, g, t] = self.expected;
+        let known = a + c + g + t;
+        (known > 0.0).then(|| (c + g) / known)
+    }
+
+    /// Shannon entropy (bits, 0 to 2) of the [`expected`](Self::expected) `ACGT`
+    /// composition. Low values flag low-complexity sequence such as satellites and
+    /// homopolymers.
+    pub fn entropy(&self) -> f64 {
+        let total: f64 = self.expected.iter().sum();
+        if total == 0.0 {
+            return 0.0;
+        }
+        let mut entropy = 0.0;
+        for count in self.expected {
+            let p = count / total;
+            if p > 0.0 {
+                entropy -= p * (p.ln() / LN_2);
+            }
+        }
+        entropy
+    }
+}
+
+/// Reverse complement of `seq`, keeping case. IUPAC ambiguity codes are
+/// complemented too (`R` to `Y`, while `S`, `W` and `N` are their own complement),
+/// and anything else is kept as is.
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|&base| {
+            let Some(mask) = iupac_mask(base) else {
+                return base;
+            };
+            // swap A with T and C with G
+            let mask = (mask & 1) << 3 | (mask & 8) >> 3 | (mask & 2) << 1 | (mask & 4) >> 1;
+            let complement = IUPAC_CODES[mask as usize];
+            if base.is_ascii_lowercase() {
+                complement.to_ascii_lowercase()
+            } else {
+                complement
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reverse_complement() {
+        assert_eq!(reverse_complement(b"AACGTN"), b"NACGTT");
+        assert_eq!(reverse_complement(b"acgRYs"), b"sRYcgt");
+        assert_eq!(reverse_complement(b""), b"");
+        assert_eq!(reverse_complement(b"SWNKMBDHVU"), b"ABDHVKMNWS");
+    }
+
+    #[test]
+    fn test_homopolymer_vs_mixed_entropy() {
+        let homopolymer = Composition::new(b"AAAAAAAAAAAAAAAA");
+        assert_eq!(homopolymer.entropy(), 0.0);
+        assert_eq!(homopolymer.gc(), Some(0.0));
+
+        let mixed = Composition::new(b"ACGTacgtACGTacgt");
+        assert!((mixed.entropy() - 2.0).abs() < 1e-9);
+        assert_eq!(mixed.gc(), Some(0.5));
+    }
+
+    #[test]
+    fn test_iupac_counts() {
+        let comp = Composition::new(b"ACGTNnSWRYkm-");
+        assert_eq!((comp.a, comp.c, comp.g, comp.t), (1, 1, 1, 1));
+        assert_eq!(comp.n, 2);
+        assert_eq!(comp.ambiguity_codes(), 6);
+        assert_eq!(comp.other, 1);
+        // every code but N counts, the two-base codes half to each base
+        assert_eq!(comp.expected, [2.5; 4]);
+        assert_eq!(comp.gc(), Some(0.5));
+    }
+
+    #[test]
+    fn test_iupac_gc_fraction() {
+        // R, Y: 1/2 GC each, S: 1, W: 0, N: not counted
+        let comp = Composition::new(b"RYSSWNN");
+        assert_eq!(comp.n, 2);
+        assert!((comp.gc().unwrap() - 3.0 / 5.0).abs() < 1e-9);
+        // B is C, G or T, D is A, G or T
+        let comp = Composition::new(b"BD");
+        assert!((comp.gc().unwrap() - 0.5).abs() < 1e-9);
+        assert_eq!(Composition::new(b"NNN").gc(), None);
+        // ambiguity codes split evenly over their bases, so R and Y as A, C, G, T
+        let comp = Composition::new(b"RYRY");
+        assert!((comp.entropy() - 2.0).abs() < 1e-9);
+        assert_eq!(Composition::new(b"SSSS").entropy(), 1.0);
+    }
+}
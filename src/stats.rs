@@ -0,0 +1,113 @@
+//! Significance of the coverage split of a reverse-complement pair (`--pvalue`),
+//! and Benjamini-Hochberg correction across the pairs (`--fdr`).
+
+/// Above this many reads the binomial test uses the normal approximation.
+const EXACT_MAX_READS: u64 = 1000;
+
+/// Two-sided p-value of seeing `k` of `n` reads on one path of a pair, under the
+/// null that both paths are equally likely (a binomial with p = 0.5).
+///
+/// Exact up to [`EXACT_MAX_READS`] reads, and from the normal approximation with a
+/// continuity correction above, where the two agree closely. 1 without reads.
+pub fn binomial_pvalue(k: u64, n: u64) -> f64 {
+    if n == 0 {
+        return 1.0;
+    }
+    // the null is symmetric, so double the tail of the smaller count
+    let tail = k.min(n - k);
+    let p = if n <= EXACT_MAX_READS {
+        exact_lower_tail(tail, n)
+    } else {
+        let mean = n as f64 / 2.0;
+        let sd = (n as f64).sqrt() / 2.0;
+        let z = (tail as f64 + 0.5 - mean) / sd;
+        0.5 * erfc(-z / std::f64::consts::SQRT_2)
+    };
+    (2.0 * p).min(1.0)
+}
+
+/// P(X <= k) for X ~ Binomial(n, 0.5), summing the terms in log space so that
+/// 0.5^n doesn't underflow.
+fn exact_lower_tail(k: u64, n: u64) -> f64 {
+    let mut log_term = -(n as f64) * std::f64::consts::LN_2;
+    let mut sum = 0.0;
+    for i in 0..=k {
+        sum += log_term.exp();
+        log_term += ((n - i) as f64 / (i + 1) as f64).ln();
+    }
+    sum
+}
+
+/// The complementary error function, to a relative error below 1.2e-7
+/// (Numerical Recipes' `erfcc`).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let r = t * poly.exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+/// Benjamini-Hochberg adjusted p-values (q-values), in the order of `pvalues`.
+pub fn benjamini_hochberg(pvalues: &[f64]) -> Vec<f64> {
+    let m = pvalues.len();
+    let mut order: Vec<usize> = (0..m).collect();
+    order.sort_by(|&a, &b| pvalues[b].total_cmp(&pvalues[a]));
+
+    // from the largest p-value down, keeping the adjusted values monotone
+    let mut adjusted = vec![0.0; m];
+    let mut running = 1.0f64;
+    for (i, &index) in order.iter().enumerate() {
+        let rank = m - i;
+        running = running.min(pvalues[index] * m as f64 / rank as f64);
+        adjusted[index] = running;
+    }
+    adjusted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binomial_pvalue() {
+        // exact: P(X <= 1) for n = 10 is 11/1024, doubled
+        assert!((binomial_pvalue(1, 10) - 22.0 / 1024.0).abs() < 1e-12);
+        assert!((binomial_pvalue(9, 10) - 22.0 / 1024.0).abs() < 1e-12);
+        assert_eq!(binomial_pvalue(5, 10), 1.0);
+        assert_eq!(binomial_pvalue(0, 0), 1.0);
+
+        // balanced deep pairs are not significant, skewed ones are
+        assert!(binomial_pvalue(5000, 10000) > 0.9);
+        assert!(binomial_pvalue(500, 990) > 0.5);
+        assert!(binomial_pvalue(3000, 10000) < 1e-12);
+        assert!(binomial_pvalue(300, 1000) < 1e-12);
+
+        // the approximation picks up where the exact test stops
+        let exact = binomial_pvalue(470, 1000);
+        let approx = binomial_pvalue(471, 1002);
+        assert!((exact - approx).abs() < 0.01, "{} vs {}", exact, approx);
+    }
+
+    #[test]
+    fn test_benjamini_hochberg() {
+        let q = benjamini_hochberg(&[0.01, 0.04, 0.03, 0.5]);
+        // 0.03 alone would be adjusted to 0.03 * 4 / 2, but is capped by 0.04's
+        let expected = [0.04, 0.04 * 4.0 / 3.0, 0.04 * 4.0 / 3.0, 0.5];
+        for (q, e) in q.iter().zip(expected) {
+            assert!((q - e).abs() < 1e-12, "{:?}", q);
+        }
+        assert!(benjamini_hochberg(&[]).is_empty());
+    }
+}
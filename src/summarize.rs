@@ -0,0 +1,33 @@
+//! The `summarize` subcommand: the aggregate statistics of a `--dump-counts`
+//! table, to compare the effect of thresholds without reparsing the GAF.
+
+use anyhow::Result;
+use clap::ArgMatches;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::gaf::{summarize_counts, CountsSummary};
+
+/// Run the `summarize` subcommand.
+pub fn run(args: &ArgMatches) -> Result<()> {
+    let summary = summarize_counts(args.get_one::<PathBuf>("COUNTS").expect("COUNTS required"))?;
+    let precision = *args.get_one::<usize>("precision").unwrap();
+
+    write_summary(&summary, precision, io::stdout().lock())
+}
+
+/// Write the summary with the same labels as the GAF stage.
+pub fn write_summary<W: Write>(
+    summary: &CountsSummary,
+    precision: usize,
+    mut out: W,
+) -> Result<()> {
+    writeln!(out, "Mean entropy: {:.*}", precision, summary.mean_entropy)?;
+    writeln!(
+        out,
+        "Total entropy: {:.*}",
+        precision, summary.total_entropy
+    )?;
+    writeln!(out, "RCI: {:.*}", precision, summary.rci)?;
+    Ok(())
+}
@@ -0,0 +1,156 @@
+//! Long-format output of the per-repeat results (`--format long`): one
+//! `repeat`, `metric`, `value` row per statistic, for tidyverse-style analysis
+//! without joining the wide tables.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::gaf::{EntropyRecord, GafReport};
+use crate::manifest::Manifest;
+
+/// Write `report`'s per-repeat statistics as a long table to `path`.
+pub fn write_long_table(
+    path: &Path,
+    report: &GafReport,
+    precision: usize,
+    manifest: &mut Manifest,
+) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create long table: {:?}", path.as_os_str()))?;
+    write_long(BufWriter::new(file), report, precision)?;
+    manifest.add("long_table", path);
+    Ok(())
+}
+
+/// The value of a metric, `NA` if it is undefined for the repeat.
+enum Value {
+    Count(u64),
+    Float(Option<f64>),
+}
+
+/// The metrics of a repeat's entropy row, in table order, given the scores of its
+/// pairs.
+fn metrics(repeat: &EntropyRecord, scores: &[f64]) -> [(&'static str, Value); 9] {
+    let normalized = repeat
+        .entropy
+        .filter(|_| repeat.path_count > 1)
+        .map(|h| h / (repeat.path_count as f64).log2());
+    let potential = (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64);
+    [
+        ("path_count", Value::Count(repeat.path_count as u64)),
+        ("total_cov", Value::Count(repeat.fwd_cov + repeat.rev_cov)),
+        ("fwd_cov", Value::Count(repeat.fwd_cov)),
+        ("rev_cov", Value::Count(repeat.rev_cov)),
+        ("entropy", Value::Float(repeat.entropy)),
+        ("normalized_entropy", Value::Float(normalized)),
+        ("top_path_frac", Value::Float(repeat.top_path_frac)),
+        ("n_pairs", Value::Count(scores.len() as u64)),
+        ("recomb_potential", Value::Float(potential)),
+    ]
+}
+
+/// Write the long table of `report`: for each row of its entropy table, one row per
+/// metric. With `--multi-focal`, a repeat's rows are told apart by a `:<focal_pos>`
+/// suffix, e.g. `u66:1`, and share the pair metrics of the repeat.
+fn write_long<W: Write>(mut out: W, report: &GafReport, precision: usize) -> Result<()> {
+    let mut scores: HashMap<String, Vec<f64>> = HashMap::new();
+    for pair in &report.pairs {
+        if let Some(repeat) = pair.repeat() {
+            scores.entry(repeat).or_default().push(pair.recomb_score);
+        }
+    }
+
+    writeln!(out, "repeat\tmetric\tvalue")?;
+    for repeat in &report.repeats {
+        let name = match repeat.focal_pos {
+            Some(pos) => format!("{}:{}", repeat.repeat_node, pos),
+            None => repeat.repeat_node.clone(),
+        };
+        let scores = scores
+            .get(&repeat.repeat_node)
+            .map_or(&[][..], Vec::as_slice);
+        for (metric, value) in metrics(repeat, scores) {
+            match value {
+                Value::Count(n) => writeln!(out, "{}\t{}\t{}", name, metric, n)?,
+                Value::Float(Some(x)) => {
+                    writeln!(out, "{}\t{}\t{:.*}", name, metric, precision, x)?
+                }
+                Value::Float(None) => writeln!(out, "{}\t{}\tNA", name, metric)?,
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gaf::PairRecord;
+
+    #[test]
+    fn test_long_table() {
+        let pair = |path_1: &str, path_2: &str, recomb_score| PairRecord {
+            path_1: path_1.into(),
+            cov_1: 10,
+            path_2: path_2.into(),
+            cov_2: 10,
+            recomb_score,
+            rel_1: None,
+            rel_2: None,
+            pvalue: None,
+            qvalue: None,
+            direction: None,
+            breakpoint: None,
+            focal_node: None,
+            focal_pos: None,
+            left_flank: None,
+            right_flank: None,
+            flank_distance: None,
+        };
+        let repeat = |repeat_node: &str, path_count, entropy| EntropyRecord {
+            repeat_node: repeat_node.into(),
+            path_count,
+            entropy,
+            fwd_cov: 30,
+            rev_cov: 10,
+            top_path: None,
+            top_path_frac: entropy.map(|_| 0.5),
+            focal_pos: None,
+            n_pairs: None,
+        };
+        let report = GafReport {
+            pairs: vec![
+                pair(">a>u66>b", "<b<u66<a", 1.0),
+                pair(">a>u66>c", "<c<u66<a", 0.5),
+            ],
+            repeats: vec![repeat("u66", 4, Some(1.5)), repeat("u69", 0, None)],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        write_long(&mut out, &report, 3).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = out.lines().collect();
+        assert_eq!(rows.len(), 1 + 2 * 9);
+        assert_eq!(
+            rows[..10],
+            [
+                "repeat\tmetric\tvalue",
+                "u66\tpath_count\t4",
+                "u66\ttotal_cov\t40",
+                "u66\tfwd_cov\t30",
+                "u66\trev_cov\t10",
+                "u66\tentropy\t1.500",
+                "u66\tnormalized_entropy\t0.750",
+                "u66\ttop_path_frac\t0.500",
+                "u66\tn_pairs\t2",
+                "u66\trecomb_potential\t0.750",
+            ]
+        );
+        assert!(rows.contains(&"u69\tentropy\tNA"));
+        assert!(rows.contains(&"u69\tn_pairs\t0"));
+    }
+}
@@ -0,0 +1,55 @@
+//! Wall-clock durations of the main phases of a run, printed with `--timings`.
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// Durations of the phases recorded so far, in the order they first ran.
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    /// Record the time elapsed since `start` under `phase`. A phase that runs more
+    /// than once accumulates its durations.
+    pub fn record(&mut self, phase: &'static str, start: Instant) {
+        let elapsed = start.elapsed();
+        match self.phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += elapsed,
+            None => self.phases.push((phase, elapsed)),
+        }
+    }
+
+    /// Write one `[timings] <phase>: <seconds>s` line per phase, and the total.
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        for (phase, duration) in &self.phases {
+            writeln!(out, "[timings] {}: {:.3}s", phase, duration.as_secs_f64())?;
+        }
+        let total: Duration = self.phases.iter().map(|(_, d)| *d).sum();
+        writeln!(out, "[timings] total: {:.3}s", total.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timings_accumulate() {
+        let mut timings = Timings::default();
+        let start = Instant::now();
+        timings.record("graph load", start);
+        timings.record("detection", start);
+        timings.record("graph load", start);
+
+        let phases: Vec<_> = timings.phases.iter().map(|(name, _)| *name).collect();
+        assert_eq!(phases, ["graph load", "detection"]);
+
+        let mut out = Vec::new();
+        timings.write(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 3);
+        assert!(out.starts_with("[timings] graph load: "));
+        assert!(out.ends_with("s\n"));
+    }
+}
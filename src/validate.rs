@@ -0,0 +1,218 @@
+//! The `validate` subcommand: check that a GFA meets this tool's assumptions
+//! before running the analysis.
+
+use anyhow::{Context, Result};
+use bstr::{io::*, ByteSlice};
+use clap::ArgMatches;
+use gfa::{
+    gfa::Line,
+    optfields::{OptField, OptFieldVal, OptFields},
+    parser::GFAParser,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+/// A problem found on a line of the GFA.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// 1-based line number.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Run the `validate` subcommand, failing if the GFA has any violations.
+pub fn run(args: &ArgMatches) -> Result<()> {
+    let path = args.get_one::<PathBuf>("GFA").expect("GFA required");
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open GFA file: {:?}", path.as_os_str()))?;
+    let violations = validate(BufReader::new(file))?;
+
+    let mut out = io::stdout().lock();
+    for violation in &violations {
+        writeln!(out, "{}", violation)?;
+    }
+    out.flush()?;
+    if !violations.is_empty() {
+        anyhow::bail!(
+            "GFA failed validation with {} violation(s)",
+            violations.len()
+        );
+    }
+    eprintln!("GFA is valid");
+    Ok(())
+}
+
+/// Check every line of a GFA, returning all violations in line order:
+///
+/// - lines that can't be parsed as `H`, `S`, `L`, `C` or `P` records
+/// - segment names defined more than once
+/// - links, containments and paths referencing undefined segments
+/// - segments whose `LN` tag disagrees with their sequence length
+///
+/// Blank lines, `#` comments and other record types are skipped.
+pub fn validate<R: BufRead>(reader: R) -> Result<Vec<Violation>> {
+    let parser: GFAParser<Vec<u8>, Vec<OptField>> = GFAParser::new();
+    let mut violations = Vec::new();
+    // segment name -> line it was first defined on
+    let mut segments: HashMap<Vec<u8>, usize> = HashMap::new();
+    // (line, record type, segment) references checked once all segments are known
+    let mut references: Vec<(usize, &'static str, Vec<u8>)> = Vec::new();
+
+    for (i, line) in reader.byte_lines().enumerate() {
+        let line = line?;
+        let line = crate::gfa::clean_line(&line, i == 0);
+        let line_no = i + 1;
+        let record_type = match line.trim().first() {
+            None | Some(b'#') => continue,
+            Some(c @ (b'H' | b'S' | b'L' | b'C' | b'P')) => *c as char,
+            Some(_) => continue,
+        };
+        let parsed = match parser.parse_gfa_line(line) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                violations.push(Violation {
+                    line: line_no,
+                    message: format!("malformed {} line: {}", record_type, e),
+                });
+                continue;
+            }
+        };
+
+        match parsed {
+            Line::Segment(segment) => {
+                let name = segment.name.to_str_lossy().into_owned();
+                if let Some(first) = segments.get(&segment.name) {
+                    violations.push(Violation {
+                        line: line_no,
+                        message: format!(
+                            "duplicate segment name {} (first defined on line {})",
+                            name, first
+                        ),
+                    });
+                } else {
+                    segments.insert(segment.name.clone(), line_no);
+                }
+                let length = segment.optional.get_field(b"LN").map(|f| &f.value);
+                if let Some(OptFieldVal::Int(length)) = length {
+                    let sequence = segment.sequence.len() as i64;
+                    if segment.sequence != b"*" && *length != sequence {
+                        violations.push(Violation {
+                            line: line_no,
+                            message: format!(
+                                "segment {} has LN:i:{} but a {} bp sequence",
+                                name, length, sequence
+                            ),
+                        });
+                    }
+                }
+            }
+            Line::Link(link) => {
+                references.push((line_no, "link", link.from_segment));
+                references.push((line_no, "link", link.to_segment));
+            }
+            Line::Containment(containment) => {
+                references.push((line_no, "containment", containment.container_name));
+                references.push((line_no, "containment", containment.contained_name));
+            }
+            Line::Path(path) => {
+                for (segment, _) in path.iter() {
+                    references.push((line_no, "path", segment.to_vec()));
+                }
+            }
+            Line::Header(_) => {}
+        }
+    }
+
+    for (line, kind, segment) in references {
+        if !segments.contains_key(&segment) {
+            violations.push(Violation {
+                line,
+                message: format!(
+                    "{} references undefined segment {}",
+                    kind,
+                    segment.to_str_lossy()
+                ),
+            });
+        }
+    }
+    violations.sort_by_key(|v| v.line);
+
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(lines: &[&str]) -> Vec<String> {
+        validate(lines.join("\n").as_bytes())
+            .unwrap()
+            .iter()
+            .map(|v| v.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_gfa() {
+        let violations = check(&[
+            "H\tVN:Z:1.0",
+            "# a comment",
+            "S\ta\tACGT\tLN:i:4",
+            "S\tb\t*\tLN:i:100",
+            "",
+            "L\ta\t+\tb\t-\t0M",
+            "P\tp1\ta+,b-\t*",
+        ]);
+        assert!(violations.is_empty(), "{:?}", violations);
+    }
+
+    #[test]
+    fn test_duplicate_segment() {
+        assert_eq!(
+            check(&["S\ta\tACGT", "S\tb\tACGT", "S\ta\tAC"]),
+            vec!["line 3: duplicate segment name a (first defined on line 1)"]
+        );
+    }
+
+    #[test]
+    fn test_dangling_references() {
+        assert_eq!(
+            check(&[
+                "S\ta\tACGT",
+                "L\ta\t+\tmissing\t+\t0M",
+                "C\tgone\t+\ta\t-\t1\t2M",
+                "P\tp1\ta+,lost-\t*",
+            ]),
+            vec![
+                "line 2: link references undefined segment missing",
+                "line 3: containment references undefined segment gone",
+                "line 4: path references undefined segment lost",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_length_tag_mismatch() {
+        assert_eq!(
+            check(&["S\ta\tACGT\tLN:i:5", "S\tb\tACGT\tLN:i:4"]),
+            vec!["line 1: segment a has LN:i:5 but a 4 bp sequence"]
+        );
+    }
+
+    #[test]
+    fn test_malformed_line() {
+        let violations = check(&["S\ta\tACGT", "L\ta\t+", "S\tb"]);
+        assert_eq!(violations.len(), 2);
+        assert!(violations[0].starts_with("line 2: malformed L line"));
+        assert!(violations[1].starts_with("line 3: malformed S line"));
+    }
+}
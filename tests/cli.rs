@@ -0,0 +1,442 @@
+//! End-to-end tests running the `gfa_recomb` binary.
+
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gfa_recomb"))
+        .args(args)
+        .output()
+        .expect("failed to run gfa_recomb")
+}
+
+#[test]
+fn empty_result_exits_zero_by_default() {
+    let out = run(&["tests/data/linear.gfa"]);
+    assert_eq!(out.status.code(), Some(0));
+    assert!(out.stdout.is_empty());
+}
+
+#[test]
+fn empty_result_exit_code_with_empty_is_error() {
+    let out = run(&["--empty-is-error", "tests/data/linear.gfa"]);
+    assert_eq!(out.status.code(), Some(3));
+}
+
+#[test]
+fn candidates_found_exit_zero_with_empty_is_error() {
+    let out = run(&["--empty-is-error", "data/Arabidopsis_thaliana.mito.gfa"]);
+    assert_eq!(out.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&out.stdout).starts_with("ID\tSize"));
+}
+
+#[test]
+fn missing_input_is_a_genuine_error() {
+    let out = run(&["--empty-is-error", "tests/data/does_not_exist.gfa"]);
+    assert_eq!(out.status.code(), Some(1));
+}
+
+#[test]
+fn simulated_graph_and_gaf_round_trip() {
+    let dir = std::env::temp_dir().join(format!("gfa_recomb_cli_sim_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let gfa = dir.join("sim.gfa");
+    let gaf = dir.join("sim.gaf");
+
+    let out = run(&[
+        "simulate",
+        "--repeats",
+        "2",
+        "--coverage",
+        "4",
+        "-o",
+        gfa.to_str().unwrap(),
+        "-g",
+        gaf.to_str().unwrap(),
+    ]);
+    assert!(out.status.success());
+
+    let out = run(&["-g", gaf.to_str().unwrap(), gfa.to_str().unwrap()]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(out.status.success());
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    // 2 repeats x 2 in-flanks x 2 out-flanks, each seen in both directions
+    let pairs = stdout.lines().skip(1).take_while(|l| !l.is_empty()).count();
+    assert_eq!(pairs, 8);
+    assert!(stdout.contains("Recombination potential: 1.000"));
+}
+
+#[test]
+fn precision_applies_to_gaf_stage_floats() {
+    let out = run(&[
+        "--precision",
+        "6",
+        "-g",
+        "data/Arabidopsis_thaliana.gaf",
+        "data/Arabidopsis_thaliana.mito.gfa",
+    ]);
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+
+    let decimals = |value: &str| value.split_once('.').map(|(_, d)| d.len());
+    let rci = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("RCI: "))
+        .unwrap();
+    assert_eq!(decimals(rci), Some(6));
+    let mean = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("Mean entropy: "))
+        .unwrap();
+    assert_eq!(decimals(mean), Some(6));
+    let score = stdout.lines().nth(1).unwrap().rsplit('\t').next().unwrap();
+    assert_eq!(decimals(score), Some(6));
+}
+
+#[test]
+fn manifest_lists_every_written_file() {
+    let dir = std::env::temp_dir().join(format!("gfa_recomb_cli_manifest_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+    let out = run(&[
+        "-g",
+        "data/Arabidopsis_thaliana.gaf",
+        "--dump-counts",
+        &path("counts.tsv"),
+        "--split-dir",
+        &path("split"),
+        "--graphml",
+        &path("repeats.graphml"),
+        "--manifest",
+        &path("manifest.json"),
+        "data/Arabidopsis_thaliana.mito.gfa",
+    ]);
+    assert!(out.status.success());
+    let manifest = std::fs::read_to_string(path("manifest.json")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+    let entries: Vec<(String, Option<String>, String)> = manifest["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| {
+            (
+                f["type"].as_str().unwrap().to_string(),
+                f["repeat"].as_str().map(String::from),
+                f["path"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    let split = |repeat: &str| {
+        (
+            "per_repeat".to_string(),
+            Some(repeat.to_string()),
+            dir.join("split")
+                .join(format!("{repeat}.tsv"))
+                .to_str()
+                .unwrap()
+                .to_string(),
+        )
+    };
+    assert_eq!(
+        entries,
+        vec![
+            ("graphml".to_string(), None, path("repeats.graphml")),
+            ("counts".to_string(), None, path("counts.tsv")),
+            split("u66"),
+            split("u69"),
+        ]
+    );
+}
+
+#[test]
+fn no_pairs_keeps_aggregates() {
+    let args = [
+        "-g",
+        "data/Arabidopsis_thaliana.gaf",
+        "data/Arabidopsis_thaliana.mito.gfa",
+    ];
+    let full = String::from_utf8(run(&args).stdout).unwrap();
+    let out = run(&[&["--no-pairs"], &args[..]].concat());
+    assert!(out.status.success());
+    let summary = String::from_utf8(out.stdout).unwrap();
+
+    assert!(full.contains("path_1\t"));
+    assert!(!summary.contains("path_1\t"));
+    assert!(!summary.contains("<u67<u66>u65"));
+    for prefix in ["Recombination potential: ", "RCI: ", "Mean entropy: "] {
+        let value = |s: &str| s.lines().find(|l| l.starts_with(prefix)).map(String::from);
+        assert_eq!(value(&summary), value(&full));
+        assert!(value(&full).is_some());
+    }
+}
+
+#[test]
+fn inout_zero_warns_unless_allowed() {
+    let out = run(&["--inout", "0", "tests/data/linear.gfa"]);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Warning: --inout 0"));
+
+    let out = run(&["--inout", "0", "--allow-trivial", "tests/data/linear.gfa"]);
+    assert!(out.status.success());
+    assert!(out.stderr.is_empty());
+
+    let out = run(&["--inout", "1", "tests/data/linear.gfa"]);
+    assert!(out.stderr.is_empty());
+}
+
+#[test]
+fn validate_reports_violations() {
+    let out = run(&["validate", "data/Arabidopsis_thaliana.mito.gfa"]);
+    assert_eq!(out.status.code(), Some(0));
+    assert!(out.stdout.is_empty());
+
+    let path = std::env::temp_dir().join(format!("gfa_recomb_cli_bad_{}.gfa", std::process::id()));
+    std::fs::write(&path, "S\ta\tACGT\nS\ta\tACGT\nL\ta\t+\tb\t+\t0M\n").unwrap();
+    let out = run(&["validate", path.to_str().unwrap()]);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout),
+        "line 2: duplicate segment name a (first defined on line 1)\n\
+         line 3: link references undefined segment b\n"
+    );
+}
+
+#[test]
+fn degrees_lists_every_segment() {
+    let out = run(&["degrees", "tests/data/linear.gfa"]);
+    assert_eq!(out.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "ID\tSize\tin_degree\tout_degree\tdegree\n\
+         b\t8\t1\t1\t2\n\
+         a\t8\t0\t1\t1\n\
+         c\t8\t1\t0\t1\n"
+    );
+}
+
+#[test]
+fn with_neighbors_lists_candidate_adjacency() {
+    let path = std::env::temp_dir().join(format!("gfa_recomb_cli_nb_{}.gfa", std::process::id()));
+    let flank = "A".repeat(20);
+    let gfa = format!(
+        "S\tR\tACGT\nS\ta\t{f}\nS\tb\t{f}\nS\tc\t{f}\nS\td\t{f}\n\
+         L\ta\t+\tR\t+\t0M\nL\tR\t+\tb\t+\t0M\nL\tc\t+\tR\t+\t0M\nL\tR\t+\td\t-\t0M\n",
+        f = flank
+    );
+    std::fs::write(&path, gfa).unwrap();
+    let args = ["-r", "10", "-n", "10", path.to_str().unwrap()];
+    let plain = run(&args);
+    let out = run(&[&args[..], &["--with-neighbors"]].concat());
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(String::from_utf8(plain.stdout).unwrap(), "ID\tSize\nR\t4\n");
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "ID\tSize\tneighbors\nR\t4\ta+,b+,c+,d-\n"
+    );
+}
+
+#[test]
+fn timings_are_printed_only_when_asked() {
+    let out = run(&["data/Arabidopsis_thaliana.mito.gfa"]);
+    assert!(!String::from_utf8_lossy(&out.stderr).contains("[timings]"));
+
+    let out = run(&["--timings", "data/Arabidopsis_thaliana.mito.gfa"]);
+    assert!(out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    for phase in ["graph load", "indexing", "detection", "total"] {
+        assert!(
+            stderr.contains(&format!("[timings] {}: ", phase)),
+            "{}",
+            stderr
+        );
+    }
+}
+
+#[test]
+fn max_candidates_keeps_the_largest() {
+    let out = run(&[
+        "--max-candidates",
+        "1",
+        "data/Arabidopsis_thaliana.mito.gfa",
+    ]);
+    assert_eq!(out.status.code(), Some(2));
+
+    let out = run(&[
+        "--sort",
+        "size",
+        "--max-candidates",
+        "1",
+        "data/Arabidopsis_thaliana.mito.gfa",
+    ]);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout),
+        "ID\tSize\nu69\t4731\n"
+    );
+    assert!(String::from_utf8_lossy(&out.stderr)
+        .contains("Note: 1 candidate(s) suppressed by --max-candidates 1"));
+}
+
+#[test]
+fn empty_gaf_is_reported() {
+    let dir = std::env::temp_dir().join(format!("gfa_recomb_cli_empty_gaf_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let empty = dir.join("empty.gaf");
+    let comments = dir.join("comments.gaf");
+    std::fs::write(&empty, "").unwrap();
+    std::fs::write(&comments, "# no alignments\n\n").unwrap();
+
+    for gaf in [&empty, &comments] {
+        let gaf = gaf.to_str().unwrap();
+        let out = run(&["-g", gaf, "data/Arabidopsis_thaliana.mito.gfa"]);
+        assert_eq!(out.status.code(), Some(0));
+        assert!(String::from_utf8_lossy(&out.stderr)
+            .contains("Warning: GAF contained no usable alignments"));
+
+        let out = run(&[
+            "--empty-is-error",
+            "-g",
+            gaf,
+            "data/Arabidopsis_thaliana.mito.gfa",
+        ]);
+        assert_eq!(out.status.code(), Some(4));
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn query_reports_only_the_requested_flanks() {
+    let out = run(&[
+        "-g",
+        "data/Arabidopsis_thaliana.gaf",
+        "--query",
+        "u65",
+        "u66",
+        "u67",
+        "data/Arabidopsis_thaliana.mito.gfa",
+    ]);
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.starts_with("path_1\tcov_1\tpath_2\tcov_2\trecomb_score\n"));
+    assert_eq!(stdout.lines().filter(|l| l.contains("u66")).count(), 1);
+    assert!(!stdout.contains("entropy"));
+}
+
+#[test]
+fn summarize_reproduces_the_gaf_aggregates() {
+    let counts = std::env::temp_dir().join(format!(
+        "gfa_recomb_cli_summarize_{}.tsv",
+        std::process::id()
+    ));
+    let counts = counts.to_str().unwrap();
+    let out = run(&[
+        "-g",
+        "data/Arabidopsis_thaliana.gaf",
+        "--dump-counts",
+        counts,
+        "--precision",
+        "6",
+        "data/Arabidopsis_thaliana.mito.gfa",
+    ]);
+    assert!(out.status.success());
+    let full = String::from_utf8(out.stdout).unwrap();
+    let out = run(&["summarize", "--precision", "6", counts]);
+    std::fs::remove_file(counts).unwrap();
+    assert!(out.status.success());
+    let summary = String::from_utf8(out.stdout).unwrap();
+
+    let expected: Vec<&str> = ["Mean entropy: ", "Total entropy: ", "RCI: "]
+        .iter()
+        .map(|prefix| full.lines().find(|l| l.starts_with(prefix)).unwrap())
+        .collect();
+    assert_eq!(summary.lines().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn batch_summarizes_each_sample() {
+    let dir = std::env::temp_dir().join(format!("gfa_recomb_cli_batch_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    for (sample, coverage) in [("a", "4"), ("b", "6")] {
+        let out = run(&[
+            "simulate",
+            "--repeats",
+            "2",
+            "--coverage",
+            coverage,
+            "-o",
+            &path(&format!("{}.gfa", sample)),
+            "-g",
+            &path(&format!("{}.gaf", sample)),
+        ]);
+        assert!(out.status.success());
+    }
+    std::fs::write(path("nodes.txt"), "r1\nr2\n").unwrap();
+    // relative to the manifest
+    std::fs::write(
+        path("manifest.tsv"),
+        "a.gfa\ta.gaf\tnodes.txt\nb.gfa\tb.gaf\tnodes.txt\n",
+    )
+    .unwrap();
+
+    let out = run(&["batch", &path("manifest.tsv"), "--", "--precision", "2"]);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "sample\tpairs\trecombination_potential\trci\trecombination_rate\tmean_entropy\ttotal_entropy\n\
+         a\t8\t1.0\t3.0\t1.0\t3.0\t6.0\n\
+         b\t8\t1.0\t3.0\t1.0\t3.0\t6.0\n"
+    );
+
+    // a failing row is reported at the end, after the others
+    std::fs::write(
+        path("manifest.tsv"),
+        "a.gfa\tmissing.gaf\tnodes.txt\nb.gfa\tb.gaf\tnodes.txt\n",
+    )
+    .unwrap();
+    let out = run(&["batch", &path("manifest.tsv")]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(String::from_utf8(out.stdout).unwrap().lines().count(), 2);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.contains("Error: sample missing (manifest line 1)"));
+    assert!(stderr.contains("1 of 2 batch row(s) failed"));
+}
+
+#[test]
+fn merge_combines_entropy_tables() {
+    let dir = std::env::temp_dir().join(format!("gfa_recomb_cli_merge_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let report = |name: &str, rows: &str| {
+        let path = dir.join(name);
+        std::fs::write(
+            &path,
+            format!(
+                "Recombination potential: 1.000\nRCI: 1.000\n\n\
+                 repeat_node\tpath_count\tentropy\tfwd_cov\trev_cov\ttop_path\ttop_path_frac\n\
+                 {}\nMean entropy: 1.000\n",
+                rows
+            ),
+        )
+        .unwrap();
+        path.to_str().unwrap().to_string()
+    };
+    let a = report("a.tsv", "u1\t2\t1.000\t5\t5\t>x>u1>y\t0.500\n");
+    let b = report("b.tsv", "u2\t2\t0.500\t5\t5\t>x>u2>y\t0.800\n");
+
+    let out = run(&["merge", &a, &b]);
+    assert_eq!(out.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "repeat_node\ta\tb\nu1\t1.000\tNA\nu2\tNA\t0.500\n"
+    );
+    let out = run(&["merge", "--names", "before,after", &a, &b]);
+    assert!(String::from_utf8(out.stdout)
+        .unwrap()
+        .starts_with("repeat_node\tbefore\tafter\n"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}